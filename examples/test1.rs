@@ -11,9 +11,9 @@
 //
 //============================================================================
 
-extern crate "rust-tiff" as tiff;
+extern crate rust_tiff as tiff;
 
-use std::os;
+use std::env;
 
 use tiff::reader::TIFFReader;
 
@@ -22,8 +22,8 @@ use tiff::reader::TIFFReader;
 
 fn main() {
     let tiff_reader = TIFFReader;
-    match tiff_reader.load(os::args()[1].as_slice()) {
-        Ok(x) => println!("Read tiff {}", x),
+    match tiff_reader.load(&env::args().nth(1).unwrap()) {
+        Ok(x) => println!("Read tiff {:?}", x.header),
         Err(e) => println!("File I/O Error: {}", e),
     }
 }
@@ -6,45 +6,90 @@ use std::env;
 fn print_header(x: &tiff::TIFFHeader) {
     println!("byte_order: {:?}", x.byte_order);
     println!("magic:      {:?}", x.magic);
+    println!("version:    {:?}", x.version);
     println!("ifd_offset: {}", x.ifd_offset);
 }
 
+// List the fields of an IFD, checking known tags against the spec
+fn print_ifd(ifd: &tiff::IFD, version: tiff::TIFFVersion) {
+    for e in &ifd.entries {
+        let tag = tiff::decode_tag(e.tag);
+        match tag {
+            Some(tag) => println!("  tag: {:?} type: {:?} count: {} offset: {:08x}", tag, e.typ, e.count, e.value_offset),
+            None => println!("  tag: {:04x} (unknown) type: {:?} count: {} offset: {:08x}",
+                             e.tag, e.typ, e.count, e.value_offset),
+        }
+
+        if let Some((expected_typ, expected_count)) = tag.and_then(tiff::type_and_count_for_tag) {
+            let valid_short_or_long = expected_typ == tiff::TagType::ShortOrLongTag &&
+                (e.typ == tiff::TagType::ShortTag || e.typ == tiff::TagType::LongTag);
+
+            // BigTIFF allows LONG8 wherever LONG is permitted
+            let valid_long8 = version == tiff::TIFFVersion::BigTIFF && e.typ == tiff::TagType::Long8 &&
+                (expected_typ == tiff::TagType::LongTag || expected_typ == tiff::TagType::ShortOrLongTag);
+
+            // Supplement 1: IFD may be used in place of LONG for offsets
+            let valid_ifd = e.typ == tiff::TagType::IFDTag && expected_typ == tiff::TagType::LongTag;
+
+            if !valid_short_or_long && !valid_long8 && !valid_ifd && e.typ != expected_typ {
+                println!("    *** ERROR: expected typ: {:?} found: {:?}", expected_typ, e.typ);
+            }
+            if expected_count != 0 && e.count != expected_count as u64 {
+                println!("    *** ERROR: expected count: {:?} found: {:?}", expected_count, e.count);
+            }
+        }
+
+        if e.count == 1 {
+            println!("    {:?}", e.value());
+        }
+    }
+    for sub_ifd in &ifd.sub_ifds {
+        println!("  SubIFD at {}:", sub_ifd.offset);
+        print_ifd(sub_ifd, version);
+    }
+}
+
 fn main() {
     let tiff_reader = tiff::TIFFReader;
     let file_name = env::args().nth(1).unwrap();
     println!("tiffinfo: {}", file_name);
     match tiff_reader.load(&file_name) {
         Ok(h) => {
-            println!("Read tiff {:?}", h);
-            print_header(&*h);
+            print_header(&h.header);
+            println!("ifd_count:  {}", h.ifds.len());
+            for ifd in &h.ifds {
+                println!("IFD at {}:", ifd.offset);
+                print_ifd(ifd, h.header.version);
+                if let Some(res) = ifd.resolution() {
+                    println!("resolution: {}, {} {:?}", res.x, res.y, res.unit);
+                }
+            }
+
+            // Check each image against the fields its type requires
+            println!("Image validity test:");
+            for (i, ifd) in h.ifds.iter().enumerate() {
+                let typ = match ifd.image_type() {
+                    Some(typ) => typ,
+                    None => {
+                        println!("  IFD {}: unknown image type", i);
+                        continue;
+                    },
+                };
+                match tiff::validate_required_tags_for(&typ, ifd) {
+                    Some(missing_tags) => {
+                        println!("  IFD {} {:?}: invalid, missing:", i, typ);
+                        for x in &missing_tags {
+                            println!("    - {:?}", x);
+                        }
+                    },
+                    None => {
+                        println!("  IFD {} {:?}: Valid ./", i, typ);
+                    },
+                }
+            }
         },
         Err(e) => {
             println!("File I/O Error: {}", e);
         }
     }
-
-    // Test image profile validation
-    let all_types = [
-        tiff::ImageType::Bilevel,
-        tiff::ImageType::Grayscale,
-        tiff::ImageType::PaletteColour,
-        tiff::ImageType::RGB,
-        tiff::ImageType::YCbCr];
-    println!("Image validity test:");
-    for typ in all_types.iter() {
-
-        let ans = tiff::validate_required_tags_for(typ);
-        match ans {
-            Some(missing_tags) => {
-                println!("  {:?}: invalid, missing:", typ);
-                for x in &missing_tags {
-                    println!("    - {:?}", x);
-                }
-            },
-            None => {
-                println!("  {:?}: Valid ./", typ);
-            },
-        }
-    }
-
 }
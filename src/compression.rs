@@ -0,0 +1,437 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, Read, Write};
+use std::collections::HashMap;
+
+use flate2::Compression as DeflateLevel;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use {Compression, Predictor, TIFFByteOrder};
+
+//----------------------------------------------------------------------------
+// Compression
+
+pub fn compress(compression: Compression, data: &[u8]) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(data.to_vec()),
+        Compression::PackBits => Ok(packbits_encode(data)),
+        Compression::LZW => Ok(lzw_encode(data)),
+        Compression::Deflate | Compression::DeflateOld => deflate_encode(data),
+        _ => Err(Error::other(format!("Unsupported compression {:?}", compression))),
+    }
+}
+
+// Decompress a strip or tile, expected to expand to `size` bytes.
+pub fn decompress(compression: Compression, data: &[u8], size: usize) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(data.to_vec()),
+        Compression::PackBits => Ok(packbits_decode(data, size)),
+        Compression::LZW => lzw_decode(data, size),
+        Compression::Deflate | Compression::DeflateOld => deflate_decode(data, size),
+        _ => Err(Error::other(format!("Unsupported compression {:?}", compression))),
+    }
+}
+
+// The most bytes that one stored byte can decompress to, bounding the size
+// a strip or tile may claim.  A PackBits run of 128 bytes takes 2 bytes, an
+// LZW code of 9 or more bits stands for at most one string per table entry,
+// and Deflate expands by at most 1032 times.
+pub fn max_expansion(compression: Compression) -> u64 {
+    match compression {
+        Compression::None => 1,
+        Compression::PackBits => 64,
+        Compression::LZW => LZW_TABLE_SIZE as u64 * 8 / 9,
+        Compression::Deflate | Compression::DeflateOld => 1032,
+        _ => u64::MAX,
+    }
+}
+
+/*
+    Section 9: PackBits Compression
+
+    Each run begins with a header byte n:
+
+        0 to 127     copy the next n + 1 bytes literally
+        -127 to -1   repeat the next byte -n + 1 times
+        -128         no operation
+*/
+
+pub fn packbits_encode(data: &[u8]) -> Vec<u8> {
+
+    let mut result = Vec::with_capacity(data.len() + data.len() / 128 + 1);
+    let mut i = 0;
+
+    while i < data.len() {
+        let mut run = 1;
+        while i + run < data.len() && run < 128 && data[i + run] == data[i] {
+            run += 1;
+        }
+
+        if run > 1 {
+            result.push((1 - run as i16) as u8);
+            result.push(data[i]);
+            i += run;
+        } else {
+            // Extend the literal until a run of 3 or more begins
+            let start = i;
+            while i < data.len() && i - start < 128 {
+                if i + 2 < data.len() && data[i] == data[i + 1] && data[i] == data[i + 2] {
+                    break;
+                }
+                i += 1;
+            }
+            result.push((i - start - 1) as u8);
+            result.extend_from_slice(&data[start..i]);
+        }
+    }
+
+    result
+}
+
+pub fn packbits_decode(data: &[u8], size: usize) -> Vec<u8> {
+
+    let mut result = Vec::with_capacity(size);
+    let mut i = 0;
+
+    while i < data.len() && result.len() < size {
+        let n = data[i] as i8;
+        i += 1;
+        if n >= 0 {
+            let end = (i + n as usize + 1).min(data.len());
+            result.extend_from_slice(&data[i..end]);
+            i = end;
+        } else if n != -128 && i < data.len() {
+            let count = 1 - n as isize;
+            result.extend(std::iter::repeat_n(data[i], count as usize));
+            i += 1;
+        }
+    }
+
+    result.truncate(size);
+    result
+}
+
+/*
+    Section 13: LZW Compression
+
+    Codes are written most significant bit first, starting at 9 bits and
+    growing to a maximum of 12.  The code width grows one code earlier than
+    in the original algorithm ("early change").
+*/
+
+const LZW_CLEAR: u16 = 256;
+const LZW_EOI: u16 = 257;
+const LZW_FIRST: u16 = 258;
+const LZW_MAX_BITS: u32 = 12;
+const LZW_TABLE_SIZE: usize = 1 << LZW_MAX_BITS;
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+
+    fn write(&mut self, code: u16, width: u32) {
+        self.buffer = (self.buffer << width) | code as u32;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.bytes.push((self.buffer >> self.bits) as u8);
+        }
+        self.buffer &= (1 << self.bits) - 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push((self.buffer << (8 - self.bits)) as u8);
+        }
+        self.bytes
+    }
+}
+
+pub fn lzw_encode(data: &[u8]) -> Vec<u8> {
+
+    let mut out = BitWriter { bytes: Vec::with_capacity(data.len() / 2), buffer: 0, bits: 0 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = LZW_FIRST;
+    let mut width = 9;
+
+    out.write(LZW_CLEAR, width);
+
+    let mut iter = data.iter();
+    let mut prefix = match iter.next() {
+        Some(&b) => b as u16,
+        None => {
+            out.write(LZW_EOI, width);
+            return out.finish();
+        },
+    };
+
+    for &b in iter {
+        if let Some(&code) = table.get(&(prefix, b)) {
+            prefix = code;
+            continue;
+        }
+
+        out.write(prefix, width);
+        table.insert((prefix, b), next_code);
+        next_code += 1;
+
+        if next_code as usize == LZW_TABLE_SIZE - 2 {
+            // Table full: start afresh
+            out.write(LZW_CLEAR, width);
+            table.clear();
+            next_code = LZW_FIRST;
+            width = 9;
+        } else if next_code == 1 << width {
+            width += 1;
+        }
+
+        prefix = b as u16;
+    }
+
+    out.write(prefix, width);
+    next_code += 1;
+    if next_code == 1 << width && width < LZW_MAX_BITS {
+        width += 1;
+    }
+    out.write(LZW_EOI, width);
+
+    out.finish()
+}
+
+pub fn lzw_decode(data: &[u8], size: usize) -> Result<Vec<u8>> {
+
+    // Each table entry is its prefix code and final byte; strings are
+    // rebuilt by following prefixes back to a single byte code.
+    let mut prefixes = vec![0u16; LZW_TABLE_SIZE];
+    let mut suffixes = vec![0u8; LZW_TABLE_SIZE];
+    let mut lengths = vec![1usize; LZW_TABLE_SIZE];
+    for (i, suffix) in suffixes.iter_mut().take(256).enumerate() {
+        *suffix = i as u8;
+    }
+
+    let mut result: Vec<u8> = Vec::with_capacity(size);
+    let mut next_code = LZW_FIRST;
+    let mut width = 9;
+    let mut previous: Option<u16> = None;
+
+    let mut buffer = 0u32;
+    let mut bits = 0u32;
+    let mut bytes = data.iter();
+
+    fn append(result: &mut Vec<u8>, code: u16, prefixes: &[u16], suffixes: &[u8], lengths: &[usize]) {
+        let start = result.len();
+        result.resize(start + lengths[code as usize], 0);
+        let mut c = code;
+        for i in (start..result.len()).rev() {
+            result[i] = suffixes[c as usize];
+            c = prefixes[c as usize];
+        }
+    }
+
+    while result.len() < size {
+
+        while bits < width {
+            match bytes.next() {
+                Some(&b) => {
+                    buffer = (buffer << 8) | b as u32;
+                    bits += 8;
+                },
+                None => return Ok(result),
+            }
+        }
+        bits -= width;
+        let code = ((buffer >> bits) & ((1 << width) - 1)) as u16;
+        buffer &= (1 << bits) - 1;
+
+        if code == LZW_EOI {
+            break;
+        }
+        if code == LZW_CLEAR {
+            next_code = LZW_FIRST;
+            width = 9;
+            previous = None;
+            continue;
+        }
+
+        let prev = match previous {
+            Some(p) => p,
+            None => {
+                if code >= LZW_CLEAR {
+                    return Err(Error::other("Invalid LZW code after clear"));
+                }
+                result.push(code as u8);
+                previous = Some(code);
+                continue;
+            },
+        };
+
+        let start = result.len();
+        if code < next_code {
+            append(&mut result, code, &prefixes, &suffixes, &lengths);
+        } else if code == next_code {
+            append(&mut result, prev, &prefixes, &suffixes, &lengths);
+            let first = result[start];
+            result.push(first);
+        } else {
+            return Err(Error::other(format!("Invalid LZW code {}", code)));
+        }
+
+        if (next_code as usize) < LZW_TABLE_SIZE {
+            prefixes[next_code as usize] = prev;
+            suffixes[next_code as usize] = result[start];
+            lengths[next_code as usize] = lengths[prev as usize] + 1;
+            next_code += 1;
+            if next_code == (1 << width) - 1 && width < LZW_MAX_BITS {
+                width += 1;
+            }
+        }
+        previous = Some(code);
+    }
+
+    result.truncate(size);
+    Ok(result)
+}
+
+// Deflate, as per the Adobe TIFF Technical Note, uses the zlib format.
+
+pub fn deflate_encode(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), DeflateLevel::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+pub fn deflate_decode(data: &[u8], size: usize) -> Result<Vec<u8>> {
+    let mut result = Vec::with_capacity(size);
+    ZlibDecoder::new(data).take(size as u64).read_to_end(&mut result)?;
+    Ok(result)
+}
+
+//----------------------------------------------------------------------------
+// Section 14: Differencing Predictor
+
+// Apply a predictor to each row of `data` in place.  Rows hold
+// `samples_per_row` samples with the given stride between the samples of
+// consecutive pixels; values are in `byte_order`.
+pub fn apply_predictor(predictor: Predictor, data: &mut [u8], samples_per_row: usize, stride: usize,
+                       bits: u16, byte_order: TIFFByteOrder) -> Result<()> {
+    predict(predictor, data, samples_per_row, stride, bits, byte_order, true)
+}
+
+pub fn undo_predictor(predictor: Predictor, data: &mut [u8], samples_per_row: usize, stride: usize,
+                      bits: u16, byte_order: TIFFByteOrder) -> Result<()> {
+    predict(predictor, data, samples_per_row, stride, bits, byte_order, false)
+}
+
+fn predict(predictor: Predictor, data: &mut [u8], samples_per_row: usize, stride: usize,
+           bits: u16, byte_order: TIFFByteOrder, encode: bool) -> Result<()> {
+
+    if predictor == Predictor::None {
+        return Ok(());
+    }
+    if bits == 0 || !bits.is_multiple_of(8) {
+        return Err(Error::other(format!("Predictor requires whole byte samples, not {} bits", bits)));
+    }
+
+    let size = bits as usize / 8;
+    let row_bytes = samples_per_row * size;
+
+    for row in data.chunks_mut(row_bytes) {
+        match predictor {
+            Predictor::Horizontal => {
+                if encode {
+                    for i in (stride..row.len() / size).rev() {
+                        let v = sample(row, i, size, byte_order).wrapping_sub(sample(row, i - stride, size, byte_order));
+                        set_sample(row, i, size, byte_order, v);
+                    }
+                } else {
+                    for i in stride..row.len() / size {
+                        let v = sample(row, i, size, byte_order).wrapping_add(sample(row, i - stride, size, byte_order));
+                        set_sample(row, i, size, byte_order, v);
+                    }
+                }
+            },
+            Predictor::FloatingPoint => {
+                if encode {
+                    shuffle_bytes(row, size, byte_order);
+                    for i in (stride..row.len()).rev() {
+                        row[i] = row[i].wrapping_sub(row[i - stride]);
+                    }
+                } else {
+                    for i in stride..row.len() {
+                        row[i] = row[i].wrapping_add(row[i - stride]);
+                    }
+                    unshuffle_bytes(row, size, byte_order);
+                }
+            },
+            Predictor::None => {},
+        }
+    }
+
+    Ok(())
+}
+
+fn sample(row: &[u8], i: usize, size: usize, byte_order: TIFFByteOrder) -> u64 {
+    let bytes = &row[i * size..(i + 1) * size];
+    let fold = |acc: u64, &b: &u8| (acc << 8) | b as u64;
+    match byte_order {
+        TIFFByteOrder::BigEndian => bytes.iter().fold(0, fold),
+        TIFFByteOrder::LittleEndian => bytes.iter().rev().fold(0, fold),
+    }
+}
+
+fn set_sample(row: &mut [u8], i: usize, size: usize, byte_order: TIFFByteOrder, value: u64) {
+    let bytes = &mut row[i * size..(i + 1) * size];
+    for k in 0..size {
+        let b = (value >> (8 * k)) as u8;
+        match byte_order {
+            TIFFByteOrder::BigEndian => bytes[size - 1 - k] = b,
+            TIFFByteOrder::LittleEndian => bytes[k] = b,
+        }
+    }
+}
+
+// The floating point predictor (Adobe Photoshop TIFF Technical Note 3)
+// regroups each row into byte planes, most significant bytes first.
+fn shuffle_bytes(row: &mut [u8], size: usize, byte_order: TIFFByteOrder) {
+    let count = row.len() / size;
+    let copy = row.to_vec();
+    for i in 0..count {
+        for b in 0..size {
+            let plane = match byte_order {
+                TIFFByteOrder::BigEndian => b,
+                TIFFByteOrder::LittleEndian => size - 1 - b,
+            };
+            row[plane * count + i] = copy[i * size + b];
+        }
+    }
+}
+
+fn unshuffle_bytes(row: &mut [u8], size: usize, byte_order: TIFFByteOrder) {
+    let count = row.len() / size;
+    let copy = row.to_vec();
+    for i in 0..count {
+        for b in 0..size {
+            let plane = match byte_order {
+                TIFFByteOrder::BigEndian => b,
+                TIFFByteOrder::LittleEndian => size - 1 - b,
+            };
+            row[i * size + b] = copy[plane * count + i];
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, SeekFrom};
+use std::collections::HashSet;
+
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
+
+use {TIFF, TIFFHeader, TIFFByteOrder, TIFFTag, TagType, TagSpace, IFD, IFDEntry, SeekableReader, SeekableWriter,
+     size_of_tag_type, SHORT};
+use exif::EXIFTag;
+use reader::{TIFFReader, read_bytes};
+use writer::{WrittenIFD, write_header, write_ifd, write_offset, first_ifd_position, prepare_entries, align};
+
+//----------------------------------------------------------------------------
+// Constants
+
+// Fields giving the position of data stored outside the IFD, each with the
+// field giving its length.
+const DATA_FIELDS: [(TIFFTag, TIFFTag); 4] = [
+    (TIFFTag::StripOffsetsTag, TIFFTag::StripByteCountsTag),
+    (TIFFTag::TileOffsetsTag, TIFFTag::TileByteCountsTag),
+    (TIFFTag::FreeOffsetsTag, TIFFTag::FreeByteCountsTag),
+    (TIFFTag::JPEGInterchangeFormatTag, TIFFTag::JPEGInterchangeFormatLengthTag),
+];
+
+//----------------------------------------------------------------------------
+
+// Write a parsed file back out unchanged apart from where things are stored.
+// Every field keeps its type, count and value, and image data is copied from
+// `source` as is, without recompressing.  SubIFDs are written from the
+// parsed `sub_ifds` of each IFD, so edits to them are kept; private IFDs such
+// as EXIF and GPS, and any SubIFDs that were not parsed, are copied from
+// `source`.  The byte order and version are those of the source.
+//
+// Offsets within opaque values, such as those in a MakerNote, cannot be
+// known and are not adjusted.
+pub fn copy_tiff(source: &mut dyn SeekableReader, tiff: &TIFF, writer: &mut dyn SeekableWriter) -> Result<()> {
+    match tiff.header.byte_order {
+        TIFFByteOrder::LittleEndian => copy_tiff_::<LittleEndian>(source, tiff, writer),
+        TIFFByteOrder::BigEndian => copy_tiff_::<BigEndian>(source, tiff, writer),
+    }
+}
+
+fn copy_tiff_<Endian: ByteOrder>(source: &mut dyn SeekableReader, tiff: &TIFF,
+                                 writer: &mut dyn SeekableWriter) -> Result<()> {
+
+    let header = &tiff.header;
+    write_header(writer, header.byte_order, header.version)?;
+
+    let mut copier = Copier {
+        source,
+        header,
+        visited: tiff.ifds.iter().map(|ifd| ifd.offset).collect(),
+    };
+
+    let mut link = first_ifd_position(header.version);
+    for ifd in &tiff.ifds {
+        let written = copier.copy_ifd::<Endian>(writer, ifd, TagSpace::TIFF)?;
+        write_offset::<Endian>(writer, link, written.offset, header.version)?;
+        link = written.next_offset_position;
+    }
+
+    writer.flush()
+}
+
+//----------------------------------------------------------------------------
+
+struct Copier<'a> {
+    source: &'a mut dyn SeekableReader,
+    header: &'a TIFFHeader,
+    // Source offsets of the IFDs copied so far, to guard against loops
+    visited: HashSet<u64>,
+}
+
+impl<'a> Copier<'a> {
+
+    // Copy an IFD's data and child IFDs, then the IFD itself, to the end of
+    // the file.
+    fn copy_ifd<Endian: ByteOrder>(&mut self, writer: &mut dyn SeekableWriter, ifd: &IFD,
+                                   space: TagSpace) -> Result<WrittenIFD> {
+
+        let mut entries = prepare_entries(&ifd.entries, self.header.byte_order);
+
+        for &(offsets_tag, counts_tag) in DATA_FIELDS.iter().filter(|_| space == TagSpace::TIFF) {
+            let index = match entries.iter().position(|e| e.tag == offsets_tag as u16) {
+                Some(index) => index,
+                None => continue,
+            };
+            let offsets = offsets_of(&entries[index])?;
+            let counts = entries.iter().find(|e| e.tag == counts_tag as u16).and_then(|e| e.as_u64s())
+                .ok_or_else(|| Error::other(format!("{:?} without {:?}", offsets_tag, counts_tag)))?;
+            if counts.len() != offsets.len() {
+                return Err(Error::other(format!("{} {:?} but {} {:?}", offsets.len(), offsets_tag,
+                                                counts.len(), counts_tag)));
+            }
+
+            let mut moved = Vec::with_capacity(offsets.len());
+            for (&offset, &count) in offsets.iter().zip(&counts) {
+                // Keep empty blocks, such as missing tiles, as they are
+                if count == 0 {
+                    moved.push(offset);
+                    continue;
+                }
+                let data = read_bytes(self.source, offset, count)?;
+                writer.seek(SeekFrom::End(0))?;
+                align(writer)?;
+                moved.push(writer.stream_position()?);
+                writer.write_all(&data)?;
+            }
+            set_offsets::<Endian>(&mut entries[index], &moved)?;
+        }
+
+        for e in entries.iter_mut() {
+            let child_space = match child_space(space, e) {
+                Some(child_space) => child_space,
+                None => continue,
+            };
+            let parsed: &[IFD] = if space == TagSpace::TIFF && e.tag == TIFFTag::SubIFDsTag as SHORT {
+                &ifd.sub_ifds
+            } else {
+                &[]
+            };
+            let offsets = offsets_of(e)?;
+            let moved = offsets.iter()
+                .map(|&offset| self.copy_chain::<Endian>(writer, offset, child_space, parsed))
+                .collect::<Result<Vec<_>>>()?;
+            set_offsets::<Endian>(e, &moved)?;
+        }
+
+        write_ifd::<Endian>(writer, self.header.byte_order, self.header.version, &entries)
+    }
+
+    // Copy a child IFD and any IFDs chained after it, returning the new
+    // offset of the first.  Each IFD is taken from `parsed` if it was read
+    // from that offset, and otherwise read from the source.
+    fn copy_chain<Endian: ByteOrder>(&mut self, writer: &mut dyn SeekableWriter, offset: u64,
+                                     space: TagSpace, parsed: &[IFD]) -> Result<u64> {
+
+        let mut first = 0;
+        let mut link = None;
+        let mut next = offset;
+
+        while next != 0 {
+            if !self.visited.insert(next) {
+                return Err(Error::other(format!("IFD at offset {} is referenced more than once", next)));
+            }
+            let read;
+            let ifd = match parsed.iter().find(|ifd| ifd.offset == next) {
+                Some(ifd) => ifd,
+                None => {
+                    read = TIFFReader.read_ifd(self.source, self.header, next)?;
+                    &read
+                },
+            };
+            let written = self.copy_ifd::<Endian>(writer, ifd, space)?;
+            match link {
+                Some(position) => write_offset::<Endian>(writer, position, written.offset, self.header.version)?,
+                None => first = written.offset,
+            }
+            link = Some(written.next_offset_position);
+            next = ifd.next_offset;
+        }
+
+        Ok(first)
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+// The tag space of the IFDs a field points to, if it points to any.  Fields
+// of type IFD are taken to point to IFDs of the same space.
+fn child_space(space: TagSpace, entry: &IFDEntry) -> Option<TagSpace> {
+    let tag = entry.tag;
+    match space {
+        TagSpace::TIFF if tag == TIFFTag::EXIFTag as SHORT => Some(TagSpace::EXIF),
+        TagSpace::TIFF if tag == TIFFTag::GPSTag as SHORT => Some(TagSpace::GPS),
+        TagSpace::TIFF if tag == TIFFTag::SubIFDsTag as SHORT => Some(TagSpace::TIFF),
+        TagSpace::EXIF if tag == EXIFTag::InteroperabilityIFDTag as SHORT => Some(TagSpace::Interoperability),
+        _ if entry.typ == TagType::IFDTag || entry.typ == TagType::IFD8 => Some(space),
+        _ => None,
+    }
+}
+
+fn offsets_of(entry: &IFDEntry) -> Result<Vec<u64>> {
+    entry.as_u64s().ok_or_else(|| {
+        Error::other(format!("Field {:04x} of type {:?} cannot hold offsets", entry.tag, entry.typ))
+    })
+}
+
+// Replace the values of an offset field, keeping its type.
+fn set_offsets<Endian: ByteOrder>(entry: &mut IFDEntry, offsets: &[u64]) -> Result<()> {
+    let size = size_of_tag_type(entry.typ);
+    let mut data = vec![0u8; offsets.len() * size];
+    for (c, &offset) in data.chunks_mut(size).zip(offsets) {
+        match entry.typ {
+            TagType::ShortTag if offset <= SHORT::MAX as u64 => Endian::write_u16(c, offset as u16),
+            TagType::LongTag | TagType::IFDTag if offset <= u32::MAX as u64 => Endian::write_u32(c, offset as u32),
+            TagType::Long8 | TagType::IFD8 => Endian::write_u64(c, offset),
+            _ => return Err(Error::other(format!("Offset {} does not fit field {:04x} of type {:?}",
+                                                 offset, entry.tag, entry.typ))),
+        }
+    }
+    entry.data = data;
+    Ok(())
+}
@@ -0,0 +1,241 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and H. S. Malvar, L. He and R. Cutler, "High-quality linear interpolation
+//  for demosaicing of Bayer-patterned color images", ICASSP 2004.
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, ErrorKind};
+
+use PhotometricInterpretation;
+use dng::{CFAPattern, RawImage, CFA_LAYOUT_RECTANGULAR};
+use image::{Image, ImageData};
+
+//----------------------------------------------------------------------------
+// Constants
+
+// Malvar-He-Cutler filters as (row offset, column offset, weight), with
+// weights in eighths.
+
+// Green at a red or blue pixel
+const GREEN_AT_RED_BLUE: [(isize, isize, f32); 9] = [
+    (0, 0, 4.0),
+    (-1, 0, 2.0), (1, 0, 2.0), (0, -1, 2.0), (0, 1, 2.0),
+    (-2, 0, -1.0), (2, 0, -1.0), (0, -2, -1.0), (0, 2, -1.0),
+];
+
+// Red or blue at a green pixel, for the colour on the same row
+const ROW_AT_GREEN: [(isize, isize, f32); 11] = [
+    (0, 0, 5.0),
+    (0, -1, 4.0), (0, 1, 4.0),
+    (0, -2, -1.0), (0, 2, -1.0),
+    (-1, -1, -1.0), (-1, 1, -1.0), (1, -1, -1.0), (1, 1, -1.0),
+    (-2, 0, 0.5), (2, 0, 0.5),
+];
+
+// Red or blue at a green pixel, for the colour on the same column
+const COLUMN_AT_GREEN: [(isize, isize, f32); 11] = [
+    (0, 0, 5.0),
+    (-1, 0, 4.0), (1, 0, 4.0),
+    (-2, 0, -1.0), (2, 0, -1.0),
+    (-1, -1, -1.0), (-1, 1, -1.0), (1, -1, -1.0), (1, 1, -1.0),
+    (0, -2, 0.5), (0, 2, 0.5),
+];
+
+// Red at a blue pixel, or blue at a red pixel
+const RED_BLUE_AT_BLUE_RED: [(isize, isize, f32); 9] = [
+    (0, 0, 6.0),
+    (-1, -1, 2.0), (-1, 1, 2.0), (1, -1, 2.0), (1, 1, 2.0),
+    (-2, 0, -1.5), (2, 0, -1.5), (0, -2, -1.5), (0, 2, -1.5),
+];
+
+//----------------------------------------------------------------------------
+// Types
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Demosaic {
+    // Average of the nearest samples of each colour
+    Bilinear,
+    // Bilinear, corrected by the gradient of the pixel's own colour; needs
+    // a 2x2 Bayer pattern
+    MalvarHeCutler,
+}
+
+//----------------------------------------------------------------------------
+
+impl RawImage {
+
+    // Interpolate CFA data to a 16-bit RGB image.  Each sample is first
+    // multiplied by the white balance multiplier of its plane, as given by
+    // DNGMetadata::white_balance.
+    pub fn demosaic(&self, algorithm: Demosaic, white_balance: &[f64]) -> Result<Image> {
+
+        let cfa = self.cfa.as_ref()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Demosaicing needs CFA data"))?;
+        if self.samples_per_pixel != 1 || cfa.layout != CFA_LAYOUT_RECTANGULAR {
+            return Err(Error::other("Only rectangular CFA layouts with one sample per pixel are supported"));
+        }
+
+        // Output channel of each plane
+        let mut channels = cfa.plane_colors.clone();
+        channels.sort_unstable();
+        if channels != [0, 1, 2] {
+            return Err(Error::other(format!("Unsupported CFA plane colours {:?}", cfa.plane_colors)));
+        }
+        if white_balance.len() != cfa.plane_colors.len() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("{} white balance multipliers for {} planes",
+                                          white_balance.len(), cfa.plane_colors.len())));
+        }
+
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut mosaic = Vec::with_capacity(width * height);
+        for (row, line) in self.data.chunks(width.max(1)).enumerate() {
+            for (col, &value) in line.iter().enumerate() {
+                mosaic.push(value * white_balance[cfa.plane_at(row, col) as usize] as f32);
+            }
+        }
+        let mosaic = Mosaic { data: &mosaic, width, height, cfa };
+
+        let planes = match algorithm {
+            Demosaic::Bilinear => mosaic.bilinear(),
+            Demosaic::MalvarHeCutler => mosaic.malvar_he_cutler()?,
+        };
+
+        let mut data = vec![0u16; width * height * 3];
+        for (i, pixel) in planes.iter().enumerate() {
+            for (plane, &value) in pixel.iter().enumerate() {
+                let channel = cfa.plane_colors[plane] as usize;
+                data[i * 3 + channel] = (value.clamp(0.0, 1.0) * 65535.0).round() as u16;
+            }
+        }
+
+        Ok(Image::new(self.width, self.height, 3, PhotometricInterpretation::RGB, ImageData::U16(data)))
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+// White balanced CFA samples, one per pixel.
+struct Mosaic<'a> {
+    data:   &'a [f32],
+    width:  usize,
+    height: usize,
+    cfa:    &'a CFAPattern,
+}
+
+impl<'a> Mosaic<'a> {
+
+    fn plane_at(&self, row: usize, col: usize) -> usize {
+        self.cfa.plane_at(row, col) as usize
+    }
+
+    // Each plane averaged over the 3x3 neighbourhood of every pixel, using
+    // the pixel's own sample for its plane.
+    fn bilinear(&self) -> Vec<[f32; 3]> {
+        let mut result = Vec::with_capacity(self.width * self.height);
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let mut sums = [0.0f32; 3];
+                let mut counts = [0u32; 3];
+                for y in row.saturating_sub(1)..(row + 2).min(self.height) {
+                    for x in col.saturating_sub(1)..(col + 2).min(self.width) {
+                        let plane = self.plane_at(y, x);
+                        sums[plane] += self.data[y * self.width + x];
+                        counts[plane] += 1;
+                    }
+                }
+                let own = self.plane_at(row, col);
+                let mut pixel = [0.0f32; 3];
+                for plane in 0..3 {
+                    pixel[plane] = if plane == own {
+                        self.data[row * self.width + col]
+                    } else if counts[plane] > 0 {
+                        sums[plane] / counts[plane] as f32
+                    } else {
+                        0.0
+                    };
+                }
+                result.push(pixel);
+            }
+        }
+        result
+    }
+
+    fn malvar_he_cutler(&self) -> Result<Vec<[f32; 3]>> {
+
+        let green = bayer_green(self.cfa).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "Malvar-He-Cutler demosaicing needs a 2x2 Bayer pattern")
+        })?;
+
+        let mut result = Vec::with_capacity(self.width * self.height);
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let own = self.plane_at(row, col);
+                let mut pixel = [0.0f32; 3];
+                pixel[own] = self.data[row * self.width + col];
+                if own == green {
+                    pixel[self.plane_at(row, col + 1)] = self.filter(row, col, &ROW_AT_GREEN);
+                    pixel[self.plane_at(row + 1, col)] = self.filter(row, col, &COLUMN_AT_GREEN);
+                } else {
+                    pixel[green] = self.filter(row, col, &GREEN_AT_RED_BLUE);
+                    pixel[3 - green - own] = self.filter(row, col, &RED_BLUE_AT_BLUE_RED);
+                }
+                result.push(pixel);
+            }
+        }
+        Ok(result)
+    }
+
+    // Apply a filter, mirroring the image at its edges.
+    fn filter(&self, row: usize, col: usize, kernel: &[(isize, isize, f32)]) -> f32 {
+        let sum: f32 = kernel.iter().map(|&(dy, dx, weight)| {
+            let y = mirror(row as isize + dy, self.height);
+            let x = mirror(col as isize + dx, self.width);
+            weight * self.data[y * self.width + x]
+        }).sum();
+        sum / 8.0
+    }
+}
+
+// The green plane of a 2x2 pattern with two greens on a diagonal and the
+// other two planes once each.
+fn bayer_green(cfa: &CFAPattern) -> Option<usize> {
+    if cfa.rows != 2 || cfa.cols != 2 {
+        return None;
+    }
+    let p = &cfa.pattern;
+    let (green, others) = if p[0] == p[3] {
+        (p[0], [p[1], p[2]])
+    } else if p[1] == p[2] {
+        (p[1], [p[0], p[3]])
+    } else {
+        return None;
+    };
+    if others[0] == others[1] || others.contains(&green) {
+        return None;
+    }
+    Some(green as usize)
+}
+
+// Reflect an index about the first and last positions, keeping its parity
+// so that it lands on the same colour of the pattern.
+fn mirror(i: isize, len: usize) -> usize {
+    let last = len as isize - 1;
+    let reflected = if i < 0 { -i } else if i > last { 2 * last - i } else { i };
+    if (0..=last).contains(&reflected) {
+        reflected as usize
+    } else {
+        (i.rem_euclid(2) as usize).min(len - 1)
+    }
+}
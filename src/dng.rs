@@ -0,0 +1,378 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and the Digital Negative (DNG) Specification, version 1.4.0.0.
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, ErrorKind};
+
+use {TIFF, IFD, TIFFTag, PhotometricInterpretation, SampleFormat, SeekableReader};
+use reader::TIFFReader;
+use image::ImageData;
+
+//----------------------------------------------------------------------------
+// Constants
+
+// CFAPlaneColor default: red, green and blue planes
+const DEFAULT_PLANE_COLORS: [u8; 3] = [0, 1, 2];
+
+// CFALayout: a rectangular (square) grid
+pub(crate) const CFA_LAYOUT_RECTANGULAR: u16 = 1;
+
+//----------------------------------------------------------------------------
+// Types
+
+// Camera and colour fields of a DNG file, from its first IFD.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DNGMetadata {
+    pub version:                 [u8; 4],
+    pub backward_version:        [u8; 4],
+    pub unique_camera_model:     Option<String>,
+    // XYZ to camera space matrices, row major, one row per camera colour
+    pub color_matrix1:           Option<Vec<f64>>,
+    pub color_matrix2:           Option<Vec<f64>>,
+    pub camera_calibration1:     Option<Vec<f64>>,
+    pub camera_calibration2:     Option<Vec<f64>>,
+    pub analog_balance:          Option<Vec<f64>>,
+    // White balance, as the camera neutral or as an xy chromaticity
+    pub as_shot_neutral:         Option<Vec<f64>>,
+    pub as_shot_white_xy:        Option<(f64, f64)>,
+    pub baseline_exposure:       f64,
+    // EXIF LightSource values of the illuminants for each matrix
+    pub calibration_illuminant1: Option<u16>,
+    pub calibration_illuminant2: Option<u16>,
+}
+
+// The colour filter array over the sensor.  Pattern entries are plane
+// indices (positions in `plane_colors`), rows first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CFAPattern {
+    pub rows:         usize,
+    pub cols:         usize,
+    pub pattern:      Vec<u8>,
+    // CFAPattern colour codes of each plane: 0 red, 1 green, 2 blue, ...
+    pub plane_colors: Vec<u8>,
+    pub layout:       u16,
+}
+
+// Raw sensor samples mapped to linear reference values: linearised, with
+// the black level subtracted, scaled so that the white level is 1.0, and
+// cropped to the active area.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawImage {
+    pub width:             u32,
+    pub height:            u32,
+    pub samples_per_pixel: u16,
+    // CFA or LinearRaw
+    pub photometric:       PhotometricInterpretation,
+    // Only for CFA data; the pattern starts at the active area's origin
+    pub cfa:               Option<CFAPattern>,
+    // DefaultCropOrigin and DefaultCropSize, relative to the active area
+    pub crop_origin:       (f64, f64),
+    pub crop_size:         (f64, f64),
+    pub data:              Vec<f32>,
+}
+
+//----------------------------------------------------------------------------
+
+impl DNGMetadata {
+
+    // The DNG fields of an IFD; None if it has no DNGVersion.
+    pub fn from_ifd(ifd: &IFD) -> Option<DNGMetadata> {
+
+        let version = version_field(ifd, TIFFTag::DNGVersionTag)?;
+        let backward_version = version_field(ifd, TIFFTag::DNGBackwardVersionTag)
+            .unwrap_or([version[0], version[1], 0, 0]);
+
+        let white_xy = ifd.get_f64s(TIFFTag::AsShotWhiteXYTag)
+            .filter(|v| v.len() == 2)
+            .map(|v| (v[0], v[1]));
+
+        Some(DNGMetadata {
+            version,
+            backward_version,
+            unique_camera_model:     ifd.get_ascii(TIFFTag::UniqueCameraModelTag),
+            color_matrix1:           ifd.get_f64s(TIFFTag::ColorMatrix1Tag),
+            color_matrix2:           ifd.get_f64s(TIFFTag::ColorMatrix2Tag),
+            camera_calibration1:     ifd.get_f64s(TIFFTag::CameraCalibration1Tag),
+            camera_calibration2:     ifd.get_f64s(TIFFTag::CameraCalibration2Tag),
+            analog_balance:          ifd.get_f64s(TIFFTag::AnalogBalanceTag),
+            as_shot_neutral:         ifd.get_f64s(TIFFTag::AsShotNeutralTag),
+            as_shot_white_xy:        white_xy,
+            baseline_exposure:       ifd.get_f64(TIFFTag::BaselineExposureTag).unwrap_or(0.0),
+            calibration_illuminant1: ifd.get_u32(TIFFTag::CalibrationIlluminant1Tag).map(|i| i as u16),
+            calibration_illuminant2: ifd.get_u32(TIFFTag::CalibrationIlluminant2Tag).map(|i| i as u16),
+        })
+    }
+
+    // Number of colour planes the camera records, from the colour matrix.
+    pub fn color_planes(&self) -> Option<usize> {
+        self.color_matrix1.as_ref().map(|m| m.len() / 3)
+    }
+
+    // White balance multipliers for each colour plane, from AsShotNeutral:
+    // the reciprocal of the neutral, scaled so that the smallest is 1.0.
+    pub fn white_balance(&self) -> Option<Vec<f64>> {
+        let neutral = self.as_shot_neutral.as_ref()?;
+        if neutral.is_empty() || neutral.iter().any(|&n| n <= 0.0) {
+            return None;
+        }
+        let multipliers: Vec<f64> = neutral.iter().map(|n| 1.0 / n).collect();
+        let min = multipliers.iter().cloned().fold(f64::INFINITY, f64::min);
+        Some(multipliers.iter().map(|m| m / min).collect())
+    }
+}
+
+impl CFAPattern {
+
+    pub fn from_ifd(ifd: &IFD) -> Result<Option<CFAPattern>> {
+
+        let pattern = match ifd.get(TIFFTag::CFAPatternTag) {
+            Some(e) => e.data.clone(),
+            None => return Ok(None),
+        };
+        let dims = ifd.get_u32s(TIFFTag::CFARepeatPatternDimTag).unwrap_or_default();
+        if dims.len() != 2 || dims[0] == 0 || dims[1] == 0 {
+            return Err(invalid("CFARepeatPatternDim must hold two non-zero values"));
+        }
+        let (rows, cols) = (dims[0] as usize, dims[1] as usize);
+        if pattern.len() != rows * cols {
+            return Err(invalid(&format!("CFAPattern has {} values for a {}x{} pattern",
+                                        pattern.len(), rows, cols)));
+        }
+
+        let plane_colors = ifd.get(TIFFTag::CFAPlaneColorTag)
+            .map(|e| e.data.clone())
+            .unwrap_or_else(|| DEFAULT_PLANE_COLORS.to_vec());
+        let pattern = pattern.iter()
+            .map(|c| plane_colors.iter().position(|p| p == c).map(|i| i as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| invalid("CFAPattern holds a colour not listed in CFAPlaneColor"))?;
+
+        Ok(Some(CFAPattern {
+            rows,
+            cols,
+            pattern,
+            plane_colors,
+            layout: ifd.get_u32(TIFFTag::CFALayoutTag).unwrap_or(CFA_LAYOUT_RECTANGULAR as u32) as u16,
+        }))
+    }
+
+    // The plane sampled by the pixel at the given position.
+    pub fn plane_at(&self, row: usize, col: usize) -> u8 {
+        self.pattern[(row % self.rows) * self.cols + col % self.cols]
+    }
+}
+
+impl RawImage {
+
+    // Samples of one pixel.
+    pub fn pixel(&self, x: u32, y: u32) -> &[f32] {
+        let spp = self.samples_per_pixel as usize;
+        let start = (y as usize * self.width as usize + x as usize) * spp;
+        &self.data[start..start + spp]
+    }
+}
+
+impl IFD {
+
+    pub fn dng(&self) -> Option<DNGMetadata> {
+        DNGMetadata::from_ifd(self)
+    }
+}
+
+impl TIFF {
+
+    // The IFD holding the main raw image: the full resolution image (a
+    // NewSubfileType of 0) with CFA or LinearRaw data, usually a SubIFD of
+    // the first IFD.
+    pub fn dng_raw_ifd(&self) -> Option<&IFD> {
+        self.all_ifds().into_iter().find(|ifd| {
+            let subfile_type = ifd.get_u32(TIFFTag::NewSubfileTypeTag).unwrap_or(0);
+            subfile_type == 0 && is_raw(ifd)
+        })
+    }
+}
+
+impl TIFFReader {
+
+    // Read the raw image of a DNG IFD, mapping its samples to linear
+    // values between 0.0 (black) and 1.0 (white).
+    pub fn read_dng_raw(&self, reader: &mut dyn SeekableReader, ifd: &IFD) -> Result<RawImage> {
+
+        if !is_raw(ifd) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Not a CFA or LinearRaw image"));
+        }
+
+        if ifd.get_u32(TIFFTag::SamplesPerPixelTag) == Some(0) {
+            return Err(invalid("SamplesPerPixel must be at least 1"));
+        }
+
+        let image = self.read_image(reader, ifd)?;
+        let spp = image.samples_per_pixel as usize;
+        let (width, height) = (image.width as usize, image.height as usize);
+
+        // ActiveArea: top, left, bottom, right
+        let area = ifd.get_u32s(TIFFTag::ActiveAreaTag)
+            .unwrap_or_else(|| vec![0, 0, height as u32, width as u32]);
+        if area.len() != 4 || area[0] >= area[2] || area[1] >= area[3] ||
+           area[2] as usize > height || area[3] as usize > width {
+            return Err(invalid(&format!("ActiveArea {:?} outside a {}x{} image", area, width, height)));
+        }
+        let (top, left) = (area[0] as usize, area[1] as usize);
+        let (active_width, active_height) = ((area[3] - area[1]) as usize, (area[2] - area[0]) as usize);
+
+        let samples = linearize(&image.data, ifd.get_u32s(TIFFTag::LinearizationTableTag));
+        let black = BlackLevel::from_ifd(ifd, spp, active_width, active_height)?;
+
+        let white_levels = ifd.get_f64s(TIFFTag::WhiteLevelTag).unwrap_or_else(|| {
+            match image.sample_format {
+                SampleFormat::IEEEFloatingPoint => vec![1.0],
+                _ => vec![2f64.powi(image.bits_per_sample as i32) - 1.0],
+            }
+        });
+        if white_levels.len() != 1 && white_levels.len() != spp {
+            return Err(invalid(&format!("WhiteLevel has {} values for {} samples", white_levels.len(), spp)));
+        }
+
+        let mut data = Vec::with_capacity(active_width * active_height * spp);
+        for row in 0..active_height {
+            let start = ((top + row) * width + left) * spp;
+            let line = &samples[start..start + active_width * spp];
+            for (col, pixel) in line.chunks(spp).enumerate() {
+                for (s, &value) in pixel.iter().enumerate() {
+                    let black = black.at(row, col, s);
+                    let white = white_levels[s % white_levels.len()];
+                    let linear = if white > black { (value - black) / (white - black) } else { 0.0 };
+                    data.push(linear.clamp(0.0, 1.0) as f32);
+                }
+            }
+        }
+
+        let cfa = if image.photometric == PhotometricInterpretation::CFA {
+            Some(CFAPattern::from_ifd(ifd)?.ok_or_else(|| invalid("CFA image without a CFAPattern"))?)
+        } else {
+            None
+        };
+
+        let pair = |tag| ifd.get_f64s(tag).filter(|v| v.len() == 2).map(|v| (v[0], v[1]));
+
+        Ok(RawImage {
+            width: active_width as u32,
+            height: active_height as u32,
+            samples_per_pixel: spp as u16,
+            photometric: image.photometric,
+            cfa,
+            crop_origin: pair(TIFFTag::DefaultCropOriginTag).unwrap_or((0.0, 0.0)),
+            crop_size: pair(TIFFTag::DefaultCropSizeTag).unwrap_or((active_width as f64, active_height as f64)),
+            data,
+        })
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+// BlackLevel repeated over BlackLevelRepeatDim, plus the per-column and
+// per-row deltas across the active area.
+struct BlackLevel {
+    rows:     usize,
+    cols:     usize,
+    spp:      usize,
+    levels:   Vec<f64>,
+    delta_h:  Vec<f64>,
+    delta_v:  Vec<f64>,
+}
+
+impl BlackLevel {
+
+    fn from_ifd(ifd: &IFD, spp: usize, width: usize, height: usize) -> Result<BlackLevel> {
+
+        let dims = ifd.get_u32s(TIFFTag::BlackLevelRepeatDimTag).unwrap_or_else(|| vec![1, 1]);
+        if dims.len() != 2 || dims[0] == 0 || dims[1] == 0 {
+            return Err(invalid("BlackLevelRepeatDim must hold two non-zero values"));
+        }
+        let (rows, cols) = (dims[0] as usize, dims[1] as usize);
+
+        let expected = rows.checked_mul(cols).and_then(|n| n.checked_mul(spp))
+            .ok_or_else(|| invalid("BlackLevelRepeatDim is too large"))?;
+        let (rows, cols, levels) = match ifd.get_f64s(TIFFTag::BlackLevelTag) {
+            Some(ref levels) if levels.len() != expected =>
+                return Err(invalid(&format!("BlackLevel has {} values, expected {}", levels.len(), expected))),
+            Some(levels) => (rows, cols, levels),
+            // The default of 0 has no pattern to repeat
+            None => (1, 1, vec![0.0; spp]),
+        };
+
+        let delta = |tag, count, name| -> Result<Vec<f64>> {
+            match ifd.get_f64s(tag) {
+                Some(ref d) if d.len() != count =>
+                    Err(invalid(&format!("{} has {} values, expected {}", name, d.len(), count))),
+                Some(d) => Ok(d),
+                None => Ok(vec![0.0; count]),
+            }
+        };
+
+        Ok(BlackLevel {
+            rows,
+            cols,
+            spp,
+            levels,
+            delta_h: delta(TIFFTag::BlackLevelDeltaHTag, width, "BlackLevelDeltaH")?,
+            delta_v: delta(TIFFTag::BlackLevelDeltaVTag, height, "BlackLevelDeltaV")?,
+        })
+    }
+
+    fn at(&self, row: usize, col: usize, sample: usize) -> f64 {
+        let index = ((row % self.rows) * self.cols + col % self.cols) * self.spp + sample;
+        self.levels[index] + self.delta_h[col] + self.delta_v[row]
+    }
+}
+
+fn is_raw(ifd: &IFD) -> bool {
+    let photometric = ifd.get_u32(TIFFTag::PhotometricInterpretationTag).unwrap_or(0);
+    photometric == PhotometricInterpretation::CFA as u32 || photometric == PhotometricInterpretation::LinearRaw as u32
+}
+
+fn version_field(ifd: &IFD, tag: TIFFTag) -> Option<[u8; 4]> {
+    let data = &ifd.get(tag)?.data;
+    if data.len() < 4 {
+        return None;
+    }
+    Some([data[0], data[1], data[2], data[3]])
+}
+
+// Samples as f64, mapped through the LinearizationTable if there is one.
+// The table only applies to unsigned integer samples.
+fn linearize(data: &ImageData, table: Option<Vec<u32>>) -> Vec<f64> {
+    let table = table.filter(|t| !t.is_empty());
+    let lookup = |x: u64| match table {
+        Some(ref t) => t[(x as usize).min(t.len() - 1)] as f64,
+        None => x as f64,
+    };
+    match *data {
+        ImageData::U8(ref v) => v.iter().map(|&x| lookup(x as u64)).collect(),
+        ImageData::U16(ref v) => v.iter().map(|&x| lookup(x as u64)).collect(),
+        ImageData::U32(ref v) => v.iter().map(|&x| lookup(x as u64)).collect(),
+        ImageData::U64(ref v) => v.iter().map(|&x| lookup(x)).collect(),
+        ImageData::I8(ref v) => v.iter().map(|&x| x as f64).collect(),
+        ImageData::I16(ref v) => v.iter().map(|&x| x as f64).collect(),
+        ImageData::I32(ref v) => v.iter().map(|&x| x as f64).collect(),
+        ImageData::I64(ref v) => v.iter().map(|&x| x as f64).collect(),
+        ImageData::F32(ref v) => v.iter().map(|&x| x as f64).collect(),
+        ImageData::F64(ref v) => v.clone(),
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid DNG: {}", message))
+}
@@ -0,0 +1,164 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, ErrorKind, Read, Write, Seek, SeekFrom};
+use std::fs::{File, OpenOptions};
+
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
+
+use {TIFF, IFD, TIFFByteOrder, TIFFVersion};
+use reader::TIFFReader;
+use writer::{prepare_entries, inline_size, write_ifd_entries, write_offset, first_ifd_position, align};
+
+//----------------------------------------------------------------------------
+// Types
+
+// Edits the fields of an existing file.  Changed IFDs are written back by
+// `save`, in place if they fit and otherwise at the end of the file; image
+// data is left untouched.
+pub struct TIFFEditor<F: Read + Write + Seek> {
+    file: F,
+    pub tiff: Box<TIFF>,
+    // The IFDs as last read or saved
+    saved: Vec<IFD>,
+}
+
+//----------------------------------------------------------------------------
+
+impl TIFFEditor<File> {
+
+    pub fn load(filename: &str) -> Result<TIFFEditor<File>> {
+        let file = OpenOptions::new().read(true).write(true).open(filename)?;
+        TIFFEditor::open(file)
+    }
+}
+
+impl<F: Read + Write + Seek> TIFFEditor<F> {
+
+    pub fn open(mut file: F) -> Result<TIFFEditor<F>> {
+        let tiff = TIFFReader.read(&mut file)?;
+        let saved = tiff.ifds.clone();
+        Ok(TIFFEditor { file, tiff, saved })
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+
+    // Write back every IFD whose fields have changed.  IFDs may be edited
+    // but not added or removed.
+    pub fn save(&mut self) -> Result<()> {
+        if self.tiff.ifds.len() != self.saved.len() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("Editing {} IFDs of a file with {}", self.tiff.ifds.len(), self.saved.len())));
+        }
+        for index in 0..self.tiff.ifds.len() {
+            if self.changed(index) {
+                match self.tiff.header.byte_order {
+                    TIFFByteOrder::LittleEndian => self.save_ifd::<LittleEndian>(index)?,
+                    TIFFByteOrder::BigEndian => self.save_ifd::<BigEndian>(index)?,
+                }
+            }
+        }
+        self.file.flush()
+    }
+
+    fn changed(&self, index: usize) -> bool {
+        let byte_order = self.tiff.header.byte_order;
+        let saved = &self.saved[index];
+        let ifd = &self.tiff.ifds[index];
+
+        ifd.entries.len() != saved.entries.len() || ifd.entries.iter().any(|e| {
+            let e = e.with_byte_order(byte_order);
+            saved.get_raw(e.tag).is_none_or(|s| {
+                s.typ as u16 != e.typ as u16 || s.count != e.count || s.data != e.data
+            })
+        })
+    }
+
+    fn save_ifd<Endian: ByteOrder>(&mut self, index: usize) -> Result<()> {
+
+        let version = self.tiff.header.version;
+        let inline = inline_size(version);
+        let saved = &self.saved[index];
+        let mut entries = prepare_entries(&self.tiff.ifds[index].entries, self.tiff.header.byte_order);
+
+        // Unchanged values stay where they are, and changed values replace
+        // the old ones if they fit
+        for e in entries.iter_mut().filter(|e| e.data.len() > inline) {
+            let old = saved.get_raw(e.tag).filter(|o| o.data.len() > inline);
+            e.value_offset = match old {
+                Some(o) if o.data == e.data => o.value_offset,
+                Some(o) if e.data.len() <= o.data.len() => {
+                    self.file.seek(SeekFrom::Start(o.value_offset))?;
+                    self.file.write_all(&e.data)?;
+                    o.value_offset
+                },
+                _ => {
+                    self.file.seek(SeekFrom::End(0))?;
+                    align(&mut self.file)?;
+                    let offset = self.file.stream_position()?;
+                    self.file.write_all(&e.data)?;
+                    offset
+                },
+            };
+        }
+
+        let in_place = entries.len() as u64 <= saved.count;
+        if in_place {
+            self.file.seek(SeekFrom::Start(saved.offset))?;
+        } else {
+            self.file.seek(SeekFrom::End(0))?;
+            align(&mut self.file)?;
+        }
+
+        let next_offset = self.tiff.ifds[index].next_offset;
+        let written = write_ifd_entries::<Endian>(&mut self.file, version, &entries, next_offset)?;
+
+        // Relink from the header or the previous IFD
+        if !in_place {
+            let link = if index == 0 {
+                first_ifd_position(version)
+            } else {
+                next_offset_position(&self.saved[index - 1], version)
+            };
+            write_offset::<Endian>(&mut self.file, link, written.offset, version)?;
+            if index == 0 {
+                self.tiff.header.ifd_offset = written.offset;
+            } else {
+                self.tiff.ifds[index - 1].next_offset = written.offset;
+                self.saved[index - 1].next_offset = written.offset;
+            }
+        }
+
+        let ifd = &mut self.tiff.ifds[index];
+        ifd.offset = written.offset;
+        ifd.count = entries.len() as u64;
+        ifd.entries = entries;
+        self.saved[index] = ifd.clone();
+
+        Ok(())
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+// Position of an IFD's next IFD offset, following its entries.
+fn next_offset_position(ifd: &IFD, version: TIFFVersion) -> u64 {
+    match version {
+        TIFFVersion::Classic => ifd.offset + 2 + 12 * ifd.count,
+        TIFFVersion::BigTIFF => ifd.offset + 8 + 20 * ifd.count,
+    }
+}
+
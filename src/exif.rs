@@ -0,0 +1,370 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and the Exif 2.32 specification (CIPA DC-008-2019).
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::Result;
+
+use {IFD, IFDEntry, TIFFHeader, TIFFTag, TIFFByteOrder, TagType, SeekableReader};
+use reader::TIFFReader;
+
+//----------------------------------------------------------------------------
+// Types
+
+// Tags of the EXIF IFD, which overlap with the baseline tag numbers and so
+// are kept apart from `TIFFTag`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EXIFTag {
+
+    // Version
+
+    ExifVersionTag                = 0x9000,
+    FlashpixVersionTag            = 0xa000,
+
+    // Image data characteristics
+
+    ColorSpaceTag                 = 0xa001,
+    ComponentsConfigurationTag    = 0x9101,
+    PixelXDimensionTag            = 0xa002,
+    PixelYDimensionTag            = 0xa003,
+
+    // User information
+
+    MakerNoteTag                  = 0x927c,
+    UserCommentTag                = 0x9286,
+
+    // Date and time
+
+    DateTimeOriginalTag           = 0x9003,
+    DateTimeDigitizedTag          = 0x9004,
+    OffsetTimeTag                 = 0x9010,
+    OffsetTimeOriginalTag         = 0x9011,
+    OffsetTimeDigitizedTag        = 0x9012,
+    SubSecTimeTag                 = 0x9290,
+    SubSecTimeOriginalTag         = 0x9291,
+    SubSecTimeDigitizedTag        = 0x9292,
+
+    // Picture-taking conditions
+
+    ExposureTimeTag               = 0x829a,
+    FNumberTag                    = 0x829d,
+    ExposureProgramTag            = 0x8822,
+    PhotographicSensitivityTag    = 0x8827,
+    SensitivityTypeTag            = 0x8830,
+    ShutterSpeedValueTag          = 0x9201,
+    ApertureValueTag              = 0x9202,
+    BrightnessValueTag            = 0x9203,
+    ExposureBiasValueTag          = 0x9204,
+    MaxApertureValueTag           = 0x9205,
+    SubjectDistanceTag            = 0x9206,
+    MeteringModeTag               = 0x9207,
+    LightSourceTag                = 0x9208,
+    FlashTag                      = 0x9209,
+    FocalLengthTag                = 0x920a,
+    ExposureModeTag               = 0xa402,
+    WhiteBalanceTag               = 0xa403,
+    DigitalZoomRatioTag           = 0xa404,
+    FocalLengthIn35mmFilmTag      = 0xa405,
+    SceneCaptureTypeTag           = 0xa406,
+
+    // Other
+
+    CameraOwnerNameTag            = 0xa430,
+    BodySerialNumberTag           = 0xa431,
+    LensSpecificationTag          = 0xa432,
+    LensMakeTag                   = 0xa433,
+    LensModelTag                  = 0xa434,
+    LensSerialNumberTag           = 0xa435,
+
+    // Offset of the Interoperability IFD
+    InteroperabilityIFDTag        = 0xa005,
+}
+
+// The fields of an EXIF IFD.
+#[derive(Clone, Debug)]
+pub struct EXIF {
+    pub ifd: IFD,
+}
+
+//----------------------------------------------------------------------------
+
+pub fn decode_exif_tag(value: u16) -> Option<EXIFTag> {
+    match value {
+        0x9000 => Some(EXIFTag::ExifVersionTag),
+        0xa000 => Some(EXIFTag::FlashpixVersionTag),
+
+        0xa001 => Some(EXIFTag::ColorSpaceTag),
+        0x9101 => Some(EXIFTag::ComponentsConfigurationTag),
+        0xa002 => Some(EXIFTag::PixelXDimensionTag),
+        0xa003 => Some(EXIFTag::PixelYDimensionTag),
+
+        0x927c => Some(EXIFTag::MakerNoteTag),
+        0x9286 => Some(EXIFTag::UserCommentTag),
+
+        0x9003 => Some(EXIFTag::DateTimeOriginalTag),
+        0x9004 => Some(EXIFTag::DateTimeDigitizedTag),
+        0x9010 => Some(EXIFTag::OffsetTimeTag),
+        0x9011 => Some(EXIFTag::OffsetTimeOriginalTag),
+        0x9012 => Some(EXIFTag::OffsetTimeDigitizedTag),
+        0x9290 => Some(EXIFTag::SubSecTimeTag),
+        0x9291 => Some(EXIFTag::SubSecTimeOriginalTag),
+        0x9292 => Some(EXIFTag::SubSecTimeDigitizedTag),
+
+        0x829a => Some(EXIFTag::ExposureTimeTag),
+        0x829d => Some(EXIFTag::FNumberTag),
+        0x8822 => Some(EXIFTag::ExposureProgramTag),
+        0x8827 => Some(EXIFTag::PhotographicSensitivityTag),
+        0x8830 => Some(EXIFTag::SensitivityTypeTag),
+        0x9201 => Some(EXIFTag::ShutterSpeedValueTag),
+        0x9202 => Some(EXIFTag::ApertureValueTag),
+        0x9203 => Some(EXIFTag::BrightnessValueTag),
+        0x9204 => Some(EXIFTag::ExposureBiasValueTag),
+        0x9205 => Some(EXIFTag::MaxApertureValueTag),
+        0x9206 => Some(EXIFTag::SubjectDistanceTag),
+        0x9207 => Some(EXIFTag::MeteringModeTag),
+        0x9208 => Some(EXIFTag::LightSourceTag),
+        0x9209 => Some(EXIFTag::FlashTag),
+        0x920a => Some(EXIFTag::FocalLengthTag),
+        0xa402 => Some(EXIFTag::ExposureModeTag),
+        0xa403 => Some(EXIFTag::WhiteBalanceTag),
+        0xa404 => Some(EXIFTag::DigitalZoomRatioTag),
+        0xa405 => Some(EXIFTag::FocalLengthIn35mmFilmTag),
+        0xa406 => Some(EXIFTag::SceneCaptureTypeTag),
+
+        0xa430 => Some(EXIFTag::CameraOwnerNameTag),
+        0xa431 => Some(EXIFTag::BodySerialNumberTag),
+        0xa432 => Some(EXIFTag::LensSpecificationTag),
+        0xa433 => Some(EXIFTag::LensMakeTag),
+        0xa434 => Some(EXIFTag::LensModelTag),
+        0xa435 => Some(EXIFTag::LensSerialNumberTag),
+
+        0xa005 => Some(EXIFTag::InteroperabilityIFDTag),
+        _ => None,
+    }
+}
+
+// Returns (type, count) for EXIF tags. 0 is unknown/variable/unspecified.
+pub fn type_and_count_for_exif_tag(tag: EXIFTag) -> Option<(TagType, u32)> {
+    match tag {
+        EXIFTag::ExifVersionTag               => Some((TagType::UndefinedTag, 4)),
+        EXIFTag::FlashpixVersionTag           => Some((TagType::UndefinedTag, 4)),
+        EXIFTag::ColorSpaceTag                => Some((TagType::ShortTag, 1)),
+        EXIFTag::ComponentsConfigurationTag   => Some((TagType::UndefinedTag, 4)),
+        EXIFTag::PixelXDimensionTag           => Some((TagType::ShortOrLongTag, 1)),
+        EXIFTag::PixelYDimensionTag           => Some((TagType::ShortOrLongTag, 1)),
+        EXIFTag::MakerNoteTag                 => Some((TagType::UndefinedTag, 0)),
+        EXIFTag::UserCommentTag               => Some((TagType::UndefinedTag, 0)),
+        EXIFTag::DateTimeOriginalTag          => Some((TagType::ASCIITag, 20)),
+        EXIFTag::DateTimeDigitizedTag         => Some((TagType::ASCIITag, 20)),
+        EXIFTag::OffsetTimeTag                => Some((TagType::ASCIITag, 7)),
+        EXIFTag::OffsetTimeOriginalTag        => Some((TagType::ASCIITag, 7)),
+        EXIFTag::OffsetTimeDigitizedTag       => Some((TagType::ASCIITag, 7)),
+        EXIFTag::SubSecTimeTag                => Some((TagType::ASCIITag, 0)),
+        EXIFTag::SubSecTimeOriginalTag        => Some((TagType::ASCIITag, 0)),
+        EXIFTag::SubSecTimeDigitizedTag       => Some((TagType::ASCIITag, 0)),
+        EXIFTag::ExposureTimeTag              => Some((TagType::RationalTag, 1)),
+        EXIFTag::FNumberTag                   => Some((TagType::RationalTag, 1)),
+        EXIFTag::ExposureProgramTag           => Some((TagType::ShortTag, 1)),
+        EXIFTag::PhotographicSensitivityTag   => Some((TagType::ShortTag, 0)),
+        EXIFTag::SensitivityTypeTag           => Some((TagType::ShortTag, 1)),
+        EXIFTag::ShutterSpeedValueTag         => Some((TagType::SignedRationalTag, 1)),
+        EXIFTag::ApertureValueTag             => Some((TagType::RationalTag, 1)),
+        EXIFTag::BrightnessValueTag           => Some((TagType::SignedRationalTag, 1)),
+        EXIFTag::ExposureBiasValueTag         => Some((TagType::SignedRationalTag, 1)),
+        EXIFTag::MaxApertureValueTag          => Some((TagType::RationalTag, 1)),
+        EXIFTag::SubjectDistanceTag           => Some((TagType::RationalTag, 1)),
+        EXIFTag::MeteringModeTag              => Some((TagType::ShortTag, 1)),
+        EXIFTag::LightSourceTag               => Some((TagType::ShortTag, 1)),
+        EXIFTag::FlashTag                     => Some((TagType::ShortTag, 1)),
+        EXIFTag::FocalLengthTag               => Some((TagType::RationalTag, 1)),
+        EXIFTag::ExposureModeTag              => Some((TagType::ShortTag, 1)),
+        EXIFTag::WhiteBalanceTag              => Some((TagType::ShortTag, 1)),
+        EXIFTag::DigitalZoomRatioTag          => Some((TagType::RationalTag, 1)),
+        EXIFTag::FocalLengthIn35mmFilmTag     => Some((TagType::ShortTag, 1)),
+        EXIFTag::SceneCaptureTypeTag          => Some((TagType::ShortTag, 1)),
+        EXIFTag::CameraOwnerNameTag           => Some((TagType::ASCIITag, 0)),
+        EXIFTag::BodySerialNumberTag          => Some((TagType::ASCIITag, 0)),
+        EXIFTag::LensSpecificationTag         => Some((TagType::RationalTag, 4)),
+        EXIFTag::LensMakeTag                  => Some((TagType::ASCIITag, 0)),
+        EXIFTag::LensModelTag                 => Some((TagType::ASCIITag, 0)),
+        EXIFTag::LensSerialNumberTag          => Some((TagType::ASCIITag, 0)),
+        EXIFTag::InteroperabilityIFDTag       => Some((TagType::LongTag, 1)),
+    }
+}
+
+//----------------------------------------------------------------------------
+
+impl TIFFReader {
+
+    // Read the EXIF IFD that `ifd` points to, if any.
+    pub fn read_exif(&self, reader: &mut dyn SeekableReader, header: &TIFFHeader, ifd: &IFD) -> Result<Option<EXIF>> {
+        let exif = self.read_pointed_ifd(reader, header, ifd, TIFFTag::EXIFTag)?;
+        Ok(exif.map(|ifd| EXIF { ifd }))
+    }
+}
+
+impl EXIF {
+
+    pub fn get(&self, tag: EXIFTag) -> Option<&IFDEntry> {
+        self.ifd.get_raw(tag as u16)
+    }
+
+    fn get_u32(&self, tag: EXIFTag) -> Option<u32> {
+        self.get(tag).and_then(|e| e.as_u32s()).and_then(|v| v.first().cloned())
+    }
+
+    // Rationals with a zero denominator are treated as absent.
+    fn get_f64(&self, tag: EXIFTag) -> Option<f64> {
+        self.get(tag).and_then(|e| e.as_f64s()).and_then(|v| v.first().cloned()).filter(|v| v.is_finite())
+    }
+
+    // Strings are often padded with spaces to a fixed length.
+    fn get_ascii(&self, tag: EXIFTag) -> Option<String> {
+        self.get(tag).and_then(|e| e.as_ascii())
+            .map(|s| s.trim_end().to_string())
+            .filter(|s| !s.is_empty())
+    }
+
+    // Four ASCII digits, such as "0232".
+    pub fn exif_version(&self) -> Option<String> {
+        self.get(EXIFTag::ExifVersionTag).map(|e| String::from_utf8_lossy(&e.data).into_owned())
+    }
+
+    // Exposure time in seconds.
+    pub fn exposure_time(&self) -> Option<f64> {
+        self.get_f64(EXIFTag::ExposureTimeTag)
+    }
+
+    pub fn f_number(&self) -> Option<f64> {
+        self.get_f64(EXIFTag::FNumberTag)
+    }
+
+    // 0 not defined, 1 manual, 2 normal, 3 aperture priority, 4 shutter
+    // priority, and so on.
+    pub fn exposure_program(&self) -> Option<u32> {
+        self.get_u32(EXIFTag::ExposureProgramTag)
+    }
+
+    // PhotographicSensitivity, formerly ISOSpeedRatings.
+    pub fn iso(&self) -> Option<u32> {
+        self.get_u32(EXIFTag::PhotographicSensitivityTag)
+    }
+
+    // Exposure bias in EV.
+    pub fn exposure_bias(&self) -> Option<f64> {
+        self.get_f64(EXIFTag::ExposureBiasValueTag)
+    }
+
+    pub fn metering_mode(&self) -> Option<u32> {
+        self.get_u32(EXIFTag::MeteringModeTag)
+    }
+
+    // The raw Flash field: bit 0 is set if the flash fired.
+    pub fn flash(&self) -> Option<u32> {
+        self.get_u32(EXIFTag::FlashTag)
+    }
+
+    pub fn flash_fired(&self) -> Option<bool> {
+        self.flash().map(|f| f & 1 != 0)
+    }
+
+    // Focal length of the lens in millimetres.
+    pub fn focal_length(&self) -> Option<f64> {
+        self.get_f64(EXIFTag::FocalLengthTag)
+    }
+
+    pub fn focal_length_35mm(&self) -> Option<u32> {
+        self.get_u32(EXIFTag::FocalLengthIn35mmFilmTag).filter(|&f| f != 0)
+    }
+
+    // 0 auto, 1 manual.
+    pub fn white_balance(&self) -> Option<u32> {
+        self.get_u32(EXIFTag::WhiteBalanceTag)
+    }
+
+    // 1 sRGB, 0xffff uncalibrated.
+    pub fn color_space(&self) -> Option<u32> {
+        self.get_u32(EXIFTag::ColorSpaceTag)
+    }
+
+    pub fn pixel_dimensions(&self) -> Option<(u32, u32)> {
+        Some((self.get_u32(EXIFTag::PixelXDimensionTag)?, self.get_u32(EXIFTag::PixelYDimensionTag)?))
+    }
+
+    // "YYYY:MM:DD HH:MM:SS", local to where the picture was taken.
+    pub fn date_time_original(&self) -> Option<String> {
+        self.get_ascii(EXIFTag::DateTimeOriginalTag)
+    }
+
+    pub fn date_time_digitized(&self) -> Option<String> {
+        self.get_ascii(EXIFTag::DateTimeDigitizedTag)
+    }
+
+    // Offset from UTC of DateTimeOriginal, as "+HH:MM".
+    pub fn offset_time_original(&self) -> Option<String> {
+        self.get_ascii(EXIFTag::OffsetTimeOriginalTag)
+    }
+
+    pub fn sub_sec_time_original(&self) -> Option<String> {
+        self.get_ascii(EXIFTag::SubSecTimeOriginalTag)
+    }
+
+    pub fn body_serial_number(&self) -> Option<String> {
+        self.get_ascii(EXIFTag::BodySerialNumberTag)
+    }
+
+    pub fn lens_make(&self) -> Option<String> {
+        self.get_ascii(EXIFTag::LensMakeTag)
+    }
+
+    pub fn lens_model(&self) -> Option<String> {
+        self.get_ascii(EXIFTag::LensModelTag)
+    }
+
+    pub fn lens_serial_number(&self) -> Option<String> {
+        self.get_ascii(EXIFTag::LensSerialNumberTag)
+    }
+
+    // Minimum and maximum focal length in millimetres, then the minimum
+    // F number at each.  Unknown values are NaN.
+    pub fn lens_specification(&self) -> Option<[f64; 4]> {
+        let v = self.get(EXIFTag::LensSpecificationTag).and_then(|e| e.as_f64s())?;
+        if v.len() != 4 {
+            return None;
+        }
+        Some([v[0], v[1], v[2], v[3]])
+    }
+
+    // The comment follows an 8 byte character code: ASCII, UNICODE (UCS-2
+    // in the file's byte order), JIS or undefined.  JIS is not decoded.
+    pub fn user_comment(&self) -> Option<String> {
+        let e = self.get(EXIFTag::UserCommentTag)?;
+        if e.data.len() < 8 {
+            return None;
+        }
+        let (code, text) = e.data.split_at(8);
+        let comment = match code {
+            b"UNICODE\0" => {
+                let units: Vec<u16> = text.chunks_exact(2).map(|c| match e.byte_order {
+                    TIFFByteOrder::LittleEndian => u16::from_le_bytes([c[0], c[1]]),
+                    TIFFByteOrder::BigEndian => u16::from_be_bytes([c[0], c[1]]),
+                }).collect();
+                String::from_utf16_lossy(&units)
+            },
+            b"JIS\0\0\0\0\0" => return None,
+            _ => String::from_utf8_lossy(text).into_owned(),
+        };
+        let comment = comment.trim_end_matches(|c: char| c == '\0' || c.is_whitespace());
+        if comment.is_empty() { None } else { Some(comment.to_string()) }
+    }
+}
@@ -0,0 +1,238 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and the GDAL GeoTIFF driver's private fields, GDAL_METADATA and
+//  GDAL_NODATA (https://gdal.org/drivers/raster/gtiff.html).
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, ErrorKind};
+use std::collections::BTreeMap;
+
+use {IFD, TIFFTag, SampleFormat, decode_sample_format};
+use image::{Image, ImageData};
+use xml::{self, attribute};
+
+//----------------------------------------------------------------------------
+// Types
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GDALMetadataItem {
+    pub name:   String,
+    pub value:  String,
+    // None for the default domain
+    pub domain: Option<String>,
+    // Band properties such as "scale", "offset", "unittype" or "description"
+    pub role:   Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GDALMetadata {
+    // Items describing the whole dataset
+    pub dataset: Vec<GDALMetadataItem>,
+    // Items describing each band, by band number from 0
+    pub bands:   BTreeMap<u32, Vec<GDALMetadataItem>>,
+}
+
+// The nodata value, typed to match the samples it applies to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoData {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+}
+
+//----------------------------------------------------------------------------
+
+impl GDALMetadata {
+
+    pub fn parse(text: &str) -> Result<GDALMetadata> {
+
+        let (root, _) = xml::parse(text)?;
+        if root.name.name != "GDALMetadata" {
+            return Err(invalid(&format!("Root element is {}, not GDALMetadata", root.name.name)));
+        }
+
+        let mut metadata = GDALMetadata::default();
+        for item in root.children.iter().filter(|e| e.name.name == "Item") {
+            let name = attribute(item, "", "name").ok_or_else(|| invalid("Item without a name"))?;
+            let entry = GDALMetadataItem {
+                name:   name.to_string(),
+                value:  item.text.clone(),
+                domain: attribute(item, "", "domain").filter(|d| !d.is_empty()).map(|d| d.to_string()),
+                role:   attribute(item, "", "role").map(|r| r.to_string()),
+            };
+            match attribute(item, "", "sample") {
+                Some(sample) => {
+                    let band = sample.trim().parse().map_err(|_| invalid(&format!("Bad sample {:?}", sample)))?;
+                    metadata.bands.entry(band).or_insert_with(Vec::new).push(entry);
+                },
+                None => metadata.dataset.push(entry),
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    // A dataset item of the default domain.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        find(&self.dataset, |i| i.name == name && i.domain.is_none())
+    }
+
+    pub fn get_in_domain(&self, domain: &str, name: &str) -> Option<&str> {
+        find(&self.dataset, |i| i.name == name && i.domain.as_ref().is_some_and(|d| d == domain))
+    }
+
+    pub fn band(&self, band: u32) -> &[GDALMetadataItem] {
+        self.bands.get(&band).map(|items| &items[..]).unwrap_or(&[])
+    }
+
+    // A band item of the default domain that is not a band property.
+    pub fn band_get(&self, band: u32, name: &str) -> Option<&str> {
+        find(self.band(band), |i| i.name == name && i.domain.is_none() && i.role.is_none())
+    }
+
+    fn band_role(&self, band: u32, role: &str) -> Option<&str> {
+        find(self.band(band), |i| i.role.as_ref().is_some_and(|r| r == role))
+    }
+
+    // Physical value = raw value * scale + offset
+    pub fn band_scale(&self, band: u32) -> Option<f64> {
+        self.band_role(band, "scale").and_then(|v| v.trim().parse().ok())
+    }
+
+    pub fn band_offset(&self, band: u32) -> Option<f64> {
+        self.band_role(band, "offset").and_then(|v| v.trim().parse().ok())
+    }
+
+    pub fn band_unit(&self, band: u32) -> Option<&str> {
+        self.band_role(band, "unittype")
+    }
+
+    pub fn band_description(&self, band: u32) -> Option<&str> {
+        self.band_role(band, "description")
+    }
+}
+
+impl NoData {
+
+    // Parse the text of GDAL_NODATA for samples of the given format and
+    // size.  None if it cannot be represented in them.
+    pub fn parse(text: &str, format: SampleFormat, bits_per_sample: u16) -> Option<NoData> {
+
+        let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        let value = match text.to_ascii_lowercase().as_str() {
+            "nan" => f64::NAN,
+            "inf" | "+inf" => f64::INFINITY,
+            "-inf" => f64::NEG_INFINITY,
+            _ => text.parse::<f64>().ok()?,
+        };
+        let integral = value.is_finite() && value.fract() == 0.0;
+        let bits = bits_per_sample.clamp(1, 64) as u32;
+
+        match format {
+            SampleFormat::IEEEFloatingPoint => Some(NoData::Float(value)),
+            SampleFormat::TwosComplementSignedInteger if integral => {
+                let (min, max) = (-(2f64.powi(bits as i32 - 1)), 2f64.powi(bits as i32 - 1) - 1.0);
+                if value < min || value > max {
+                    return None;
+                }
+                Some(NoData::Signed(text.parse().unwrap_or(value as i64)))
+            },
+            SampleFormat::UnsignedInteger | SampleFormat::Undefined if integral => {
+                if value < 0.0 || value > 2f64.powi(bits as i32) - 1.0 {
+                    return None;
+                }
+                Some(NoData::Unsigned(text.parse().unwrap_or(value as u64)))
+            },
+            _ => None,
+        }
+    }
+
+    fn as_i128(&self) -> Option<i128> {
+        match *self {
+            NoData::Unsigned(n) => Some(n as i128),
+            NoData::Signed(n) => Some(n as i128),
+            NoData::Float(f) if f.is_finite() && f.fract() == 0.0 => Some(f as i128),
+            NoData::Float(_) => None,
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match *self {
+            NoData::Unsigned(n) => n as f64,
+            NoData::Signed(n) => n as f64,
+            NoData::Float(f) => f,
+        }
+    }
+}
+
+impl Image {
+
+    // Whether each pixel holds data: false where every sample of the pixel
+    // equals the nodata value.  Floating point samples are compared at
+    // their own precision, and NaN matches NaN.
+    pub fn validity_mask(&self, nodata: NoData) -> Vec<bool> {
+
+        let spp = (self.samples_per_pixel as usize).max(1);
+        let integer = nodata.as_i128();
+        let float = nodata.as_f64();
+
+        let is_nodata: Vec<bool> = match self.data {
+            ImageData::U8(ref v) => v.iter().map(|&x| Some(x as i128) == integer).collect(),
+            ImageData::U16(ref v) => v.iter().map(|&x| Some(x as i128) == integer).collect(),
+            ImageData::U32(ref v) => v.iter().map(|&x| Some(x as i128) == integer).collect(),
+            ImageData::U64(ref v) => v.iter().map(|&x| Some(x as i128) == integer).collect(),
+            ImageData::I8(ref v) => v.iter().map(|&x| Some(x as i128) == integer).collect(),
+            ImageData::I16(ref v) => v.iter().map(|&x| Some(x as i128) == integer).collect(),
+            ImageData::I32(ref v) => v.iter().map(|&x| Some(x as i128) == integer).collect(),
+            ImageData::I64(ref v) => v.iter().map(|&x| Some(x as i128) == integer).collect(),
+            ImageData::F32(ref v) => {
+                let nd = float as f32;
+                v.iter().map(|&x| x == nd || (x.is_nan() && nd.is_nan())).collect()
+            },
+            ImageData::F64(ref v) => v.iter().map(|&x| x == float || (x.is_nan() && float.is_nan())).collect(),
+        };
+
+        is_nodata.chunks(spp).map(|pixel| !pixel.iter().all(|&n| n)).collect()
+    }
+}
+
+impl IFD {
+
+    pub fn gdal_metadata(&self) -> Result<Option<GDALMetadata>> {
+        match self.get(TIFFTag::GDALMetadataTag) {
+            Some(e) => Ok(Some(GDALMetadata::parse(&String::from_utf8_lossy(&e.data))?)),
+            None => Ok(None),
+        }
+    }
+
+    // The nodata value, typed by this IFD's SampleFormat and BitsPerSample.
+    pub fn gdal_nodata(&self) -> Option<NoData> {
+        let text = String::from_utf8_lossy(&self.get(TIFFTag::GDALNoDataTag)?.data).into_owned();
+        let format = self.get_u32(TIFFTag::SampleFormatTag)
+            .and_then(|f| decode_sample_format(f as u16))
+            .unwrap_or(SampleFormat::UnsignedInteger);
+        let bits = self.get_u32(TIFFTag::BitsPerSampleTag).unwrap_or(1);
+        NoData::parse(&text, format, bits as u16)
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+fn find<F: Fn(&GDALMetadataItem) -> bool>(items: &[GDALMetadataItem], predicate: F) -> Option<&str> {
+    items.iter().find(|&i| predicate(i)).map(|i| i.value.as_str())
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid GDAL_METADATA: {}", message))
+}
@@ -0,0 +1,479 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and the OGC GeoTIFF Standard, version 1.1 (OGC 19-008r4).
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, ErrorKind};
+use std::collections::BTreeMap;
+
+use {IFD, IFDEntry, TIFFTag};
+
+//----------------------------------------------------------------------------
+// Constants
+
+// Fields holding GeoTIFF information
+const GEOTIFF_TAGS: [TIFFTag; 6] = [
+    TIFFTag::ModelPixelScaleTag,
+    TIFFTag::ModelTiepointTag,
+    TIFFTag::ModelTransformationTag,
+    TIFFTag::GeoKeyDirectoryTag,
+    TIFFTag::GeoDoubleParamsTag,
+    TIFFTag::GeoAsciiParamsTag,
+];
+
+// Codes meaning the value is defined by other keys rather than by EPSG
+const USER_DEFINED: u16 = 32767;
+const UNDEFINED: u16 = 0;
+
+//----------------------------------------------------------------------------
+// Types
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GeoKey {
+
+    // GeoTIFF configuration
+
+    GTModelTypeGeoKey             = 1024,
+    GTRasterTypeGeoKey            = 1025,
+    GTCitationGeoKey              = 1026,
+
+    // Geodetic CRS
+
+    GeographicTypeGeoKey          = 2048,
+    GeogCitationGeoKey            = 2049,
+    GeogGeodeticDatumGeoKey       = 2050,
+    GeogPrimeMeridianGeoKey       = 2051,
+    GeogLinearUnitsGeoKey         = 2052,
+    GeogLinearUnitSizeGeoKey      = 2053,
+    GeogAngularUnitsGeoKey        = 2054,
+    GeogAngularUnitSizeGeoKey     = 2055,
+    GeogEllipsoidGeoKey           = 2056,
+    GeogSemiMajorAxisGeoKey       = 2057,
+    GeogSemiMinorAxisGeoKey       = 2058,
+    GeogInvFlatteningGeoKey       = 2059,
+    GeogAzimuthUnitsGeoKey        = 2060,
+    GeogPrimeMeridianLongGeoKey   = 2061,
+
+    // Projected CRS
+
+    ProjectedCSTypeGeoKey         = 3072,
+    PCSCitationGeoKey             = 3073,
+    ProjectionGeoKey              = 3074,
+    ProjCoordTransGeoKey          = 3075,
+    ProjLinearUnitsGeoKey         = 3076,
+    ProjLinearUnitSizeGeoKey      = 3077,
+    ProjStdParallel1GeoKey        = 3078,
+    ProjStdParallel2GeoKey        = 3079,
+    ProjNatOriginLongGeoKey       = 3080,
+    ProjNatOriginLatGeoKey        = 3081,
+    ProjFalseEastingGeoKey        = 3082,
+    ProjFalseNorthingGeoKey       = 3083,
+    ProjFalseOriginLongGeoKey     = 3084,
+    ProjFalseOriginLatGeoKey      = 3085,
+    ProjFalseOriginEastingGeoKey  = 3086,
+    ProjFalseOriginNorthingGeoKey = 3087,
+    ProjCenterLongGeoKey          = 3088,
+    ProjCenterLatGeoKey           = 3089,
+    ProjCenterEastingGeoKey       = 3090,
+    ProjCenterNorthingGeoKey      = 3091,
+    ProjScaleAtNatOriginGeoKey    = 3092,
+    ProjScaleAtCenterGeoKey       = 3093,
+    ProjAzimuthAngleGeoKey        = 3094,
+    ProjStraightVertPoleLongGeoKey = 3095,
+
+    // Vertical CRS
+
+    VerticalCSTypeGeoKey          = 4096,
+    VerticalCitationGeoKey        = 4097,
+    VerticalDatumGeoKey           = 4098,
+    VerticalUnitsGeoKey           = 4099,
+}
+
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModelType {
+    Projected  = 1,
+    Geographic = 2,
+    Geocentric = 3,
+}
+
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RasterType {
+    PixelIsArea  = 1,
+    PixelIsPoint = 2,
+}
+
+// A key's value, held in the directory itself or in the double or ASCII
+// parameter fields.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeoKeyValue {
+    Shorts(Vec<u16>),
+    Doubles(Vec<f64>),
+    Ascii(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoKeyDirectory {
+    pub version:  u16,
+    // Key revision and minor revision, 1.0 or 1.1
+    pub revision: (u16, u16),
+    pub keys:     BTreeMap<u16, GeoKeyValue>,
+}
+
+// A raster position (I, J, K) and the model position (X, Y, Z) it maps to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tiepoint {
+    pub raster: [f64; 3],
+    pub model:  [f64; 3],
+}
+
+// Maps raster space (column, row) to model space (x, y):
+//
+//     x = m[0][0] * column + m[0][1] * row + m[0][2]
+//     y = m[1][0] * column + m[1][1] * row + m[1][2]
+//
+// In raster space the centre of the top left pixel is (0.5, 0.5) when the
+// raster type is PixelIsArea, and (0, 0) when it is PixelIsPoint.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AffineTransform {
+    pub matrix: [[f64; 3]; 2],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoTIFF {
+    pub pixel_scale:    Option<[f64; 3]>,
+    pub tiepoints:      Vec<Tiepoint>,
+    // 4x4 matrix in row-major order
+    pub transformation: Option<[f64; 16]>,
+    pub keys:           GeoKeyDirectory,
+}
+
+//----------------------------------------------------------------------------
+
+impl Default for GeoKeyDirectory {
+    fn default() -> GeoKeyDirectory {
+        GeoKeyDirectory { version: 1, revision: (1, 1), keys: BTreeMap::new() }
+    }
+}
+
+impl GeoKeyDirectory {
+
+    // Parse the directory, taking values from the parameter fields.
+    pub fn parse(directory: &[u16], doubles: &[f64], ascii: &[u8]) -> Result<GeoKeyDirectory> {
+
+        if directory.len() < 4 {
+            return Err(invalid("GeoKeyDirectory has no header"));
+        }
+        let count = directory[3] as usize;
+        if directory.len() < 4 * (count + 1) {
+            return Err(invalid(&format!("GeoKeyDirectory too short for {} keys", count)));
+        }
+
+        let mut keys = BTreeMap::new();
+        for key in directory[4..4 * (count + 1)].chunks_exact(4) {
+            let (id, location, n, offset) = (key[0], key[1], key[2] as usize, key[3] as usize);
+            let missing = || invalid(&format!("GeoKey {} lies outside its field", id));
+            let value = match location {
+                0 => GeoKeyValue::Shorts(vec![key[3]]),
+                l if l == TIFFTag::GeoKeyDirectoryTag as u16 =>
+                    GeoKeyValue::Shorts(directory.get(offset..offset + n).ok_or_else(missing)?.to_vec()),
+                l if l == TIFFTag::GeoDoubleParamsTag as u16 =>
+                    GeoKeyValue::Doubles(doubles.get(offset..offset + n).ok_or_else(missing)?.to_vec()),
+                l if l == TIFFTag::GeoAsciiParamsTag as u16 => {
+                    let text = ascii.get(offset..offset + n).ok_or_else(missing)?;
+                    let text: String = text.iter().map(|&b| b as char).collect();
+                    GeoKeyValue::Ascii(text.trim_end_matches(['|', '\0']).to_string())
+                },
+                _ => return Err(invalid(&format!("GeoKey {} is held in unknown field {}", id, location))),
+            };
+            keys.insert(id, value);
+        }
+
+        Ok(GeoKeyDirectory { version: directory[0], revision: (directory[1], directory[2]), keys })
+    }
+
+    pub fn get(&self, key: GeoKey) -> Option<&GeoKeyValue> {
+        self.keys.get(&(key as u16))
+    }
+
+    pub fn set(&mut self, key: GeoKey, value: GeoKeyValue) {
+        self.keys.insert(key as u16, value);
+    }
+
+    pub fn get_short(&self, key: GeoKey) -> Option<u16> {
+        match self.get(key) {
+            Some(GeoKeyValue::Shorts(v)) => v.first().cloned(),
+            _ => None,
+        }
+    }
+
+    pub fn get_double(&self, key: GeoKey) -> Option<f64> {
+        match self.get(key) {
+            Some(GeoKeyValue::Doubles(v)) => v.first().cloned(),
+            _ => None,
+        }
+    }
+
+    pub fn get_ascii(&self, key: GeoKey) -> Option<&str> {
+        match self.get(key) {
+            Some(GeoKeyValue::Ascii(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn model_type(&self) -> Option<ModelType> {
+        match self.get_short(GeoKey::GTModelTypeGeoKey)? {
+            1 => Some(ModelType::Projected),
+            2 => Some(ModelType::Geographic),
+            3 => Some(ModelType::Geocentric),
+            _ => None,
+        }
+    }
+
+    // PixelIsArea if not given.
+    pub fn raster_type(&self) -> RasterType {
+        match self.get_short(GeoKey::GTRasterTypeGeoKey) {
+            Some(2) => RasterType::PixelIsPoint,
+            _ => RasterType::PixelIsArea,
+        }
+    }
+
+    pub fn citation(&self) -> Option<&str> {
+        self.get_ascii(GeoKey::GTCitationGeoKey)
+    }
+
+    // EPSG codes, where not undefined or user-defined
+    pub fn projected_cs_type(&self) -> Option<u16> {
+        self.get_short(GeoKey::ProjectedCSTypeGeoKey).filter(|&c| c != UNDEFINED && c != USER_DEFINED)
+    }
+
+    pub fn geographic_type(&self) -> Option<u16> {
+        self.get_short(GeoKey::GeographicTypeGeoKey).filter(|&c| c != UNDEFINED && c != USER_DEFINED)
+    }
+
+    pub fn vertical_cs_type(&self) -> Option<u16> {
+        self.get_short(GeoKey::VerticalCSTypeGeoKey).filter(|&c| c != UNDEFINED && c != USER_DEFINED)
+    }
+
+    pub fn linear_units(&self) -> Option<u16> {
+        self.get_short(GeoKey::ProjLinearUnitsGeoKey)
+    }
+
+    pub fn angular_units(&self) -> Option<u16> {
+        self.get_short(GeoKey::GeogAngularUnitsGeoKey)
+    }
+
+    // The EPSG code of the CRS: the projected CRS if there is one, otherwise
+    // the geographic CRS.
+    pub fn epsg(&self) -> Option<u16> {
+        self.projected_cs_type().or_else(|| self.geographic_type())
+    }
+
+    // The directory, double and ASCII parameter values for writing.
+    fn encode(&self) -> (Vec<u16>, Vec<f64>, String) {
+        let mut directory = vec![self.version, self.revision.0, self.revision.1, self.keys.len() as u16];
+        let mut extra_shorts = Vec::new();
+        let mut doubles = Vec::new();
+        let mut ascii = String::new();
+        let extra_start = 4 * (self.keys.len() + 1);
+
+        for (&id, value) in &self.keys {
+            let key = match *value {
+                GeoKeyValue::Shorts(ref v) if v.len() == 1 => [id, 0, 1, v[0]],
+                GeoKeyValue::Shorts(ref v) => {
+                    let offset = extra_start + extra_shorts.len();
+                    extra_shorts.extend_from_slice(v);
+                    [id, TIFFTag::GeoKeyDirectoryTag as u16, v.len() as u16, offset as u16]
+                },
+                GeoKeyValue::Doubles(ref v) => {
+                    let offset = doubles.len();
+                    doubles.extend_from_slice(v);
+                    [id, TIFFTag::GeoDoubleParamsTag as u16, v.len() as u16, offset as u16]
+                },
+                GeoKeyValue::Ascii(ref s) => {
+                    let offset = ascii.len();
+                    ascii.push_str(s);
+                    ascii.push('|');
+                    [id, TIFFTag::GeoAsciiParamsTag as u16, (s.len() + 1) as u16, offset as u16]
+                },
+            };
+            directory.extend_from_slice(&key);
+        }
+        directory.extend_from_slice(&extra_shorts);
+
+        (directory, doubles, ascii)
+    }
+}
+
+impl AffineTransform {
+
+    pub fn pixel_to_model(&self, column: f64, row: f64) -> (f64, f64) {
+        let m = &self.matrix;
+        (m[0][0] * column + m[0][1] * row + m[0][2], m[1][0] * column + m[1][1] * row + m[1][2])
+    }
+
+    // None if the transform cannot be inverted.
+    pub fn model_to_pixel(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        self.inverse().map(|t| t.pixel_to_model(x, y))
+    }
+
+    pub fn inverse(&self) -> Option<AffineTransform> {
+        let [[a, b, c], [d, e, f]] = self.matrix;
+        let det = a * e - b * d;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        Some(AffineTransform {
+            matrix: [[e / det, -b / det, (b * f - c * e) / det],
+                     [-d / det, a / det, (c * d - a * f) / det]],
+        })
+    }
+}
+
+impl GeoTIFF {
+
+    pub fn new(keys: GeoKeyDirectory) -> GeoTIFF {
+        GeoTIFF { pixel_scale: None, tiepoints: Vec::new(), transformation: None, keys }
+    }
+
+    // The raster to model transform, from ModelTransformation if present,
+    // otherwise from the first tiepoint and the pixel scale.
+    pub fn transform(&self) -> Option<AffineTransform> {
+        if let Some(m) = self.transformation {
+            return Some(AffineTransform { matrix: [[m[0], m[1], m[3]], [m[4], m[5], m[7]]] });
+        }
+        let scale = self.pixel_scale?;
+        let tiepoint = self.tiepoints.first()?;
+        let ([i, j, _], [x, y, _]) = (tiepoint.raster, tiepoint.model);
+        Some(AffineTransform {
+            matrix: [[scale[0], 0.0, x - i * scale[0]],
+                     [0.0, -scale[1], y + j * scale[1]]],
+        })
+    }
+
+    // Record a transform as a tiepoint and pixel scale if it has no rotation
+    // and rows run southward, otherwise as ModelTransformation.
+    pub fn set_transform(&mut self, transform: &AffineTransform) {
+        let [[a, b, c], [d, e, f]] = transform.matrix;
+        if b == 0.0 && d == 0.0 && a > 0.0 && e < 0.0 {
+            self.pixel_scale = Some([a, -e, 0.0]);
+            self.tiepoints = vec![Tiepoint { raster: [0.0; 3], model: [c, f, 0.0] }];
+            self.transformation = None;
+        } else {
+            self.pixel_scale = None;
+            self.tiepoints.clear();
+            self.transformation = Some([a, b, 0.0, c,
+                                        d, e, 0.0, f,
+                                        0.0, 0.0, 0.0, 0.0,
+                                        0.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    // The GeoTIFF fields, for `IFD::set_geotiff` or `StripWriter::set_tag`.
+    pub fn entries(&self) -> Vec<IFDEntry> {
+
+        let mut entries = Vec::new();
+        if let Some(ref scale) = self.pixel_scale {
+            entries.push(IFDEntry::doubles(TIFFTag::ModelPixelScaleTag, scale));
+        }
+        if !self.tiepoints.is_empty() {
+            let values: Vec<f64> = self.tiepoints.iter().flat_map(|t| [t.raster, t.model].concat()).collect();
+            entries.push(IFDEntry::doubles(TIFFTag::ModelTiepointTag, &values));
+        }
+        if let Some(ref m) = self.transformation {
+            entries.push(IFDEntry::doubles(TIFFTag::ModelTransformationTag, m));
+        }
+
+        let (directory, doubles, ascii) = self.keys.encode();
+        entries.push(IFDEntry::shorts(TIFFTag::GeoKeyDirectoryTag, &directory));
+        if !doubles.is_empty() {
+            entries.push(IFDEntry::doubles(TIFFTag::GeoDoubleParamsTag, &doubles));
+        }
+        if !ascii.is_empty() {
+            entries.push(IFDEntry::ascii(TIFFTag::GeoAsciiParamsTag, &ascii));
+        }
+        entries
+    }
+}
+
+impl IFD {
+
+    // None if the IFD has none of the GeoTIFF fields.
+    pub fn geotiff(&self) -> Result<Option<GeoTIFF>> {
+
+        if GEOTIFF_TAGS.iter().all(|&tag| self.get(tag).is_none()) {
+            return Ok(None);
+        }
+
+        let doubles = |tag| self.get(tag).map(|e| {
+            e.as_f64s().ok_or_else(|| invalid(&format!("{:?} of type {:?}", tag, e.typ)))
+        }).transpose();
+
+        let pixel_scale = match doubles(TIFFTag::ModelPixelScaleTag)? {
+            Some(v) if v.len() >= 2 => Some([v[0], v[1], v.get(2).cloned().unwrap_or(0.0)]),
+            Some(v) => return Err(invalid(&format!("ModelPixelScale has {} values", v.len()))),
+            None => None,
+        };
+
+        let tiepoints = doubles(TIFFTag::ModelTiepointTag)?.unwrap_or_default();
+        if !tiepoints.len().is_multiple_of(6) {
+            return Err(invalid(&format!("ModelTiepoint has {} values", tiepoints.len())));
+        }
+        let tiepoints = tiepoints.chunks_exact(6).map(|t| Tiepoint {
+            raster: [t[0], t[1], t[2]],
+            model: [t[3], t[4], t[5]],
+        }).collect();
+
+        let transformation = match doubles(TIFFTag::ModelTransformationTag)? {
+            Some(v) => {
+                let mut m = [0.0; 16];
+                if v.len() != 16 {
+                    return Err(invalid(&format!("ModelTransformation has {} values", v.len())));
+                }
+                m.copy_from_slice(&v);
+                Some(m)
+            },
+            None => None,
+        };
+
+        let keys = match self.get(TIFFTag::GeoKeyDirectoryTag) {
+            Some(e) => {
+                let directory: Vec<u16> = e.as_u32s().ok_or_else(|| invalid("GeoKeyDirectory is not SHORT"))?
+                    .into_iter().map(|v| v as u16).collect();
+                let double_params = doubles(TIFFTag::GeoDoubleParamsTag)?.unwrap_or_default();
+                let ascii_params = self.get(TIFFTag::GeoAsciiParamsTag).map(|e| &e.data[..]).unwrap_or(&[]);
+                GeoKeyDirectory::parse(&directory, &double_params, ascii_params)?
+            },
+            None => GeoKeyDirectory::default(),
+        };
+
+        Ok(Some(GeoTIFF { pixel_scale, tiepoints, transformation, keys }))
+    }
+
+    // Replace any GeoTIFF fields with these.
+    pub fn set_geotiff(&mut self, geotiff: &GeoTIFF) {
+        for &tag in GEOTIFF_TAGS.iter() {
+            self.remove(tag as u16);
+        }
+        for entry in geotiff.entries() {
+            self.set(entry);
+        }
+    }
+}
+
+//----------------------------------------------------------------------------
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid GeoTIFF: {}", message))
+}
@@ -0,0 +1,281 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and the Exif 2.32 specification (CIPA DC-008-2019).
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::Result;
+
+use {IFD, IFDEntry, TIFFHeader, TIFFTag, TagType, SeekableReader};
+use reader::TIFFReader;
+
+//----------------------------------------------------------------------------
+// Types
+
+// Tags of the GPS IFD, numbered from 0 independently of `TIFFTag`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GPSTag {
+    VersionIDTag                  = 0x0000,
+    LatitudeRefTag                = 0x0001,
+    LatitudeTag                   = 0x0002,
+    LongitudeRefTag               = 0x0003,
+    LongitudeTag                  = 0x0004,
+    AltitudeRefTag                = 0x0005,
+    AltitudeTag                   = 0x0006,
+    TimeStampTag                  = 0x0007,
+    SatellitesTag                 = 0x0008,
+    StatusTag                     = 0x0009,
+    MeasureModeTag                = 0x000a,
+    DOPTag                        = 0x000b,
+    SpeedRefTag                   = 0x000c,
+    SpeedTag                      = 0x000d,
+    TrackRefTag                   = 0x000e,
+    TrackTag                      = 0x000f,
+    ImgDirectionRefTag            = 0x0010,
+    ImgDirectionTag               = 0x0011,
+    MapDatumTag                   = 0x0012,
+    DestLatitudeRefTag            = 0x0013,
+    DestLatitudeTag               = 0x0014,
+    DestLongitudeRefTag           = 0x0015,
+    DestLongitudeTag              = 0x0016,
+    DestBearingRefTag             = 0x0017,
+    DestBearingTag                = 0x0018,
+    DestDistanceRefTag            = 0x0019,
+    DestDistanceTag               = 0x001a,
+    ProcessingMethodTag           = 0x001b,
+    AreaInformationTag            = 0x001c,
+    DateStampTag                  = 0x001d,
+    DifferentialTag               = 0x001e,
+    HPositioningErrorTag          = 0x001f,
+}
+
+// The fields of a GPS IFD.
+#[derive(Clone, Debug)]
+pub struct GPS {
+    pub ifd: IFD,
+}
+
+//----------------------------------------------------------------------------
+
+pub fn decode_gps_tag(value: u16) -> Option<GPSTag> {
+    match value {
+        0x0000 => Some(GPSTag::VersionIDTag),
+        0x0001 => Some(GPSTag::LatitudeRefTag),
+        0x0002 => Some(GPSTag::LatitudeTag),
+        0x0003 => Some(GPSTag::LongitudeRefTag),
+        0x0004 => Some(GPSTag::LongitudeTag),
+        0x0005 => Some(GPSTag::AltitudeRefTag),
+        0x0006 => Some(GPSTag::AltitudeTag),
+        0x0007 => Some(GPSTag::TimeStampTag),
+        0x0008 => Some(GPSTag::SatellitesTag),
+        0x0009 => Some(GPSTag::StatusTag),
+        0x000a => Some(GPSTag::MeasureModeTag),
+        0x000b => Some(GPSTag::DOPTag),
+        0x000c => Some(GPSTag::SpeedRefTag),
+        0x000d => Some(GPSTag::SpeedTag),
+        0x000e => Some(GPSTag::TrackRefTag),
+        0x000f => Some(GPSTag::TrackTag),
+        0x0010 => Some(GPSTag::ImgDirectionRefTag),
+        0x0011 => Some(GPSTag::ImgDirectionTag),
+        0x0012 => Some(GPSTag::MapDatumTag),
+        0x0013 => Some(GPSTag::DestLatitudeRefTag),
+        0x0014 => Some(GPSTag::DestLatitudeTag),
+        0x0015 => Some(GPSTag::DestLongitudeRefTag),
+        0x0016 => Some(GPSTag::DestLongitudeTag),
+        0x0017 => Some(GPSTag::DestBearingRefTag),
+        0x0018 => Some(GPSTag::DestBearingTag),
+        0x0019 => Some(GPSTag::DestDistanceRefTag),
+        0x001a => Some(GPSTag::DestDistanceTag),
+        0x001b => Some(GPSTag::ProcessingMethodTag),
+        0x001c => Some(GPSTag::AreaInformationTag),
+        0x001d => Some(GPSTag::DateStampTag),
+        0x001e => Some(GPSTag::DifferentialTag),
+        0x001f => Some(GPSTag::HPositioningErrorTag),
+        _ => None,
+    }
+}
+
+// Returns (type, count) for GPS tags. 0 is unknown/variable/unspecified.
+pub fn type_and_count_for_gps_tag(tag: GPSTag) -> Option<(TagType, u32)> {
+    match tag {
+        GPSTag::VersionIDTag                  => Some((TagType::ByteTag, 4)),
+        GPSTag::LatitudeRefTag                => Some((TagType::ASCIITag, 2)),
+        GPSTag::LatitudeTag                   => Some((TagType::RationalTag, 3)),
+        GPSTag::LongitudeRefTag               => Some((TagType::ASCIITag, 2)),
+        GPSTag::LongitudeTag                  => Some((TagType::RationalTag, 3)),
+        GPSTag::AltitudeRefTag                => Some((TagType::ByteTag, 1)),
+        GPSTag::AltitudeTag                   => Some((TagType::RationalTag, 1)),
+        GPSTag::TimeStampTag                  => Some((TagType::RationalTag, 3)),
+        GPSTag::SatellitesTag                 => Some((TagType::ASCIITag, 0)),
+        GPSTag::StatusTag                     => Some((TagType::ASCIITag, 2)),
+        GPSTag::MeasureModeTag                => Some((TagType::ASCIITag, 2)),
+        GPSTag::DOPTag                        => Some((TagType::RationalTag, 1)),
+        GPSTag::SpeedRefTag                   => Some((TagType::ASCIITag, 2)),
+        GPSTag::SpeedTag                      => Some((TagType::RationalTag, 1)),
+        GPSTag::TrackRefTag                   => Some((TagType::ASCIITag, 2)),
+        GPSTag::TrackTag                      => Some((TagType::RationalTag, 1)),
+        GPSTag::ImgDirectionRefTag            => Some((TagType::ASCIITag, 2)),
+        GPSTag::ImgDirectionTag               => Some((TagType::RationalTag, 1)),
+        GPSTag::MapDatumTag                   => Some((TagType::ASCIITag, 0)),
+        GPSTag::DestLatitudeRefTag            => Some((TagType::ASCIITag, 2)),
+        GPSTag::DestLatitudeTag               => Some((TagType::RationalTag, 3)),
+        GPSTag::DestLongitudeRefTag           => Some((TagType::ASCIITag, 2)),
+        GPSTag::DestLongitudeTag              => Some((TagType::RationalTag, 3)),
+        GPSTag::DestBearingRefTag             => Some((TagType::ASCIITag, 2)),
+        GPSTag::DestBearingTag                => Some((TagType::RationalTag, 1)),
+        GPSTag::DestDistanceRefTag            => Some((TagType::ASCIITag, 2)),
+        GPSTag::DestDistanceTag               => Some((TagType::RationalTag, 1)),
+        GPSTag::ProcessingMethodTag           => Some((TagType::UndefinedTag, 0)),
+        GPSTag::AreaInformationTag            => Some((TagType::UndefinedTag, 0)),
+        GPSTag::DateStampTag                  => Some((TagType::ASCIITag, 11)),
+        GPSTag::DifferentialTag               => Some((TagType::ShortTag, 1)),
+        GPSTag::HPositioningErrorTag          => Some((TagType::RationalTag, 1)),
+    }
+}
+
+//----------------------------------------------------------------------------
+
+impl TIFFReader {
+
+    // Read the GPS IFD that `ifd` points to, if any.
+    pub fn read_gps(&self, reader: &mut dyn SeekableReader, header: &TIFFHeader, ifd: &IFD) -> Result<Option<GPS>> {
+        let gps = self.read_pointed_ifd(reader, header, ifd, TIFFTag::GPSTag)?;
+        Ok(gps.map(|ifd| GPS { ifd }))
+    }
+}
+
+impl GPS {
+
+    pub fn get(&self, tag: GPSTag) -> Option<&IFDEntry> {
+        self.ifd.get_raw(tag as u16)
+    }
+
+    // Rationals with a zero denominator are treated as absent.
+    fn get_f64s(&self, tag: GPSTag) -> Option<Vec<f64>> {
+        self.get(tag).and_then(|e| e.as_f64s()).filter(|v| v.iter().all(|x| x.is_finite()))
+    }
+
+    fn get_f64(&self, tag: GPSTag) -> Option<f64> {
+        self.get_f64s(tag).and_then(|v| v.first().cloned())
+    }
+
+    fn get_ascii(&self, tag: GPSTag) -> Option<String> {
+        self.get(tag).and_then(|e| e.as_ascii()).map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+    }
+
+    // The first letter of a reference field, such as 'N' or 'W'.
+    fn get_ref(&self, tag: GPSTag) -> Option<char> {
+        self.get_ascii(tag).and_then(|s| s.chars().next()).map(|c| c.to_ascii_uppercase())
+    }
+
+    // Version of the GPS IFD, such as [2, 3, 0, 0].
+    pub fn version(&self) -> Option<[u8; 4]> {
+        let e = self.get(GPSTag::VersionIDTag)?;
+        if e.data.len() != 4 {
+            return None;
+        }
+        Some([e.data[0], e.data[1], e.data[2], e.data[3]])
+    }
+
+    // Latitude in signed decimal degrees, negative in the southern
+    // hemisphere.
+    pub fn latitude(&self) -> Option<f64> {
+        signed_degrees(self.get_f64s(GPSTag::LatitudeTag)?, self.get_ref(GPSTag::LatitudeRefTag)?, 'N', 'S')
+    }
+
+    // Longitude in signed decimal degrees, negative west of Greenwich.
+    pub fn longitude(&self) -> Option<f64> {
+        signed_degrees(self.get_f64s(GPSTag::LongitudeTag)?, self.get_ref(GPSTag::LongitudeRefTag)?, 'E', 'W')
+    }
+
+    // Latitude and longitude in signed decimal degrees.
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        Some((self.latitude()?, self.longitude()?))
+    }
+
+    // Altitude in metres, negative below sea level.  AltitudeRef defaults
+    // to above sea level.
+    pub fn altitude(&self) -> Option<f64> {
+        let altitude = self.get_f64(GPSTag::AltitudeTag)?;
+        match self.get(GPSTag::AltitudeRefTag).and_then(|e| e.data.first().cloned()) {
+            Some(1) => Some(-altitude),
+            _ => Some(altitude),
+        }
+    }
+
+    // UTC time of day as hours, minutes and seconds.
+    pub fn time_stamp(&self) -> Option<(f64, f64, f64)> {
+        let v = self.get_f64s(GPSTag::TimeStampTag)?;
+        if v.len() != 3 {
+            return None;
+        }
+        Some((v[0], v[1], v[2]))
+    }
+
+    // UTC date as "YYYY:MM:DD".
+    pub fn date_stamp(&self) -> Option<String> {
+        self.get_ascii(GPSTag::DateStampTag)
+    }
+
+    // Dilution of precision: HDOP in 2D mode, PDOP in 3D mode.
+    pub fn dop(&self) -> Option<f64> {
+        self.get_f64(GPSTag::DOPTag)
+    }
+
+    // "2" or "3" for a two or three dimensional measurement.
+    pub fn measure_mode(&self) -> Option<String> {
+        self.get_ascii(GPSTag::MeasureModeTag)
+    }
+
+    pub fn satellites(&self) -> Option<String> {
+        self.get_ascii(GPSTag::SatellitesTag)
+    }
+
+    pub fn map_datum(&self) -> Option<String> {
+        self.get_ascii(GPSTag::MapDatumTag)
+    }
+
+    // Speed in kilometres per hour, whatever unit SpeedRef gives.
+    pub fn speed_kmh(&self) -> Option<f64> {
+        let speed = self.get_f64(GPSTag::SpeedTag)?;
+        match self.get_ref(GPSTag::SpeedRefTag).unwrap_or('K') {
+            'K' => Some(speed),
+            'M' => Some(speed * 1.609_344),
+            'N' => Some(speed * 1.852),
+            _ => None,
+        }
+    }
+
+    // Direction of the image in degrees, relative to true ('T') or magnetic
+    // ('M') north.
+    pub fn img_direction(&self) -> Option<(f64, char)> {
+        let direction = self.get_f64(GPSTag::ImgDirectionTag)?;
+        Some((direction, self.get_ref(GPSTag::ImgDirectionRefTag).unwrap_or('T')))
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+// Degrees, minutes and seconds to signed decimal degrees.  Some writers
+// store decimal minutes with zero seconds, or fewer than three values.
+fn signed_degrees(dms: Vec<f64>, hemisphere: char, positive: char, negative: char) -> Option<f64> {
+    if dms.is_empty() || dms.len() > 3 {
+        return None;
+    }
+    let degrees = dms.iter().zip(&[1.0, 60.0, 3600.0]).map(|(v, d)| v / d).sum::<f64>();
+    match hemisphere {
+        c if c == positive => Some(degrees),
+        c if c == negative => Some(-degrees),
+        _ => None,
+    }
+}
@@ -0,0 +1,232 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and the ICC profile format specification, ICC.1:2010 (profile version
+//  4.3.0.0), which also describes the version 2 tag types still in use.
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, ErrorKind};
+
+use {IFD, IFDEntry, TIFFTag, TagType};
+
+//----------------------------------------------------------------------------
+// Constants
+
+const HEADER_SIZE: usize = 128;
+const MAGIC: &[u8; 4] = b"acsp";
+
+//----------------------------------------------------------------------------
+// Types
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ICCProfileClass {
+    Input,
+    Display,
+    Output,
+    DeviceLink,
+    ColorSpace,
+    Abstract,
+    NamedColor,
+    Other([u8; 4]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ICCColorSpace {
+    XYZ,
+    Lab,
+    Luv,
+    YCbCr,
+    Yxy,
+    RGB,
+    Gray,
+    HSV,
+    HLS,
+    CMYK,
+    CMY,
+    // Generic colour spaces of 2 to 15 channels
+    Colors(u8),
+    Other([u8; 4]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+    Other(u32),
+}
+
+// An ICC profile, with the fields of its header that decide whether and how
+// to convert an image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ICCProfile {
+    // (major, minor, bug fix), such as (4, 3, 0)
+    pub version:          (u8, u8, u8),
+    pub class:            ICCProfileClass,
+    pub color_space:      ICCColorSpace,
+    // Profile connection space: XYZ or Lab
+    pub pcs:              ICCColorSpace,
+    pub rendering_intent: RenderingIntent,
+    pub cmm:              [u8; 4],
+    pub creator:          [u8; 4],
+    pub data:             Vec<u8>,
+}
+
+//----------------------------------------------------------------------------
+
+impl ICCProfile {
+
+    pub fn parse(data: &[u8]) -> Result<ICCProfile> {
+
+        if data.len() < HEADER_SIZE + 4 {
+            return Err(invalid("Too short for a profile header"));
+        }
+        if &data[36..40] != MAGIC {
+            return Err(invalid("Missing 'acsp' signature"));
+        }
+        let size = read_u32(data, 0) as usize;
+        if size > data.len() {
+            return Err(invalid(&format!("Profile of {} bytes holds only {}", size, data.len())));
+        }
+
+        let rendering_intent = match read_u32(data, 64) & 0xffff {
+            0 => RenderingIntent::Perceptual,
+            1 => RenderingIntent::RelativeColorimetric,
+            2 => RenderingIntent::Saturation,
+            3 => RenderingIntent::AbsoluteColorimetric,
+            n => RenderingIntent::Other(n),
+        };
+
+        Ok(ICCProfile {
+            version:     (data[8], data[9] >> 4, data[9] & 0x0f),
+            class:       decode_class(signature(data, 12)),
+            color_space: decode_color_space(signature(data, 16)),
+            pcs:         decode_color_space(signature(data, 20)),
+            rendering_intent,
+            cmm:         signature(data, 4),
+            creator:     signature(data, 80),
+            data:        data[..size.max(HEADER_SIZE)].to_vec(),
+        })
+    }
+
+    // The data of a tag, by its signature, such as b"desc".
+    pub fn tag(&self, sig: &[u8; 4]) -> Option<&[u8]> {
+        let count = read_u32(&self.data, HEADER_SIZE) as usize;
+        (0..count).map(|i| HEADER_SIZE + 4 + 12 * i)
+            .take_while(|&p| p + 12 <= self.data.len())
+            .find(|&p| &self.data[p..p + 4] == sig)
+            .and_then(|p| {
+                let (offset, size) = (read_u32(&self.data, p + 4) as usize, read_u32(&self.data, p + 8) as usize);
+                self.data.get(offset..offset.checked_add(size)?)
+            })
+    }
+
+    // The profile description, from a version 2 'desc' or version 4 'mluc'
+    // tag.  Of several languages, English is preferred.
+    pub fn description(&self) -> Option<String> {
+        let d = self.tag(b"desc")?;
+        let text = match d.get(..4)? {
+            b"desc" => {
+                let length = read_u32(d, 8) as usize;
+                let ascii = d.get(12..12usize.checked_add(length)?)?;
+                ascii.iter().map(|&b| b as char).collect::<String>()
+            },
+            b"mluc" => {
+                let (count, record_size) = (read_u32(d, 8) as usize, read_u32(d, 12) as usize);
+                if record_size < 12 {
+                    return None;
+                }
+                // Only the records that fit in the tag
+                let count = count.min(d.len().saturating_sub(16) / record_size);
+                let records: Vec<usize> = (0..count).map(|i| 16 + i * record_size).collect();
+                let record = records.iter().find(|&&p| &d[p..p + 2] == b"en").or_else(|| records.first())?;
+                let (length, offset) = (read_u32(d, record + 4) as usize, read_u32(d, record + 8) as usize);
+                let units: Vec<u16> = d.get(offset..offset.checked_add(length)?)?
+                    .chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+                String::from_utf16_lossy(&units)
+            },
+            _ => return None,
+        };
+        let text = text.trim_end_matches('\0');
+        if text.is_empty() { None } else { Some(text.to_string()) }
+    }
+}
+
+impl IFD {
+
+    // The raw bytes of the embedded ICC profile.
+    pub fn icc_profile(&self) -> Option<&[u8]> {
+        self.get(TIFFTag::InterColorProfileTag).map(|e| &e.data[..])
+    }
+
+    pub fn icc(&self) -> Result<Option<ICCProfile>> {
+        match self.icc_profile() {
+            Some(data) => Ok(Some(ICCProfile::parse(data)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Embed a profile, to be written when the file is next saved.
+    pub fn set_icc_profile(&mut self, profile: &[u8]) {
+        self.set(IFDEntry::bytes(TIFFTag::InterColorProfileTag, TagType::UndefinedTag, profile));
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+fn decode_class(sig: [u8; 4]) -> ICCProfileClass {
+    match &sig {
+        b"scnr" => ICCProfileClass::Input,
+        b"mntr" => ICCProfileClass::Display,
+        b"prtr" => ICCProfileClass::Output,
+        b"link" => ICCProfileClass::DeviceLink,
+        b"spac" => ICCProfileClass::ColorSpace,
+        b"abst" => ICCProfileClass::Abstract,
+        b"nmcl" => ICCProfileClass::NamedColor,
+        _ => ICCProfileClass::Other(sig),
+    }
+}
+
+fn decode_color_space(sig: [u8; 4]) -> ICCColorSpace {
+    match &sig {
+        b"XYZ " => ICCColorSpace::XYZ,
+        b"Lab " => ICCColorSpace::Lab,
+        b"Luv " => ICCColorSpace::Luv,
+        b"YCbr" => ICCColorSpace::YCbCr,
+        b"Yxy " => ICCColorSpace::Yxy,
+        b"RGB " => ICCColorSpace::RGB,
+        b"GRAY" => ICCColorSpace::Gray,
+        b"HSV " => ICCColorSpace::HSV,
+        b"HLS " => ICCColorSpace::HLS,
+        b"CMYK" => ICCColorSpace::CMYK,
+        b"CMY " => ICCColorSpace::CMY,
+        [n, b'C', b'L', b'R'] => match char::from(*n).to_digit(16) {
+            Some(channels) if channels >= 2 => ICCColorSpace::Colors(channels as u8),
+            _ => ICCColorSpace::Other(sig),
+        },
+        _ => ICCColorSpace::Other(sig),
+    }
+}
+
+fn signature(data: &[u8], pos: usize) -> [u8; 4] {
+    [data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    data.get(pos..pos + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])).unwrap_or(0)
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid ICC profile: {}", message))
+}
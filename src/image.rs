@@ -0,0 +1,407 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::convert::TryFrom;
+use std::io::{Result, Error};
+
+use {ExtraSamples, ImageOrientation, PhotometricInterpretation, SampleFormat};
+
+//----------------------------------------------------------------------------
+// Types
+
+// Decoded sample values, one element per sample, pixels interleaved.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageData {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+    I8(Vec<i8>),
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+    I64(Vec<i64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+// How samples are combined when an image is reduced in size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resampling {
+    Nearest,
+    Average,
+}
+
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub width:             u32,
+    pub height:            u32,
+    pub samples_per_pixel: u16,
+    pub bits_per_sample:   u16,
+    pub sample_format:     SampleFormat,
+    pub photometric:       PhotometricInterpretation,
+    pub extra_samples:     Vec<ExtraSamples>,
+    // Layout of `data`, and the Orientation field as read from the file
+    pub orientation:        ImageOrientation,
+    pub source_orientation: ImageOrientation,
+    // Embedded ICC profile, if any
+    pub icc_profile:       Option<Vec<u8>>,
+    pub data:              ImageData,
+}
+
+// Apply an expression to the sample vector of any ImageData variant.
+macro_rules! map_image_data {
+    ($data:expr, $v:ident => $e:expr) => {
+        match $data {
+            ImageData::U8($v) => ImageData::U8($e),
+            ImageData::U16($v) => ImageData::U16($e),
+            ImageData::U32($v) => ImageData::U32($e),
+            ImageData::U64($v) => ImageData::U64($e),
+            ImageData::I8($v) => ImageData::I8($e),
+            ImageData::I16($v) => ImageData::I16($e),
+            ImageData::I32($v) => ImageData::I32($e),
+            ImageData::I64($v) => ImageData::I64($e),
+            ImageData::F32($v) => ImageData::F32($e),
+            ImageData::F64($v) => ImageData::F64($e),
+        }
+    }
+}
+
+//----------------------------------------------------------------------------
+
+impl ImageData {
+
+    pub fn len(&self) -> usize {
+        match *self {
+            ImageData::U8(ref v) => v.len(),
+            ImageData::U16(ref v) => v.len(),
+            ImageData::U32(ref v) => v.len(),
+            ImageData::U64(ref v) => v.len(),
+            ImageData::I8(ref v) => v.len(),
+            ImageData::I16(ref v) => v.len(),
+            ImageData::I32(ref v) => v.len(),
+            ImageData::I64(ref v) => v.len(),
+            ImageData::F32(ref v) => v.len(),
+            ImageData::F64(ref v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // No samples, of the same type as these.
+    pub fn empty(&self) -> ImageData {
+        map_image_data!(self, _v => Vec::new())
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        match *self {
+            ImageData::U8(_) | ImageData::I8(_) => 8,
+            ImageData::U16(_) | ImageData::I16(_) => 16,
+            ImageData::U32(_) | ImageData::I32(_) | ImageData::F32(_) => 32,
+            ImageData::U64(_) | ImageData::I64(_) | ImageData::F64(_) => 64,
+        }
+    }
+
+    pub fn sample_format(&self) -> SampleFormat {
+        match *self {
+            ImageData::U8(_) | ImageData::U16(_) | ImageData::U32(_) | ImageData::U64(_) =>
+                SampleFormat::UnsignedInteger,
+            ImageData::I8(_) | ImageData::I16(_) | ImageData::I32(_) | ImageData::I64(_) =>
+                SampleFormat::TwosComplementSignedInteger,
+            ImageData::F32(_) | ImageData::F64(_) =>
+                SampleFormat::IEEEFloatingPoint,
+        }
+    }
+}
+
+impl Image {
+
+    // Create a top-left oriented image without extra samples.  The sample
+    // size and format follow from the type of `data`.
+    pub fn new(width: u32, height: u32, samples_per_pixel: u16,
+               photometric: PhotometricInterpretation, data: ImageData) -> Image {
+        Image {
+            width,
+            height,
+            samples_per_pixel,
+            bits_per_sample: data.bits_per_sample(),
+            sample_format: data.sample_format(),
+            photometric,
+            extra_samples: Vec::new(),
+            orientation: ImageOrientation::TopLeft,
+            source_orientation: ImageOrientation::TopLeft,
+            icc_profile: None,
+            data,
+        }
+    }
+
+    // Number of colour samples per pixel, excluding extra samples.
+    pub fn colour_samples(&self) -> usize {
+        self.samples_per_pixel as usize - self.extra_samples.len()
+    }
+
+    // Index within each pixel of the first alpha sample, if any.
+    pub fn alpha_channel(&self) -> Option<usize> {
+        self.extra_samples.iter()
+            .position(|&e| e == ExtraSamples::AssociatedAlpha || e == ExtraSamples::UnassociatedAlpha)
+            .map(|i| self.colour_samples() + i)
+    }
+
+    // Convert straight (unassociated) alpha to premultiplied (associated).
+    pub fn premultiply_alpha(&mut self) -> Result<()> {
+        self.convert_alpha(ExtraSamples::UnassociatedAlpha, ExtraSamples::AssociatedAlpha)
+    }
+
+    // Convert premultiplied (associated) alpha to straight (unassociated).
+    // Fully transparent pixels have their colour samples set to zero.
+    pub fn unpremultiply_alpha(&mut self) -> Result<()> {
+        self.convert_alpha(ExtraSamples::AssociatedAlpha, ExtraSamples::UnassociatedAlpha)
+    }
+
+    fn convert_alpha(&mut self, from: ExtraSamples, to: ExtraSamples) -> Result<()> {
+
+        let alpha = match self.alpha_channel() {
+            Some(a) => a,
+            None => return Err(Error::other("Image has no alpha channel")),
+        };
+        let label = alpha - self.colour_samples();
+        if self.extra_samples[label] == to {
+            return Ok(());
+        }
+        if self.extra_samples[label] != from {
+            return Err(Error::other("Unexpected alpha type"));
+        }
+
+        let premultiply = to == ExtraSamples::AssociatedAlpha;
+        let spp = self.samples_per_pixel as usize;
+        let colours = self.colour_samples();
+
+        match self.data {
+            ImageData::U8(ref mut v) =>
+                convert_alpha_int(v, spp, colours, alpha, u8::MAX as u64, premultiply),
+            ImageData::U16(ref mut v) =>
+                convert_alpha_int(v, spp, colours, alpha, u16::MAX as u64, premultiply),
+            ImageData::U32(ref mut v) =>
+                convert_alpha_int(v, spp, colours, alpha, u32::MAX as u64, premultiply),
+            ImageData::F32(ref mut v) => {
+                for px in v.chunks_mut(spp) {
+                    let a = px[alpha];
+                    for c in &mut px[..colours] {
+                        *c = if premultiply { *c * a } else if a == 0.0 { 0.0 } else { *c / a };
+                    }
+                }
+            },
+            ImageData::F64(ref mut v) => {
+                for px in v.chunks_mut(spp) {
+                    let a = px[alpha];
+                    for c in &mut px[..colours] {
+                        *c = if premultiply { *c * a } else if a == 0.0 { 0.0 } else { *c / a };
+                    }
+                }
+            },
+            _ => return Err(Error::other("Alpha conversion requires unsigned or floating point samples")),
+        }
+
+        self.extra_samples[label] = to;
+        Ok(())
+    }
+
+    // The region of the given size at (x, y), cropped to the image.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Image {
+
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+
+        let spp = self.samples_per_pixel as usize;
+        let row = self.width as usize * spp;
+        let (start, end) = (x as usize * spp, (x + width) as usize * spp);
+        let rows = y as usize..(y + height) as usize;
+
+        Image {
+            width,
+            height,
+            extra_samples: self.extra_samples.clone(),
+            icc_profile: self.icc_profile.clone(),
+            data: map_image_data!(&self.data, v => {
+                rows.clone().flat_map(|r| v[r * row + start..r * row + end].iter().cloned()).collect()
+            }),
+            ..*self
+        }
+    }
+
+    // Reduce the image by an integer factor in each direction.  Pixels at
+    // the right and bottom edges cover what remains of the image.
+    pub fn downsample(&self, factor: u32, resampling: Resampling) -> Image {
+
+        let (w, h) = (self.width as usize, self.height as usize);
+        let spp = self.samples_per_pixel as usize;
+        let factor = factor.max(1) as usize;
+        let average = resampling == Resampling::Average;
+
+        let data = match self.data {
+            ImageData::U8(ref v) => ImageData::U8(reduce(v, w, h, spp, factor, average, mean_unsigned)),
+            ImageData::U16(ref v) => ImageData::U16(reduce(v, w, h, spp, factor, average, mean_unsigned)),
+            ImageData::U32(ref v) => ImageData::U32(reduce(v, w, h, spp, factor, average, mean_unsigned)),
+            ImageData::U64(ref v) => ImageData::U64(reduce(v, w, h, spp, factor, average, mean_unsigned)),
+            ImageData::I8(ref v) => ImageData::I8(reduce(v, w, h, spp, factor, average, mean_signed)),
+            ImageData::I16(ref v) => ImageData::I16(reduce(v, w, h, spp, factor, average, mean_signed)),
+            ImageData::I32(ref v) => ImageData::I32(reduce(v, w, h, spp, factor, average, mean_signed)),
+            ImageData::I64(ref v) => ImageData::I64(reduce(v, w, h, spp, factor, average, mean_signed)),
+            ImageData::F32(ref v) =>
+                ImageData::F32(reduce(v, w, h, spp, factor, average, |s| mean_float(s, |&x| x as f64) as f32)),
+            ImageData::F64(ref v) =>
+                ImageData::F64(reduce(v, w, h, spp, factor, average, |s| mean_float(s, |&x| x))),
+        };
+
+        Image {
+            width: w.div_ceil(factor) as u32,
+            height: h.div_ceil(factor) as u32,
+            extra_samples: self.extra_samples.clone(),
+            icc_profile: self.icc_profile.clone(),
+            data,
+            ..*self
+        }
+    }
+
+    // Rearrange pixels so that row 0 is the top and column 0 the left hand
+    // side.  For orientations 5 to 8 the width and height are swapped.
+    pub fn normalize_orientation(&mut self) {
+
+        let orientation = self.orientation;
+        if orientation == ImageOrientation::TopLeft {
+            return;
+        }
+
+        let (w, h) = (self.width as usize, self.height as usize);
+        let spp = self.samples_per_pixel as usize;
+
+        self.data = map_image_data!(&self.data, v => reorient(v, w, h, spp, orientation));
+
+        if transposed(orientation) {
+            self.width = h as u32;
+            self.height = w as u32;
+        }
+        self.orientation = ImageOrientation::TopLeft;
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+fn transposed(orientation: ImageOrientation) -> bool {
+    matches!(orientation,
+             ImageOrientation::LeftTop | ImageOrientation::RightTop |
+             ImageOrientation::RightBottom | ImageOrientation::LeftBottom)
+}
+
+// Copy pixels of a w x h image stored with the given orientation into
+// top-left order.
+fn reorient<T: Copy>(data: &[T], w: usize, h: usize, spp: usize,
+                     orientation: ImageOrientation) -> Vec<T> {
+
+    let (out_w, out_h) = if transposed(orientation) { (h, w) } else { (w, h) };
+    let mut result = Vec::with_capacity(data.len());
+
+    for y in 0..out_h {
+        for x in 0..out_w {
+            // Stored (row, column) of the pixel displayed at (x, y)
+            let (row, col) = match orientation {
+                ImageOrientation::TopLeft     => (y, x),
+                ImageOrientation::TopRight    => (y, w - 1 - x),
+                ImageOrientation::BottomRight => (h - 1 - y, w - 1 - x),
+                ImageOrientation::BottomLeft  => (h - 1 - y, x),
+                ImageOrientation::LeftTop     => (x, y),
+                ImageOrientation::RightTop    => (h - 1 - x, y),
+                ImageOrientation::RightBottom => (h - 1 - x, w - 1 - y),
+                ImageOrientation::LeftBottom  => (x, w - 1 - y),
+            };
+            let i = (row * w + col) * spp;
+            result.extend_from_slice(&data[i..i + spp]);
+        }
+    }
+    result
+}
+
+// Reduce a w x h image by `factor`, taking either the mean of each block of
+// pixels or the pixel nearest its centre.
+fn reduce<T: Copy>(data: &[T], w: usize, h: usize, spp: usize, factor: usize, average: bool,
+                   mean: fn(&[T]) -> T) -> Vec<T> {
+
+    let (out_w, out_h) = (w.div_ceil(factor), h.div_ceil(factor));
+    let mut result = Vec::with_capacity(out_w * out_h * spp);
+    let mut block = Vec::with_capacity(factor * factor);
+
+    for oy in 0..out_h {
+        let rows = oy * factor..((oy + 1) * factor).min(h);
+        for ox in 0..out_w {
+            let columns = ox * factor..((ox + 1) * factor).min(w);
+            for sample in 0..spp {
+                if average {
+                    block.clear();
+                    for y in rows.clone() {
+                        for x in columns.clone() {
+                            block.push(data[(y * w + x) * spp + sample]);
+                        }
+                    }
+                    result.push(mean(&block));
+                } else {
+                    let y = (rows.start + rows.end) / 2;
+                    let x = (columns.start + columns.end) / 2;
+                    result.push(data[(y * w + x) * spp + sample]);
+                }
+            }
+        }
+    }
+    result
+}
+
+fn mean_unsigned<T: Copy + Into<u64> + TryFrom<u64>>(values: &[T]) -> T {
+    let n = values.len() as u128;
+    let sum: u128 = values.iter().map(|&v| v.into() as u128).sum();
+    T::try_from(((sum + n / 2) / n) as u64).unwrap_or(values[0])
+}
+
+fn mean_signed<T: Copy + Into<i64> + TryFrom<i64>>(values: &[T]) -> T {
+    let n = values.len() as i128;
+    let sum: i128 = values.iter().map(|&v| v.into() as i128).sum();
+    // Round halves away from zero
+    let mean = if sum >= 0 { (sum + n / 2) / n } else { (sum - n / 2) / n };
+    T::try_from(mean as i64).unwrap_or(values[0])
+}
+
+fn mean_float<T>(values: &[T], to_f64: fn(&T) -> f64) -> f64 {
+    values.iter().map(to_f64).sum::<f64>() / values.len() as f64
+}
+
+fn convert_alpha_int<T>(data: &mut [T], spp: usize, colours: usize, alpha: usize,
+                        max: u64, premultiply: bool)
+    where T: Copy + Into<u64> + TryFrom<u64>
+{
+    for px in data.chunks_mut(spp) {
+        let a: u64 = px[alpha].into();
+        for c in &mut px[..colours] {
+            let v: u64 = (*c).into();
+            let result = if premultiply {
+                (v * a + max / 2) / max
+            } else {
+                (v * max + a / 2).checked_div(a).unwrap_or(0).min(max)
+            };
+            if let Ok(x) = T::try_from(result) {
+                *c = x;
+            }
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and the IPTC Information Interchange Model, version 4.2.
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, ErrorKind};
+
+use {IFD, TIFFTag, TagType};
+
+//----------------------------------------------------------------------------
+// Constants
+
+// Every dataset starts with this tag marker
+const TAG_MARKER: u8 = 0x1c;
+
+//----------------------------------------------------------------------------
+// Types
+
+// Datasets of the envelope (1) and application (2) records, numbered
+// record << 8 | dataset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IPTCTag {
+
+    // Envelope record
+
+    EnvelopeRecordVersionTag      = 0x0100,
+    DestinationTag                = 0x0105,
+    FileFormatTag                 = 0x0114,
+    ServiceIdentifierTag          = 0x011e,
+    EnvelopeNumberTag             = 0x0128,
+    ProductIdTag                  = 0x0132,
+    DateSentTag                   = 0x0146,
+    TimeSentTag                   = 0x0150,
+    CodedCharacterSetTag          = 0x015a,
+
+    // Application record
+
+    RecordVersionTag              = 0x0200,
+    ObjectNameTag                 = 0x0205,
+    EditStatusTag                 = 0x0207,
+    UrgencyTag                    = 0x020a,
+    SubjectReferenceTag           = 0x020c,
+    CategoryTag                   = 0x020f,
+    SupplementalCategoryTag       = 0x0214,
+    FixtureIdentifierTag          = 0x0216,
+    KeywordsTag                   = 0x0219,
+    ContentLocationCodeTag        = 0x021a,
+    ContentLocationNameTag        = 0x021b,
+    ReleaseDateTag                = 0x021e,
+    ReleaseTimeTag                = 0x0223,
+    SpecialInstructionsTag        = 0x0228,
+    DateCreatedTag                = 0x0237,
+    TimeCreatedTag                = 0x023c,
+    OriginatingProgramTag         = 0x0241,
+    ProgramVersionTag             = 0x0246,
+    ByLineTag                     = 0x0250,
+    ByLineTitleTag                = 0x0255,
+    CityTag                       = 0x025a,
+    SubLocationTag                = 0x025c,
+    ProvinceStateTag              = 0x025f,
+    CountryCodeTag                = 0x0264,
+    CountryNameTag                = 0x0265,
+    OriginalTransmissionReferenceTag = 0x0267,
+    HeadlineTag                   = 0x0269,
+    CreditTag                     = 0x026e,
+    SourceTag                     = 0x0273,
+    CopyrightNoticeTag            = 0x0274,
+    ContactTag                    = 0x0276,
+    CaptionTag                    = 0x0278,
+    WriterTag                     = 0x027a,
+}
+
+// The character set declared by dataset 1:90.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IPTCCharset {
+    // No 1:90 dataset
+    Unspecified,
+    UTF8,
+    Latin1,
+    // Any other ISO 2022 escape sequence
+    Other(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IPTCDataSet {
+    pub record:  u8,
+    pub dataset: u8,
+    pub data:    Vec<u8>,
+}
+
+// The datasets of an IIM stream, in the order they appear.  Repeatable
+// datasets, such as keywords, appear once per value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IPTC {
+    pub datasets: Vec<IPTCDataSet>,
+    pub charset:  IPTCCharset,
+}
+
+//----------------------------------------------------------------------------
+
+impl IPTC {
+
+    // Parse an IIM stream.  Zero padding after the last dataset is ignored.
+    pub fn parse(data: &[u8]) -> Result<IPTC> {
+
+        let mut datasets = Vec::new();
+        let mut pos = 0;
+
+        while pos < data.len() {
+            if data[pos] != TAG_MARKER {
+                if data[pos..].iter().all(|&b| b == 0) {
+                    break;
+                }
+                return Err(invalid(&format!("Expected a tag marker at offset {}", pos)));
+            }
+            let header = data.get(pos + 1..pos + 5).ok_or_else(|| invalid("Truncated dataset header"))?;
+            let (record, dataset) = (header[0], header[1]);
+            let mut length = u16::from_be_bytes([header[2], header[3]]) as usize;
+            pos += 5;
+
+            // Extended datasets give the size of their length field instead
+            if length & 0x8000 != 0 {
+                let size = length & 0x7fff;
+                let field = data.get(pos..pos + size).filter(|_| size <= 8)
+                    .ok_or_else(|| invalid("Bad extended dataset length"))?;
+                length = field.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+                pos += size;
+            }
+
+            let value = data.get(pos..pos.saturating_add(length))
+                .ok_or_else(|| invalid(&format!("Dataset {}:{} overruns the stream", record, dataset)))?;
+            datasets.push(IPTCDataSet { record, dataset, data: value.to_vec() });
+            pos += length;
+        }
+
+        let charset = match datasets.iter().find(|d| d.record == 1 && d.dataset == 90) {
+            None => IPTCCharset::Unspecified,
+            Some(d) => match &d.data[..] {
+                b"\x1b%G" => IPTCCharset::UTF8,
+                b"\x1b.A" | b"\x1b-A" => IPTCCharset::Latin1,
+                other => IPTCCharset::Other(other.to_vec()),
+            },
+        };
+
+        Ok(IPTC { datasets, charset })
+    }
+
+    pub fn get(&self, tag: IPTCTag) -> Option<&[u8]> {
+        self.get_all(tag).into_iter().next()
+    }
+
+    // Every value of a repeatable dataset.
+    pub fn get_all(&self, tag: IPTCTag) -> Vec<&[u8]> {
+        let (record, dataset) = ((tag as u16 >> 8) as u8, tag as u8);
+        self.datasets.iter().filter(|d| d.record == record && d.dataset == dataset).map(|d| &d.data[..]).collect()
+    }
+
+    // Decode text in the declared character set.  Without one, text that is
+    // valid UTF-8 is taken as such, and anything else as Latin-1.
+    pub fn decode(&self, data: &[u8]) -> String {
+        let latin1 = || data.iter().map(|&b| b as char).collect();
+        match self.charset {
+            IPTCCharset::Latin1 => latin1(),
+            IPTCCharset::Unspecified => String::from_utf8(data.to_vec()).unwrap_or_else(|_| latin1()),
+            _ => String::from_utf8_lossy(data).into_owned(),
+        }
+    }
+
+    pub fn get_text(&self, tag: IPTCTag) -> Option<String> {
+        self.get(tag).map(|d| self.decode(d).trim_end_matches(['\0', ' ']).to_string())
+    }
+
+    pub fn get_texts(&self, tag: IPTCTag) -> Vec<String> {
+        self.get_all(tag).into_iter().map(|d| self.decode(d).trim_end_matches(['\0', ' ']).to_string()).collect()
+    }
+
+    // A two byte binary number, as used by the record versions.
+    pub fn record_version(&self) -> Option<u16> {
+        self.get(IPTCTag::RecordVersionTag).filter(|d| d.len() == 2).map(|d| u16::from_be_bytes([d[0], d[1]]))
+    }
+
+    pub fn object_name(&self) -> Option<String> {
+        self.get_text(IPTCTag::ObjectNameTag)
+    }
+
+    // 1 most urgent to 8 least, with 0 reserved and 9 user-defined.
+    pub fn urgency(&self) -> Option<u8> {
+        self.get_text(IPTCTag::UrgencyTag).and_then(|s| s.parse().ok())
+    }
+
+    pub fn category(&self) -> Option<String> {
+        self.get_text(IPTCTag::CategoryTag)
+    }
+
+    pub fn supplemental_categories(&self) -> Vec<String> {
+        self.get_texts(IPTCTag::SupplementalCategoryTag)
+    }
+
+    pub fn keywords(&self) -> Vec<String> {
+        self.get_texts(IPTCTag::KeywordsTag)
+    }
+
+    pub fn special_instructions(&self) -> Option<String> {
+        self.get_text(IPTCTag::SpecialInstructionsTag)
+    }
+
+    // CCYYMMDD as (year, month, day).
+    pub fn date_created(&self) -> Option<(u16, u8, u8)> {
+        let s = self.get_text(IPTCTag::DateCreatedTag)?;
+        if s.len() != 8 || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some((s[0..4].parse().ok()?, s[4..6].parse().ok()?, s[6..8].parse().ok()?))
+    }
+
+    // HHMMSS±HHMM, as written.
+    pub fn time_created(&self) -> Option<String> {
+        self.get_text(IPTCTag::TimeCreatedTag)
+    }
+
+    pub fn by_lines(&self) -> Vec<String> {
+        self.get_texts(IPTCTag::ByLineTag)
+    }
+
+    pub fn by_line_titles(&self) -> Vec<String> {
+        self.get_texts(IPTCTag::ByLineTitleTag)
+    }
+
+    pub fn city(&self) -> Option<String> {
+        self.get_text(IPTCTag::CityTag)
+    }
+
+    pub fn sub_location(&self) -> Option<String> {
+        self.get_text(IPTCTag::SubLocationTag)
+    }
+
+    pub fn province_state(&self) -> Option<String> {
+        self.get_text(IPTCTag::ProvinceStateTag)
+    }
+
+    // Three letter ISO 3166 code.
+    pub fn country_code(&self) -> Option<String> {
+        self.get_text(IPTCTag::CountryCodeTag)
+    }
+
+    pub fn country_name(&self) -> Option<String> {
+        self.get_text(IPTCTag::CountryNameTag)
+    }
+
+    pub fn original_transmission_reference(&self) -> Option<String> {
+        self.get_text(IPTCTag::OriginalTransmissionReferenceTag)
+    }
+
+    pub fn headline(&self) -> Option<String> {
+        self.get_text(IPTCTag::HeadlineTag)
+    }
+
+    pub fn credit(&self) -> Option<String> {
+        self.get_text(IPTCTag::CreditTag)
+    }
+
+    pub fn source(&self) -> Option<String> {
+        self.get_text(IPTCTag::SourceTag)
+    }
+
+    pub fn copyright_notice(&self) -> Option<String> {
+        self.get_text(IPTCTag::CopyrightNoticeTag)
+    }
+
+    pub fn contacts(&self) -> Vec<String> {
+        self.get_texts(IPTCTag::ContactTag)
+    }
+
+    pub fn caption(&self) -> Option<String> {
+        self.get_text(IPTCTag::CaptionTag)
+    }
+
+    pub fn writers(&self) -> Vec<String> {
+        self.get_texts(IPTCTag::WriterTag)
+    }
+}
+
+impl IFD {
+
+    // The IPTC-IIM stream.  Many writers type the field LONG rather than
+    // UNDEFINED; the bytes are used as stored, or unswapped if they were
+    // written as byte-swapped LONGs.
+    pub fn iptc_data(&self) -> Option<Vec<u8>> {
+        let e = self.get(TIFFTag::IPTCTag)?;
+        let mut data = e.data.clone();
+        let swapped = e.typ == TagType::LongTag && data.len() >= 4 && data[0] != TAG_MARKER && data[3] == TAG_MARKER;
+        if swapped {
+            for c in data.chunks_exact_mut(4) {
+                c.reverse();
+            }
+        }
+        Some(data)
+    }
+
+    pub fn iptc(&self) -> Result<Option<IPTC>> {
+        match self.iptc_data() {
+            Some(data) => Ok(Some(IPTC::parse(&data)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+//----------------------------------------------------------------------------
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid IPTC: {}", message))
+}
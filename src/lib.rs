@@ -19,21 +19,56 @@
 // For binary file I/O
 extern crate byteorder;
 
+// For Deflate compression
+extern crate flate2;
+
 //----------------------------------------------------------------------------
 // Module imports
 
-use byteorder::{ReadBytesExt, WriteBytesExt, BigEndian, LittleEndian};
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
 
-use std::io::{Read, Seek};
-use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, Write};
+use std::collections::HashSet;
 
 //----------------------------------------------------------------------------
 // Reexports
 
-pub use reader::TIFFReader;
+pub use reader::{TIFFReader, DecodeOptions};
+pub use image::{Image, ImageData, Resampling};
+pub use resolution::Resolution;
+pub use editor::TIFFEditor;
+pub use copy::copy_tiff;
+pub use exif::{EXIF, EXIFTag};
+pub use gps::{GPS, GPSTag};
+pub use xmp::{XMP, XMPName, XMPValue};
+pub use iptc::{IPTC, IPTCCharset, IPTCDataSet, IPTCTag};
+pub use photoshop::{ImageResource, ImageResourceID, PhotoshopResources, PhotoshopThumbnail, PhotoshopVersionInfo,
+                    ResolutionInfo};
+pub use icc::{ICCColorSpace, ICCProfile, ICCProfileClass, RenderingIntent};
+pub use geotiff::{AffineTransform, GeoKey, GeoKeyDirectory, GeoKeyValue, GeoTIFF, ModelType, RasterType, Tiepoint};
+pub use gdal::{GDALMetadata, GDALMetadataItem, NoData};
+pub use dng::{CFAPattern, DNGMetadata, RawImage};
+pub use demosaic::Demosaic;
+pub use writer::{TIFFWriter, BigTIFFMode, PyramidOptions, StripWriter, TileWriter, PageWriter};
 
 pub mod reader;
-//mod writer;
+pub mod writer;
+pub mod editor;
+pub mod copy;
+pub mod exif;
+pub mod gps;
+pub mod xmp;
+mod xml;
+pub mod iptc;
+pub mod photoshop;
+pub mod icc;
+pub mod geotiff;
+pub mod gdal;
+pub mod dng;
+pub mod demosaic;
+pub mod compression;
+pub mod image;
+pub mod resolution;
 
 //----------------------------------------------------------------------------
 // Types
@@ -49,26 +84,37 @@ pub type SLONG     = i32;
 pub type SRATIONAL = (i32, i32);
 pub type FLOAT     = f32;
 pub type DOUBLE    = f64;
+pub type LONG8     = u64;
+pub type SLONG8    = i64;
 
 //----------------------------------------------------------------------------
 // Enums
 
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TIFFByteOrder {
     LittleEndian = 0x4949,
     BigEndian    = 0x4d4d,
 }
 
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum HeaderMagic {
-    LittleEndian = 0x002a,
-    BigEndian    = 0x2a00,
+    LittleEndian        = 0x002a,
+    BigEndian           = 0x2a00,
+    BigTIFFLittleEndian = 0x002b,
+    BigTIFFBigEndian    = 0x2b00,
+}
+
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TIFFVersion {
+    Classic = 42,
+    BigTIFF = 43,
 }
 
 #[repr(u16)]
-#[derive(Debug,PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TagType {
     ByteTag           = 1,
     ASCIITag          = 2,
@@ -82,6 +128,8 @@ pub enum TagType {
     SignedRationalTag = 10,
     FloatTag          = 11,
     DoubleTag         = 12,
+    // Supplement 1: a LONG holding the offset of a child IFD
+    IFDTag            = 13,
     Long8             = 16,
     SLong8            = 17,
     IFD8              = 18,
@@ -90,7 +138,7 @@ pub enum TagType {
     ShortOrLongTag    = 0xfffe,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum TagValue {
     ByteValue(BYTE),
     ShortValue(SHORT),
@@ -103,28 +151,52 @@ pub enum TagValue {
     SignedRationalValue(SRATIONAL),
     FloatValue(FLOAT),
     DoubleValue(DOUBLE),
+    Long8Value(LONG8),
+    SignedLong8Value(SLONG8),
+    IFD8Value(LONG8),
 }
 
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PhotometricInterpretation {
-    WhiteIsZero = 0,
-    BlackIsZero = 1,
+    WhiteIsZero      = 0,
+    BlackIsZero      = 1,
+    RGB              = 2,
+    Palette          = 3,
+    TransparencyMask = 4,
+    CMYK             = 5,
+    YCbCr            = 6,
+    CIELab           = 8,
+    // TIFF/EP and DNG raw sensor data
+    CFA              = 32803,
+    LinearRaw        = 34892,
 }
 
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Compression {
-    None     = 1,
-    Huffman  = 2,
-    LZW      = 5,
-    OJPEG    = 6,
-    JPEG     = 7,
-    PackBits = 32773,
+    None       = 1,
+    Huffman    = 2,
+    LZW        = 5,
+    OJPEG      = 6,
+    JPEG       = 7,
+    Deflate    = 8,
+    PackBits   = 32773,
+    DeflateOld = 32946,
 }
 
+// Section 14: Differencing Predictor, and Adobe Technical Note 3
+
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Predictor {
+    None          = 1,
+    Horizontal    = 2,
+    FloatingPoint = 3,
+}
+
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ResolutionUnit {
     None       = 1,
     Inch       = 2,
@@ -132,7 +204,7 @@ pub enum ResolutionUnit {
 }
 
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SampleFormat {
     UnsignedInteger             = 1,
     TwosComplementSignedInteger = 2,
@@ -140,7 +212,7 @@ pub enum SampleFormat {
     Undefined                   = 4,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ImageType {
     Bilevel,
     Grayscale,
@@ -149,8 +221,18 @@ pub enum ImageType {
     YCbCr,
 }
 
+// Section 18: ExtraSamples
+
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExtraSamples {
+    Unspecified       = 0,
+    AssociatedAlpha   = 1,	// premultiplied
+    UnassociatedAlpha = 2,	// straight
+}
+
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ImageOrientation {
     TopLeft     = 1,	// row 0 top, col 0 lhs
     TopRight    = 2,	// row 0 top, col 0 rhs
@@ -169,25 +251,39 @@ pub enum ImageOrientation {
 pub struct TIFFHeader {
     pub byte_order: TIFFByteOrder,
     pub magic:      HeaderMagic,
-    pub ifd_offset: LONG,
+    pub version:    TIFFVersion,
+    pub ifd_offset: LONG8,
 }
 
+// Tags are kept as their raw numeric code, so entries for tags this library
+// does not know about (or from other namespaces, such as EXIF) survive
+// parsing.  The value is kept as the raw bytes in the file's byte order.
+
+#[derive(Clone, Debug)]
 pub struct IFDEntry {
-    tag:          TIFFTag,
-    typ:          TagType,
-    count:        LONG,
-    value_offset: LONG,
-    value:        Option<TagValue>,
+    pub tag:          SHORT,
+    pub typ:          TagType,
+    pub count:        LONG8,
+    pub value_offset: LONG8,
+    pub byte_order:   TIFFByteOrder,
+    pub data:         Vec<BYTE>,
 }
 
+#[derive(Clone, Debug)]
 pub struct IFD {
-    count:   u16,
-    entries: Vec<IFDEntry>,
+    pub offset:      LONG8,
+    pub count:       LONG8,
+    pub entries:     Vec<IFDEntry>,
+    pub next_offset: LONG8,
+    // The IFDs listed in the SubIFDs field, each followed by any chained
+    // after it
+    pub sub_ifds:    Vec<IFD>,
 }
 
+#[derive(Debug)]
 pub struct TIFF {
-    header: TIFFHeader,
-    ifd: HashMap<TIFFTag, TagValue>,
+    pub header: TIFFHeader,
+    pub ifds:   Vec<IFD>,
 }
 
 //----------------------------------------------------------------------------
@@ -238,6 +334,21 @@ pub enum TIFFTag {
     XResolutionTag               = 0x011a,
     YResolutionTag               = 0x011b,
 
+    // Section 12: Document Storage and Retrieval
+
+    DocumentNameTag              = 0x010d,
+    PageNameTag                  = 0x011d,
+    PageNumberTag                = 0x0129,
+    XPositionTag                 = 0x011e,
+    YPositionTag                 = 0x011f,
+
+    // Section 15: Tiled Images
+
+    TileWidthTag                 = 0x0142,
+    TileLengthTag                = 0x0143,
+    TileOffsetsTag               = 0x0144,
+    TileByteCountsTag            = 0x0145,
+
     // Section 20: Colorimetry
 
     WhitePointTag                = 0x013e,
@@ -252,11 +363,17 @@ pub enum TIFFTag {
     YCbCrSubsampling             = 0x0212,
     YCbCrPositioning             = 0x0213,
 
+    // Section 22: JPEG Compression
+
+    JPEGInterchangeFormatTag     = 0x0201,
+    JPEGInterchangeFormatLengthTag = 0x0202,
+
     // TIFF/EP Tags
 
     SubIFDsTag                   = 0x014a,
     JPEGTablesTag                = 0x015b,
     CFARepeatPatternDimTag       = 0x828d,
+    CFAPatternTag                = 0x828e,
     BatteryLevelTag              = 0x828f,
     IPTCTag                      = 0x83BB,
     InterColorProfileTag         = 0x8773,
@@ -276,38 +393,85 @@ pub enum TIFFTag {
     // Private Tags
     PhotoshopTag                 = 0x8649,
     EXIFTag                      = 0x8769,
+    GPSTag                       = 0x8825,
+
+    // GeoTIFF 1.1 (OGC 19-008r4)
+    ModelPixelScaleTag           = 0x830e,
+    ModelTiepointTag             = 0x8482,
+    ModelTransformationTag       = 0x85d8,
+    GeoKeyDirectoryTag           = 0x87af,
+    GeoDoubleParamsTag           = 0x87b0,
+    GeoAsciiParamsTag            = 0x87b1,
+
+    // GDAL
+    GDALMetadataTag              = 0xa480,
+    GDALNoDataTag                = 0xa481,
+
+    // DNG 1.4
+    DNGVersionTag                = 0xc612,
+    DNGBackwardVersionTag        = 0xc613,
+    UniqueCameraModelTag         = 0xc614,
+    LocalizedCameraModelTag      = 0xc615,
+    CFAPlaneColorTag             = 0xc616,
+    CFALayoutTag                 = 0xc617,
+    LinearizationTableTag        = 0xc618,
+    BlackLevelRepeatDimTag       = 0xc619,
+    BlackLevelTag                = 0xc61a,
+    BlackLevelDeltaHTag          = 0xc61b,
+    BlackLevelDeltaVTag          = 0xc61c,
+    WhiteLevelTag                = 0xc61d,
+    DefaultScaleTag              = 0xc61e,
+    DefaultCropOriginTag         = 0xc61f,
+    DefaultCropSizeTag           = 0xc620,
+    ColorMatrix1Tag              = 0xc621,
+    ColorMatrix2Tag              = 0xc622,
+    CameraCalibration1Tag        = 0xc623,
+    CameraCalibration2Tag        = 0xc624,
+    ReductionMatrix1Tag          = 0xc625,
+    ReductionMatrix2Tag          = 0xc626,
+    AnalogBalanceTag             = 0xc627,
+    AsShotNeutralTag             = 0xc628,
+    AsShotWhiteXYTag             = 0xc629,
+    BaselineExposureTag          = 0xc62a,
+    CameraSerialNumberTag        = 0xc62f,
+    CalibrationIlluminant1Tag    = 0xc65a,
+    CalibrationIlluminant2Tag    = 0xc65b,
+    ActiveAreaTag                = 0xc68d,
+    ForwardMatrix1Tag            = 0xc714,
+    ForwardMatrix2Tag            = 0xc715,
+}
+
+// The set of tags an IFD's fields are drawn from.  Private IFDs, such as the
+// EXIF IFD, number their tags independently of the baseline tags.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TagSpace {
+    TIFF,
+    EXIF,
+    GPS,
+    Interoperability,
 }
 
 //----------------------------------------------------------------------------
-// Default Values
+// NewSubfileType flags (p36)
 
-static PHOTOMETRIC_INTERPRETATION_SHORT_DEFAULT: SHORT = 1;
-static PHOTOMETRIC_INTERPRETATION_LONG_DEFAULT: LONG = 1;
+pub const SUBFILE_REDUCED_RESOLUTION: LONG = 1;
+pub const SUBFILE_PAGE: LONG = 2;
+pub const SUBFILE_TRANSPARENCY_MASK: LONG = 4;
 
 //----------------------------------------------------------------------------
+// Default Values
 
-// Section 6: Required Fields for RGB Images
-
-fn validate_rgb_image() {
-    // ImageWidth
-    // ImageLength
-    // BitsPerSample
-    // Compression
-    // PhotometricInterpretation
-    // StripOffsets
-    // SamplesPerPixel
-    // RowsPerStrip
-    // StripByteCounts
-    // XResolution
-    // YResolution
-    // ResolutionUnit
-}
+static PHOTOMETRIC_INTERPRETATION_SHORT_DEFAULT: SHORT = 1;
+static PHOTOMETRIC_INTERPRETATION_LONG_DEFAULT: LONG = 1;
 
 //----------------------------------------------------------------------------
 
 pub trait SeekableReader: Seek + Read {}
 impl<T: Seek + Read> SeekableReader for T {}
 
+pub trait SeekableWriter: Seek + Write {}
+impl<T: Seek + Write> SeekableWriter for T {}
+
 pub fn decode_tag(value: u16) -> Option<TIFFTag> {
     match value {
         0x013b => Some(TIFFTag::ArtistTag),
@@ -349,9 +513,24 @@ pub fn decode_tag(value: u16) -> Option<TIFFTag> {
         0x011a => Some(TIFFTag::XResolutionTag),
         0x011b => Some(TIFFTag::YResolutionTag),
 
+        0x010d => Some(TIFFTag::DocumentNameTag),
+        0x011d => Some(TIFFTag::PageNameTag),
+        0x0129 => Some(TIFFTag::PageNumberTag),
+        0x011e => Some(TIFFTag::XPositionTag),
+        0x011f => Some(TIFFTag::YPositionTag),
+
+        0x0142 => Some(TIFFTag::TileWidthTag),
+        0x0143 => Some(TIFFTag::TileLengthTag),
+        0x0144 => Some(TIFFTag::TileOffsetsTag),
+        0x0145 => Some(TIFFTag::TileByteCountsTag),
+
+        0x0201 => Some(TIFFTag::JPEGInterchangeFormatTag),
+        0x0202 => Some(TIFFTag::JPEGInterchangeFormatLengthTag),
+
         0x014a => Some(TIFFTag::SubIFDsTag),
         0x015b => Some(TIFFTag::JPEGTablesTag),
         0x828d => Some(TIFFTag::CFARepeatPatternDimTag),
+        0x828e => Some(TIFFTag::CFAPatternTag),
         0x828f => Some(TIFFTag::BatteryLevelTag),
         0x83BB => Some(TIFFTag::IPTCTag),
         0x8773 => Some(TIFFTag::InterColorProfileTag),
@@ -367,6 +546,49 @@ pub fn decode_tag(value: u16) -> Option<TIFFTag> {
         0x02bc => Some(TIFFTag::XMPTag),
         0x8649 => Some(TIFFTag::PhotoshopTag),
         0x8769 => Some(TIFFTag::EXIFTag),
+        0x8825 => Some(TIFFTag::GPSTag),
+
+        0x830e => Some(TIFFTag::ModelPixelScaleTag),
+        0x8482 => Some(TIFFTag::ModelTiepointTag),
+        0x85d8 => Some(TIFFTag::ModelTransformationTag),
+        0x87af => Some(TIFFTag::GeoKeyDirectoryTag),
+        0x87b0 => Some(TIFFTag::GeoDoubleParamsTag),
+        0x87b1 => Some(TIFFTag::GeoAsciiParamsTag),
+
+        0xa480 => Some(TIFFTag::GDALMetadataTag),
+        0xa481 => Some(TIFFTag::GDALNoDataTag),
+
+        0xc612 => Some(TIFFTag::DNGVersionTag),
+        0xc613 => Some(TIFFTag::DNGBackwardVersionTag),
+        0xc614 => Some(TIFFTag::UniqueCameraModelTag),
+        0xc615 => Some(TIFFTag::LocalizedCameraModelTag),
+        0xc616 => Some(TIFFTag::CFAPlaneColorTag),
+        0xc617 => Some(TIFFTag::CFALayoutTag),
+        0xc618 => Some(TIFFTag::LinearizationTableTag),
+        0xc619 => Some(TIFFTag::BlackLevelRepeatDimTag),
+        0xc61a => Some(TIFFTag::BlackLevelTag),
+        0xc61b => Some(TIFFTag::BlackLevelDeltaHTag),
+        0xc61c => Some(TIFFTag::BlackLevelDeltaVTag),
+        0xc61d => Some(TIFFTag::WhiteLevelTag),
+        0xc61e => Some(TIFFTag::DefaultScaleTag),
+        0xc61f => Some(TIFFTag::DefaultCropOriginTag),
+        0xc620 => Some(TIFFTag::DefaultCropSizeTag),
+        0xc621 => Some(TIFFTag::ColorMatrix1Tag),
+        0xc622 => Some(TIFFTag::ColorMatrix2Tag),
+        0xc623 => Some(TIFFTag::CameraCalibration1Tag),
+        0xc624 => Some(TIFFTag::CameraCalibration2Tag),
+        0xc625 => Some(TIFFTag::ReductionMatrix1Tag),
+        0xc626 => Some(TIFFTag::ReductionMatrix2Tag),
+        0xc627 => Some(TIFFTag::AnalogBalanceTag),
+        0xc628 => Some(TIFFTag::AsShotNeutralTag),
+        0xc629 => Some(TIFFTag::AsShotWhiteXYTag),
+        0xc62a => Some(TIFFTag::BaselineExposureTag),
+        0xc62f => Some(TIFFTag::CameraSerialNumberTag),
+        0xc65a => Some(TIFFTag::CalibrationIlluminant1Tag),
+        0xc65b => Some(TIFFTag::CalibrationIlluminant2Tag),
+        0xc68d => Some(TIFFTag::ActiveAreaTag),
+        0xc714 => Some(TIFFTag::ForwardMatrix1Tag),
+        0xc715 => Some(TIFFTag::ForwardMatrix2Tag),
         _ => None,
     }
 }
@@ -386,6 +608,115 @@ pub fn decode_tag_type(typ: u16) -> Option<TagType> {
         10 => Some(TagType::SignedRationalTag),
         11 => Some(TagType::FloatTag),
         12 => Some(TagType::DoubleTag),
+        13 => Some(TagType::IFDTag),
+        16 => Some(TagType::Long8),
+        17 => Some(TagType::SLong8),
+        18 => Some(TagType::IFD8),
+        _ => None,
+    }
+}
+
+// Size in bytes of a single value of the given type.
+pub fn size_of_tag_type(typ: TagType) -> usize {
+    match typ {
+        TagType::ByteTag           => 1,
+        TagType::ASCIITag          => 1,
+        TagType::ShortTag          => 2,
+        TagType::LongTag           => 4,
+        TagType::RationalTag       => 8,
+        TagType::SignedByteTag     => 1,
+        TagType::UndefinedTag      => 1,
+        TagType::SignedShortTag    => 2,
+        TagType::SignedLongTag     => 4,
+        TagType::SignedRationalTag => 8,
+        TagType::FloatTag          => 4,
+        TagType::DoubleTag         => 8,
+        TagType::IFDTag            => 4,
+        TagType::Long8             => 8,
+        TagType::SLong8            => 8,
+        TagType::IFD8              => 8,
+        TagType::ShortOrLongTag    => 4,
+    }
+}
+
+pub fn decode_photometric_interpretation(value: u16) -> Option<PhotometricInterpretation> {
+    match value {
+        0 => Some(PhotometricInterpretation::WhiteIsZero),
+        1 => Some(PhotometricInterpretation::BlackIsZero),
+        2 => Some(PhotometricInterpretation::RGB),
+        3 => Some(PhotometricInterpretation::Palette),
+        4 => Some(PhotometricInterpretation::TransparencyMask),
+        5 => Some(PhotometricInterpretation::CMYK),
+        6 => Some(PhotometricInterpretation::YCbCr),
+        8 => Some(PhotometricInterpretation::CIELab),
+        32803 => Some(PhotometricInterpretation::CFA),
+        34892 => Some(PhotometricInterpretation::LinearRaw),
+        _ => None,
+    }
+}
+
+pub fn decode_compression(value: u16) -> Option<Compression> {
+    match value {
+        1 => Some(Compression::None),
+        2 => Some(Compression::Huffman),
+        5 => Some(Compression::LZW),
+        6 => Some(Compression::OJPEG),
+        7 => Some(Compression::JPEG),
+        8 => Some(Compression::Deflate),
+        32773 => Some(Compression::PackBits),
+        32946 => Some(Compression::DeflateOld),
+        _ => None,
+    }
+}
+
+pub fn decode_predictor(value: u16) -> Option<Predictor> {
+    match value {
+        1 => Some(Predictor::None),
+        2 => Some(Predictor::Horizontal),
+        3 => Some(Predictor::FloatingPoint),
+        _ => None,
+    }
+}
+
+pub fn decode_sample_format(value: u16) -> Option<SampleFormat> {
+    match value {
+        1 => Some(SampleFormat::UnsignedInteger),
+        2 => Some(SampleFormat::TwosComplementSignedInteger),
+        3 => Some(SampleFormat::IEEEFloatingPoint),
+        4 => Some(SampleFormat::Undefined),
+        _ => None,
+    }
+}
+
+// p31: Unknown ExtraSamples values are reserved; treat them as unspecified
+// data rather than rejecting the image.
+pub fn decode_extra_samples(value: u16) -> ExtraSamples {
+    match value {
+        1 => ExtraSamples::AssociatedAlpha,
+        2 => ExtraSamples::UnassociatedAlpha,
+        _ => ExtraSamples::Unspecified,
+    }
+}
+
+pub fn decode_resolution_unit(value: u16) -> Option<ResolutionUnit> {
+    match value {
+        1 => Some(ResolutionUnit::None),
+        2 => Some(ResolutionUnit::Inch),
+        3 => Some(ResolutionUnit::Centimetre),
+        _ => None,
+    }
+}
+
+pub fn decode_orientation(value: u16) -> Option<ImageOrientation> {
+    match value {
+        1 => Some(ImageOrientation::TopLeft),
+        2 => Some(ImageOrientation::TopRight),
+        3 => Some(ImageOrientation::BottomRight),
+        4 => Some(ImageOrientation::BottomLeft),
+        5 => Some(ImageOrientation::LeftTop),
+        6 => Some(ImageOrientation::RightTop),
+        7 => Some(ImageOrientation::RightBottom),
+        8 => Some(ImageOrientation::LeftBottom),
         _ => None,
     }
 }
@@ -416,7 +747,7 @@ pub fn type_and_count_for_tag(tag: TIFFTag) -> Option<(TagType, u32)> {
         TIFFTag::MaxSampleValueTag            => Some((TagType::ShortTag, 0)),
         TIFFTag::MinSampleValueTag            => Some((TagType::ShortTag, 0)),
         TIFFTag::ModelTag                     => Some((TagType::ASCIITag, 0)),
-//        TIFFTag::NewSubfileTypeTag => ,
+        TIFFTag::NewSubfileTypeTag            => Some((TagType::LongTag, 1)),
         TIFFTag::OrientationTag               => Some((TagType::ShortTag, 1)),
         TIFFTag::PhotometricInterpretationTag => Some((TagType::ShortTag, 1)),
         TIFFTag::PlanarConfigurationTag       => Some((TagType::ShortTag, 1)),
@@ -432,132 +763,351 @@ pub fn type_and_count_for_tag(tag: TIFFTag) -> Option<(TagType, u32)> {
         TIFFTag::ThresholdingTag              => Some((TagType::ShortTag, 1)),
         TIFFTag::XResolutionTag               => Some((TagType::RationalTag, 1)),
         TIFFTag::YResolutionTag               => Some((TagType::RationalTag, 1)),
+        // Document storage
+        TIFFTag::DocumentNameTag              => Some((TagType::ASCIITag, 0)),
+        TIFFTag::PageNameTag                  => Some((TagType::ASCIITag, 0)),
+        TIFFTag::PageNumberTag                => Some((TagType::ShortTag, 2)),
+        TIFFTag::XPositionTag                 => Some((TagType::RationalTag, 1)),
+        TIFFTag::YPositionTag                 => Some((TagType::RationalTag, 1)),
+        // Tiles
+        TIFFTag::TileWidthTag                 => Some((TagType::ShortOrLongTag, 1)),
+        TIFFTag::TileLengthTag                => Some((TagType::ShortOrLongTag, 1)),
+        TIFFTag::TileOffsetsTag               => Some((TagType::LongTag, 0)),
+        TIFFTag::TileByteCountsTag            => Some((TagType::ShortOrLongTag, 0)),
         // Extended
         TIFFTag::XMPTag                       => Some((TagType::ByteTag, 0)),
         TIFFTag::PhotoshopTag                 => Some((TagType::ByteTag, 0)),
         TIFFTag::EXIFTag                      => Some((TagType::LongTag, 0)),
+        TIFFTag::GPSTag                       => Some((TagType::LongTag, 1)),
+        // JPEG
+        TIFFTag::JPEGInterchangeFormatTag     => Some((TagType::LongTag, 1)),
+        TIFFTag::JPEGInterchangeFormatLengthTag => Some((TagType::LongTag, 1)),
+        // GeoTIFF
+        TIFFTag::ModelPixelScaleTag           => Some((TagType::DoubleTag, 3)),
+        TIFFTag::ModelTiepointTag             => Some((TagType::DoubleTag, 0)),
+        TIFFTag::ModelTransformationTag       => Some((TagType::DoubleTag, 16)),
+        TIFFTag::GeoKeyDirectoryTag           => Some((TagType::ShortTag, 0)),
+        TIFFTag::GeoDoubleParamsTag           => Some((TagType::DoubleTag, 0)),
+        TIFFTag::GeoAsciiParamsTag            => Some((TagType::ASCIITag, 0)),
+        // GDAL
+        TIFFTag::GDALMetadataTag              => Some((TagType::ASCIITag, 0)),
+        TIFFTag::GDALNoDataTag                => Some((TagType::ASCIITag, 0)),
+        // DNG; fields that may be SHORT, LONG or RATIONAL are not checked
+        TIFFTag::DNGVersionTag                => Some((TagType::ByteTag, 4)),
+        TIFFTag::DNGBackwardVersionTag        => Some((TagType::ByteTag, 4)),
+        TIFFTag::UniqueCameraModelTag         => Some((TagType::ASCIITag, 0)),
+        TIFFTag::CFAPlaneColorTag             => Some((TagType::ByteTag, 0)),
+        TIFFTag::CFALayoutTag                 => Some((TagType::ShortTag, 1)),
+        TIFFTag::LinearizationTableTag        => Some((TagType::ShortTag, 0)),
+        TIFFTag::BlackLevelRepeatDimTag       => Some((TagType::ShortTag, 2)),
+        TIFFTag::BlackLevelDeltaHTag          => Some((TagType::SignedRationalTag, 0)),
+        TIFFTag::BlackLevelDeltaVTag          => Some((TagType::SignedRationalTag, 0)),
+        TIFFTag::WhiteLevelTag                => Some((TagType::ShortOrLongTag, 0)),
+        TIFFTag::DefaultScaleTag              => Some((TagType::RationalTag, 2)),
+        TIFFTag::ColorMatrix1Tag              => Some((TagType::SignedRationalTag, 0)),
+        TIFFTag::ColorMatrix2Tag              => Some((TagType::SignedRationalTag, 0)),
+        TIFFTag::CameraCalibration1Tag        => Some((TagType::SignedRationalTag, 0)),
+        TIFFTag::CameraCalibration2Tag        => Some((TagType::SignedRationalTag, 0)),
+        TIFFTag::ReductionMatrix1Tag          => Some((TagType::SignedRationalTag, 0)),
+        TIFFTag::ReductionMatrix2Tag          => Some((TagType::SignedRationalTag, 0)),
+        TIFFTag::AnalogBalanceTag             => Some((TagType::RationalTag, 0)),
+        TIFFTag::AsShotWhiteXYTag             => Some((TagType::RationalTag, 2)),
+        TIFFTag::BaselineExposureTag          => Some((TagType::SignedRationalTag, 1)),
+        TIFFTag::CameraSerialNumberTag        => Some((TagType::ASCIITag, 0)),
+        TIFFTag::CalibrationIlluminant1Tag    => Some((TagType::ShortTag, 1)),
+        TIFFTag::CalibrationIlluminant2Tag    => Some((TagType::ShortTag, 1)),
+        TIFFTag::ActiveAreaTag                => Some((TagType::ShortOrLongTag, 4)),
+        TIFFTag::ForwardMatrix1Tag            => Some((TagType::SignedRationalTag, 0)),
+        TIFFTag::ForwardMatrix2Tag            => Some((TagType::SignedRationalTag, 0)),
         //
         _ =>  None,
     }
 }
 
-pub fn validate_required_tags_for(typ: &ImageType) -> Option<HashSet<TIFFTag>> {
+// Check an IFD against the fields the specification requires of the given
+// type of image (Sections 3 to 6 and 21), returning those it lacks, or None
+// if it has them all.  Tiled images (Section 15) need the tile fields in
+// place of the strip fields.
+pub fn validate_required_tags_for(typ: &ImageType, ifd: &IFD) -> Option<HashSet<TIFFTag>> {
 
-    let required_grayscale_tags: HashSet<TIFFTag> = [
+    let mut required = vec![
         TIFFTag::ImageWidthTag,
         TIFFTag::ImageLengthTag,
-        TIFFTag::BitsPerSampleTag,
         TIFFTag::CompressionTag,
         TIFFTag::PhotometricInterpretationTag,
-        TIFFTag::StripOffsetsTag,
-        TIFFTag::RowsPerStripTag,
-        TIFFTag::StripByteCountsTag,
-        TIFFTag::XResolutionTag,
-        TIFFTag::YResolutionTag,
-        TIFFTag::ResolutionUnitTag].iter().cloned().collect();
-
-    let required_rgb_image_tags: HashSet<TIFFTag> = [
-        TIFFTag::ImageWidthTag,
-        TIFFTag::ImageLengthTag,
-        TIFFTag::BitsPerSampleTag,
-        TIFFTag::CompressionTag,
-        TIFFTag::PhotometricInterpretationTag,
-        TIFFTag::StripOffsetsTag,
-        TIFFTag::SamplesPerPixelTag,
-        TIFFTag::RowsPerStripTag,
-        TIFFTag::StripByteCountsTag,
         TIFFTag::XResolutionTag,
         TIFFTag::YResolutionTag,
         TIFFTag::ResolutionUnitTag,
-    ].iter().cloned().collect();
+    ];
+
+    let tiled = ifd.get(TIFFTag::TileWidthTag).is_some() || ifd.get(TIFFTag::TileOffsetsTag).is_some();
+    if tiled {
+        required.extend_from_slice(&[
+            TIFFTag::TileWidthTag,
+            TIFFTag::TileLengthTag,
+            TIFFTag::TileOffsetsTag,
+            TIFFTag::TileByteCountsTag,
+        ]);
+    } else {
+        required.extend_from_slice(&[
+            TIFFTag::StripOffsetsTag,
+            TIFFTag::RowsPerStripTag,
+            TIFFTag::StripByteCountsTag,
+        ]);
+    }
 
     match *typ {
-        ImageType::Bilevel => None, 
-        ImageType::Grayscale => None,
-        ImageType::PaletteColour => None,
-        ImageType::RGB => Some(required_rgb_image_tags.difference(&required_grayscale_tags).cloned().collect()),
-        ImageType::YCbCr => None,
-    }
-}
-
-/*
-    Required Fields for Bilevel Images
-
-    - ImageWidth 
-    - ImageLength 
-    - Compression 
-    - PhotometricInterpretation 
-    - StripOffsets 
-    - RowsPerStrip 
-    - StripByteCounts 
-    - XResolution 
-    - YResolution 
-    - ResolutionUnit
- */
-
-/*
-    Required Fields for Grayscale Images
-
-    - ImageWidth
-    - ImageLength
-    - BitsPerSample
-    - Compression
-    - PhotometricInterpretation
-    - StripOffsets
-    - RowsPerStrip
-    - StripByteCounts
-    - XResolution
-    - YResolution
-    - ResolutionUnit
- */
-
-/*
-    Required Fields for Palette Colour Images
-
-    - ImageWidth
-    - ImageLength
-    - BitsPerSample
-    - Compression
-    - PhotometricInterpretation
-    - StripOffsets
-    - RowsPerStrip
-    - StripByteCounts
-    - XResolution
-    - YResolution
-    - ResolutionUnit
-    - ColorMap
- */
-
-/*
-    Required Fields for RGB Images
-
-    - ImageWidth
-    - ImageLength
-    - BitsPerSample
-    - Compression
-    - PhotometricInterpretation
-    - StripOffsets
-    - SamplesPerPixel
-    - RowsPerStrip
-    - StripByteCounts
-    - XResolution
-    - YResolution
-    - ResolutionUnit
- */
+        ImageType::Bilevel => {},
+        ImageType::Grayscale => required.push(TIFFTag::BitsPerSampleTag),
+        ImageType::PaletteColour => required.extend_from_slice(&[
+            TIFFTag::BitsPerSampleTag,
+            TIFFTag::ColorMapTag,
+        ]),
+        ImageType::RGB => required.extend_from_slice(&[
+            TIFFTag::BitsPerSampleTag,
+            TIFFTag::SamplesPerPixelTag,
+        ]),
+        // p94: the minimum requirements for YCbCr images
+        ImageType::YCbCr => required.extend_from_slice(&[
+            TIFFTag::BitsPerSampleTag,
+            TIFFTag::SamplesPerPixelTag,
+            TIFFTag::YCbCrSubsampling,
+            TIFFTag::YCbCrPositioning,
+            TIFFTag::ReferenceBlackWhite,
+        ]),
+    }
+
+    let missing: HashSet<TIFFTag> = required.into_iter().filter(|&tag| ifd.get(tag).is_none()).collect();
+    if missing.is_empty() {
+        None
+    } else {
+        Some(missing)
+    }
+}
+
+//----------------------------------------------------------------------------
+// Field values
+
+impl IFDEntry {
+
+    pub fn tag(&self) -> Option<TIFFTag> {
+        decode_tag(self.tag)
+    }
+
+    // Decode all values held by this entry.  ASCII fields yield one value
+    // per NUL-terminated string.
+    pub fn values(&self) -> Vec<TagValue> {
+        match self.byte_order {
+            TIFFByteOrder::LittleEndian => self.values_::<LittleEndian>(),
+            TIFFByteOrder::BigEndian => self.values_::<BigEndian>(),
+        }
+    }
+
+    fn values_<Endian: ByteOrder>(&self) -> Vec<TagValue> {
+
+        let d = &self.data;
+
+        match self.typ {
+            TagType::ByteTag | TagType::UndefinedTag =>
+                d.iter().map(|&b| TagValue::ByteValue(b)).collect(),
+            TagType::ASCIITag =>
+                d.split(|&b| b == 0)
+                 .filter(|s| !s.is_empty())
+                 .map(|s| TagValue::AsciiValue(String::from_utf8_lossy(s).into_owned()))
+                 .collect(),
+            TagType::ShortTag =>
+                d.chunks(2).map(|c| TagValue::ShortValue(Endian::read_u16(c))).collect(),
+            TagType::LongTag | TagType::IFDTag | TagType::ShortOrLongTag =>
+                d.chunks(4).map(|c| TagValue::LongValue(Endian::read_u32(c))).collect(),
+            TagType::RationalTag =>
+                d.chunks(8).map(|c| TagValue::RationalValue((Endian::read_u32(&c[0..4]),
+                                                             Endian::read_u32(&c[4..8])))).collect(),
+            TagType::SignedByteTag =>
+                d.iter().map(|&b| TagValue::SignedByteValue(b as SBYTE)).collect(),
+            TagType::SignedShortTag =>
+                d.chunks(2).map(|c| TagValue::SignedShortValue(Endian::read_i16(c))).collect(),
+            TagType::SignedLongTag =>
+                d.chunks(4).map(|c| TagValue::SignedLongValue(Endian::read_i32(c))).collect(),
+            TagType::SignedRationalTag =>
+                d.chunks(8).map(|c| TagValue::SignedRationalValue((Endian::read_i32(&c[0..4]),
+                                                                   Endian::read_i32(&c[4..8])))).collect(),
+            TagType::FloatTag =>
+                d.chunks(4).map(|c| TagValue::FloatValue(Endian::read_f32(c))).collect(),
+            TagType::DoubleTag =>
+                d.chunks(8).map(|c| TagValue::DoubleValue(Endian::read_f64(c))).collect(),
+            TagType::Long8 =>
+                d.chunks(8).map(|c| TagValue::Long8Value(Endian::read_u64(c))).collect(),
+            TagType::SLong8 =>
+                d.chunks(8).map(|c| TagValue::SignedLong8Value(Endian::read_i64(c))).collect(),
+            TagType::IFD8 =>
+                d.chunks(8).map(|c| TagValue::IFD8Value(Endian::read_u64(c))).collect(),
+        }
+    }
+
+    pub fn value(&self) -> Option<TagValue> {
+        self.values().into_iter().next()
+    }
+
+    // Unsigned integer values, for BYTE, SHORT and LONG fields.
+    pub fn as_u32s(&self) -> Option<Vec<u32>> {
+        let mut result = Vec::with_capacity(self.count as usize);
+        for v in self.values() {
+            match v {
+                TagValue::ByteValue(x) => result.push(x as u32),
+                TagValue::ShortValue(x) => result.push(x as u32),
+                TagValue::LongValue(x) => result.push(x),
+                _ => return None,
+            }
+        }
+        Some(result)
+    }
+
+    // Unsigned integer values, also accepting the BigTIFF LONG8 and IFD8
+    // types.
+    pub fn as_u64s(&self) -> Option<Vec<u64>> {
+        let mut result = Vec::with_capacity(self.count as usize);
+        for v in self.values() {
+            match v {
+                TagValue::ByteValue(x) => result.push(x as u64),
+                TagValue::ShortValue(x) => result.push(x as u64),
+                TagValue::LongValue(x) => result.push(x as u64),
+                TagValue::Long8Value(x) => result.push(x),
+                TagValue::IFD8Value(x) => result.push(x),
+                _ => return None,
+            }
+        }
+        Some(result)
+    }
+
+    // Any numeric values, widened to f64.  Rationals with a zero denominator
+    // are returned as NaN.
+    pub fn as_f64s(&self) -> Option<Vec<f64>> {
+        let mut result = Vec::with_capacity(self.count as usize);
+        for v in self.values() {
+            let x = match v {
+                TagValue::ByteValue(x) => x as f64,
+                TagValue::ShortValue(x) => x as f64,
+                TagValue::LongValue(x) => x as f64,
+                TagValue::RationalValue((n, d)) => rational_to_f64(n as f64, d as f64),
+                TagValue::SignedByteValue(x) => x as f64,
+                TagValue::SignedShortValue(x) => x as f64,
+                TagValue::SignedLongValue(x) => x as f64,
+                TagValue::SignedRationalValue((n, d)) => rational_to_f64(n as f64, d as f64),
+                TagValue::FloatValue(x) => x as f64,
+                TagValue::DoubleValue(x) => x,
+                TagValue::Long8Value(x) => x as f64,
+                TagValue::SignedLong8Value(x) => x as f64,
+                TagValue::IFD8Value(x) => x as f64,
+                TagValue::AsciiValue(_) => return None,
+            };
+            result.push(x);
+        }
+        Some(result)
+    }
+
+    pub fn as_ascii(&self) -> Option<String> {
+        match self.value() {
+            Some(TagValue::AsciiValue(s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+fn rational_to_f64(n: f64, d: f64) -> f64 {
+    if d == 0.0 { f64::NAN } else { n / d }
+}
+
+impl IFD {
+
+    // The type of image, from its PhotometricInterpretation and, for black
+    // and white images, BitsPerSample.  None for other types, such as CMYK.
+    pub fn image_type(&self) -> Option<ImageType> {
+        let photometric = self.get_u32(TIFFTag::PhotometricInterpretationTag)
+            .and_then(|p| decode_photometric_interpretation(p as u16))?;
+        match photometric {
+            PhotometricInterpretation::WhiteIsZero | PhotometricInterpretation::BlackIsZero => {
+                match self.get_u32(TIFFTag::BitsPerSampleTag).unwrap_or(1) {
+                    1 => Some(ImageType::Bilevel),
+                    _ => Some(ImageType::Grayscale),
+                }
+            },
+            PhotometricInterpretation::Palette => Some(ImageType::PaletteColour),
+            PhotometricInterpretation::RGB => Some(ImageType::RGB),
+            PhotometricInterpretation::YCbCr => Some(ImageType::YCbCr),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, tag: TIFFTag) -> Option<&IFDEntry> {
+        self.get_raw(tag as u16)
+    }
+
+    pub fn get_raw(&self, tag: u16) -> Option<&IFDEntry> {
+        self.entries.iter().find(|e| e.tag == tag)
+    }
+
+    pub fn get_u32(&self, tag: TIFFTag) -> Option<u32> {
+        self.get_u32s(tag).and_then(|v| v.first().cloned())
+    }
+
+    pub fn get_u32s(&self, tag: TIFFTag) -> Option<Vec<u32>> {
+        self.get(tag).and_then(|e| e.as_u32s())
+    }
+
+    pub fn get_u64s(&self, tag: TIFFTag) -> Option<Vec<u64>> {
+        self.get(tag).and_then(|e| e.as_u64s())
+    }
+
+    pub fn get_f64(&self, tag: TIFFTag) -> Option<f64> {
+        self.get_f64s(tag).and_then(|v| v.first().cloned())
+    }
+
+    pub fn get_f64s(&self, tag: TIFFTag) -> Option<Vec<f64>> {
+        self.get(tag).and_then(|e| e.as_f64s())
+    }
+
+    pub fn get_ascii(&self, tag: TIFFTag) -> Option<String> {
+        self.get(tag).and_then(|e| e.as_ascii())
+    }
+
+    // Add a field, replacing any with the same tag.
+    pub fn set(&mut self, entry: IFDEntry) {
+        self.entries.retain(|e| e.tag != entry.tag);
+        self.entries.push(entry);
+        self.count = self.entries.len() as LONG8;
+    }
+
+    // Remove a field, returning it if it was present.
+    pub fn remove(&mut self, tag: u16) -> Option<IFDEntry> {
+        let index = self.entries.iter().position(|e| e.tag == tag)?;
+        let entry = self.entries.remove(index);
+        self.count = self.entries.len() as LONG8;
+        Some(entry)
+    }
+}
 
 impl TIFF {
 
-    pub fn open(filename: &str) {
+    pub fn open(filename: &str) -> std::io::Result<Box<TIFF>> {
+        TIFFReader.load(filename)
+    }
+
+    pub fn get_field(&self, tag: TIFFTag) -> Option<&IFDEntry> {
+        self.ifds.first().and_then(|ifd| ifd.get(tag))
     }
-/*
-    pub fn get_field(&self, tag: TIFFTag) -> Option<TagValue> {
 
-        if self.ifd.contains_key(tag) {
-            return Some(self.ifd.get(tag));
+    // Every IFD of the main chain and their SubIFDs, depth first.
+    pub fn all_ifds(&self) -> Vec<&IFD> {
+        fn visit<'a>(ifd: &'a IFD, all: &mut Vec<&'a IFD>) {
+            all.push(ifd);
+            for sub in &ifd.sub_ifds {
+                visit(sub, all);
+            }
         }
-        else {
-            None
+        let mut all = Vec::new();
+        for ifd in &self.ifds {
+            visit(ifd, &mut all);
         }
+        all
     }
-*/
 }
@@ -0,0 +1,244 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and the Adobe Photoshop File Formats Specification, "Image Resources".
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, ErrorKind};
+
+use {IFD, TIFFTag, ResolutionUnit};
+use iptc::IPTC;
+use resolution::Resolution;
+
+//----------------------------------------------------------------------------
+// Types
+
+// Well-known image resource IDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageResourceID {
+    ResolutionInfo               = 0x03ed,
+    LayerState                   = 0x0400,
+    LayerGroups                  = 0x0402,
+    IPTCNAA                      = 0x0404,
+    // Photoshop 4.0 thumbnail, with its channels in BGR order
+    ThumbnailBGR                 = 0x0409,
+    Thumbnail                    = 0x040c,
+    ICCProfile                   = 0x040f,
+    VersionInfo                  = 0x0421,
+    ExifData                     = 0x0422,
+    XMPMetadata                  = 0x0424,
+    CaptionDigest                = 0x0425,
+    LayerSelectionIDs            = 0x042d,
+    LayerGroupsEnabled           = 0x0430,
+}
+
+// One resource block.  Most are signed "8BIM", though a few other
+// signatures are in use.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageResource {
+    pub signature: [u8; 4],
+    pub id:        u16,
+    pub name:      String,
+    pub data:      Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhotoshopResources {
+    pub resources: Vec<ImageResource>,
+}
+
+// Resolution is always stored in pixels per inch; the units only say how
+// Photoshop displays it (1 per inch, 2 per cm) and the image size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResolutionInfo {
+    pub h_res:       f64,
+    pub h_res_unit:  u16,
+    pub width_unit:  u16,
+    pub v_res:       f64,
+    pub v_res_unit:  u16,
+    pub height_unit: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhotoshopThumbnail {
+    // 1 for JFIF, 0 for raw RGB
+    pub format:         u32,
+    pub width:          u32,
+    pub height:         u32,
+    pub bits_per_pixel: u16,
+    pub planes:         u16,
+    // Taken from the older resource, whose channels are in BGR order
+    pub bgr:            bool,
+    pub data:           Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhotoshopVersionInfo {
+    pub version:              u32,
+    // Whether the file holds a flattened composite alongside any layers
+    pub has_real_merged_data: bool,
+    pub writer:               String,
+    pub reader:               String,
+    pub file_version:         u32,
+}
+
+//----------------------------------------------------------------------------
+
+impl PhotoshopResources {
+
+    pub fn parse(data: &[u8]) -> Result<PhotoshopResources> {
+
+        let mut resources = Vec::new();
+        let mut pos = 0;
+
+        // Some writers leave padding after the last block
+        while pos + 4 <= data.len() && data[pos..].iter().any(|&b| b != 0) {
+            let mut signature = [0; 4];
+            signature.copy_from_slice(&data[pos..pos + 4]);
+            let id = read_u16(data, pos + 4)?;
+
+            // A Pascal string, padded to an even length
+            let name_length = *data.get(pos + 6).ok_or_else(|| invalid("Truncated resource header"))? as usize;
+            let name = data.get(pos + 7..pos + 7 + name_length).ok_or_else(|| invalid("Truncated resource name"))?;
+            let name: String = name.iter().map(|&b| b as char).collect();
+            pos += 6 + (1 + name_length).next_multiple_of(2);
+
+            let size = read_u32(data, pos)? as usize;
+            let value = data.get(pos + 4..(pos + 4).saturating_add(size))
+                .ok_or_else(|| invalid(&format!("Resource {:04x} overruns the block", id)))?;
+            resources.push(ImageResource { signature, id, name, data: value.to_vec() });
+            pos += 4 + size.next_multiple_of(2);
+        }
+
+        Ok(PhotoshopResources { resources })
+    }
+
+    pub fn get(&self, id: ImageResourceID) -> Option<&ImageResource> {
+        self.get_raw(id as u16)
+    }
+
+    pub fn get_raw(&self, id: u16) -> Option<&ImageResource> {
+        self.resources.iter().find(|r| r.id == id && &r.signature == b"8BIM")
+    }
+
+    pub fn resolution_info(&self) -> Option<ResolutionInfo> {
+        let d = &self.get(ImageResourceID::ResolutionInfo)?.data;
+        if d.len() < 16 {
+            return None;
+        }
+        let fixed = |i: usize| read_u32(d, i).ok().map(|v| v as f64 / 65536.0);
+        Some(ResolutionInfo {
+            h_res:       fixed(0)?,
+            h_res_unit:  read_u16(d, 4).ok()?,
+            width_unit:  read_u16(d, 6).ok()?,
+            v_res:       fixed(8)?,
+            v_res_unit:  read_u16(d, 12).ok()?,
+            height_unit: read_u16(d, 14).ok()?,
+        })
+    }
+
+    pub fn iptc(&self) -> Result<Option<IPTC>> {
+        match self.get(ImageResourceID::IPTCNAA) {
+            Some(r) => Ok(Some(IPTC::parse(&r.data)?)),
+            None => Ok(None),
+        }
+    }
+
+    // The thumbnail, preferring the current resource to the Photoshop 4.0 one.
+    pub fn thumbnail(&self) -> Option<PhotoshopThumbnail> {
+        let (r, bgr) = match self.get(ImageResourceID::Thumbnail) {
+            Some(r) => (r, false),
+            None => (self.get(ImageResourceID::ThumbnailBGR)?, true),
+        };
+        let d = &r.data;
+        if d.len() < 28 {
+            return None;
+        }
+        Some(PhotoshopThumbnail {
+            format:         read_u32(d, 0).ok()?,
+            width:          read_u32(d, 4).ok()?,
+            height:         read_u32(d, 8).ok()?,
+            bits_per_pixel: read_u16(d, 24).ok()?,
+            planes:         read_u16(d, 26).ok()?,
+            bgr,
+            data:           d[28..].to_vec(),
+        })
+    }
+
+    pub fn version_info(&self) -> Option<PhotoshopVersionInfo> {
+        let d = &self.get(ImageResourceID::VersionInfo)?.data;
+        let version = read_u32(d, 0).ok()?;
+        let has_real_merged_data = *d.get(4)? != 0;
+        let (writer, pos) = read_unicode(d, 5)?;
+        let (reader, pos) = read_unicode(d, pos)?;
+        let file_version = read_u32(d, pos).ok()?;
+        Some(PhotoshopVersionInfo { version, has_real_merged_data, writer, reader, file_version })
+    }
+
+    // Whether the document has layers or layer masks, as recorded by the
+    // layer resources.  The layer data itself, if saved, is in the
+    // ImageSourceData field.
+    pub fn has_layers(&self) -> bool {
+        [ImageResourceID::LayerState, ImageResourceID::LayerGroups, ImageResourceID::LayerSelectionIDs,
+         ImageResourceID::LayerGroupsEnabled].iter().any(|&id| self.get(id).is_some())
+    }
+}
+
+impl ResolutionInfo {
+
+    // As a resolution in the unit Photoshop displays.
+    pub fn resolution(&self) -> Resolution {
+        let unit = if self.h_res_unit == 2 { ResolutionUnit::Centimetre } else { ResolutionUnit::Inch };
+        let resolution = Resolution { x: self.h_res, y: self.v_res, unit: ResolutionUnit::Inch };
+        match resolution.dots_per_cm() {
+            Some((x, y)) if unit == ResolutionUnit::Centimetre => Resolution { x, y, unit },
+            _ => resolution,
+        }
+    }
+}
+
+impl IFD {
+
+    pub fn photoshop_resources(&self) -> Result<Option<PhotoshopResources>> {
+        match self.get(TIFFTag::PhotoshopTag) {
+            Some(e) => Ok(Some(PhotoshopResources::parse(&e.data)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+fn read_u16(data: &[u8], pos: usize) -> Result<u16> {
+    data.get(pos..pos + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid("Truncated resource"))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32> {
+    data.get(pos..pos + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("Truncated resource"))
+}
+
+// A UTF-16 string preceded by its length in code units, and the position
+// after it.
+fn read_unicode(data: &[u8], pos: usize) -> Option<(String, usize)> {
+    let length = read_u32(data, pos).ok()? as usize;
+    let bytes = data.get(pos + 4..(pos + 4).checked_add(length.checked_mul(2)?)?)?;
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+    let text = String::from_utf16_lossy(&units).trim_end_matches('\0').to_string();
+    Some((text, pos + 4 + bytes.len()))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid Photoshop resources: {}", message))
+}
@@ -11,45 +11,60 @@
 //
 //============================================================================
 
-use std::io::{Result, Error, ErrorKind, Read, Seek, SeekFrom};
+use std::io::{Result, Error, ErrorKind, Read, SeekFrom};
+use std::collections::HashSet;
 use std::path::Path;
 use std::fs::File;
 
-use byteorder::{ReadBytesExt, WriteBytesExt, ByteOrder, BigEndian, LittleEndian};
+use byteorder::{ReadBytesExt, ByteOrder, BigEndian, LittleEndian};
 
-use {TIFFByteOrder, HeaderMagic, TIFFHeader, IFD, IFDEntry, decode_tag, decode_tag_type, type_and_count_for_tag, SeekableReader, BYTE, SBYTE, SHORT, SSHORT, LONG, SLONG, FLOAT, TagType, TagValue};
+use {TIFF, TIFFByteOrder, HeaderMagic, TIFFHeader, TIFFVersion, IFD, IFDEntry, TIFFTag, decode_tag_type, size_of_tag_type, decode_photometric_interpretation, decode_sample_format, decode_compression, decode_predictor, decode_extra_samples, decode_orientation, SeekableReader, LONG8, SampleFormat, Compression, Predictor, PhotometricInterpretation, ImageOrientation};
+use image::{Image, ImageData};
+use compression::{decompress, max_expansion, undo_predictor};
 
 pub struct TIFFReader;
 
+// Deepest nesting of SubIFDs followed below a main IFD.
+const MAX_SUB_IFD_DEPTH: usize = 16;
+
+// Options controlling how image data is decoded.
+#[derive(Clone, Debug, Default)]
+pub struct DecodeOptions {
+    // Transform pixels so that row 0 is the top and column 0 the left hand
+    // side, as given by the Orientation field.
+    pub normalize_orientation: bool,
+}
+
 impl TIFFReader {
 
-    pub fn load(&self, filename: &str) -> Result<Box<TIFFHeader>> {
+    pub fn load(&self, filename: &str) -> Result<Box<TIFF>> {
 
         let filepath = Path::new(filename);
-        let mut reader = File::open(&filepath).unwrap();
+        let mut reader = File::open(filepath)?;
 
         self.read(&mut reader)
     }
 
-    pub fn read(&self, reader: &mut SeekableReader) -> Result<Box<TIFFHeader>> {
+    pub fn read(&self, reader: &mut dyn SeekableReader) -> Result<Box<TIFF>> {
 
-        let byte_order = self.read_byte_order(reader);
+        // The header is always at the start of the file
+        reader.seek(SeekFrom::Start(0))?;
 
-        let magic = match byte_order {
-            Ok(TIFFByteOrder::LittleEndian) => self.read_magic(reader),
-            Ok(TIFFByteOrder::BigEndian) => self.read_magic(reader),
-            Err(e) => Err(e)
-        };
+        let byte_order = self.read_byte_order(reader)?;
+        let magic = self.read_magic(reader)?;
 
-        self.read_::<BigEndian>(reader)
+        match byte_order {
+            TIFFByteOrder::LittleEndian => self.read_::<LittleEndian>(reader, byte_order, magic),
+            TIFFByteOrder::BigEndian => self.read_::<BigEndian>(reader, byte_order, magic),
+        }
     }
 
-    pub fn read_byte_order(&self, reader: &mut SeekableReader) -> Result<TIFFByteOrder> {
+    pub fn read_byte_order(&self, reader: &mut dyn SeekableReader) -> Result<TIFFByteOrder> {
 
         // Bytes 0-1: "II" or "MM"
         // Read and validate ByteOrder
 
-        let byte_order_field = try!(reader.read_u16::<LittleEndian>());
+        let byte_order_field = reader.read_u16::<LittleEndian>()?;
         let byte_order: TIFFByteOrder;
 
         if byte_order_field == TIFFByteOrder::LittleEndian as u16 {
@@ -57,162 +72,599 @@ impl TIFFReader {
         } else if byte_order_field == TIFFByteOrder::BigEndian as u16 {
             byte_order = TIFFByteOrder::BigEndian;
         } else {
-            return Err(Error::new(ErrorKind::Other,
-                                  format!("Invalid byte order in header: {:04x}", byte_order_field)));
+            return Err(Error::other(format!("Invalid byte order in header: {:04x}", byte_order_field)));
         }
-        println!("byte_order {:?}", byte_order);
 
         Ok(byte_order)
     }
 
-    pub fn read_magic(&self, reader: &mut SeekableReader) -> Result<HeaderMagic> {
+    pub fn read_magic(&self, reader: &mut dyn SeekableReader) -> Result<HeaderMagic> {
 
-        // Bytes 2-3: 0042
+        // Bytes 2-3: 0042, or 0043 for BigTIFF
         // Read and validate HeaderMagic
 
-        let magic_field = try!(reader.read_u16::<LittleEndian>());
-
-        let magic: HeaderMagic;
+        let magic_field = reader.read_u16::<LittleEndian>()?;
 
         if magic_field == HeaderMagic::LittleEndian as u16 {
             Ok(HeaderMagic::LittleEndian)
         }
         else if magic_field == HeaderMagic::BigEndian as u16 {
             Ok(HeaderMagic::BigEndian)
+        }
+        else if magic_field == HeaderMagic::BigTIFFLittleEndian as u16 {
+            Ok(HeaderMagic::BigTIFFLittleEndian)
+        }
+        else if magic_field == HeaderMagic::BigTIFFBigEndian as u16 {
+            Ok(HeaderMagic::BigTIFFBigEndian)
         } else {
-            Err(Error::new(ErrorKind::Other, "Invalid magic number in header"))
+            Err(Error::other("Invalid magic number in header"))
         }
     }
 
-    pub fn read_<Endian: ByteOrder>(&self, reader: &mut SeekableReader) -> Result<Box<TIFFHeader>> {
+    pub fn read_<Endian: ByteOrder>(&self, reader: &mut dyn SeekableReader,
+                                    byte_order: TIFFByteOrder, magic: HeaderMagic) -> Result<Box<TIFF>> {
 
         // @todo Ensure file is >= min size
 
-        // Bytes 4-7: offset
-        // Offset from start of file to first IFD
+        let version = match magic {
+            HeaderMagic::BigTIFFLittleEndian | HeaderMagic::BigTIFFBigEndian => TIFFVersion::BigTIFF,
+            _ => TIFFVersion::Classic,
+        };
 
-        let ifd_offset_field = try!(reader.read_u32::<Endian>());
+        let ifd_offset_field = match version {
+            TIFFVersion::Classic => {
+                // Bytes 4-7: offset
+                // Offset from start of file to first IFD
+                reader.read_u32::<Endian>()? as LONG8
+            },
+            TIFFVersion::BigTIFF => {
+                // Bytes 4-5: offset byte size, always 8
+                // Bytes 6-7: always 0
+                // Bytes 8-15: offset to first IFD
+                let offset_size = reader.read_u16::<Endian>()?;
+                let constant = reader.read_u16::<Endian>()?;
+                if offset_size != 8 || constant != 0 {
+                    return Err(Error::other(format!("Unsupported BigTIFF offset size {}", offset_size)));
+                }
+                reader.read_u64::<Endian>()?
+            },
+        };
 
         // Assemble validated header
 
-        let header = Box::new(TIFFHeader {
-            byte_order: TIFFByteOrder::LittleEndian,
-            magic: HeaderMagic::LittleEndian,
+        let header = TIFFHeader {
+            byte_order,
+            magic,
+            version,
             ifd_offset: ifd_offset_field,
-        });
+        };
+
+        // Follow the chain of IFDs, guarding against cycles
+
+        let mut ifds = Vec::new();
+        let mut visited = HashSet::new();
+        let mut next_offset = ifd_offset_field;
 
-        try!(reader.seek(SeekFrom::Start(ifd_offset_field as u64)));
-        println!("IFD offset: {:?}", ifd_offset_field);
+        while next_offset != 0 && visited.insert(next_offset) {
+            let ifd = self.read_IFD::<Endian>(reader, byte_order, version, next_offset)?;
+            next_offset = ifd.next_offset;
+            ifds.push(*ifd);
+        }
+
+        for ifd in ifds.iter_mut() {
+            self.read_sub_ifds::<Endian>(reader, byte_order, version, ifd, &mut visited, 1);
+        }
+
+        Ok(Box::new(TIFF { header, ifds }))
+    }
+
+    // Read a single IFD at `offset`, such as the EXIF IFD, which is not part
+    // of the main chain.  SubIFDs are not followed.
+    pub fn read_ifd(&self, reader: &mut dyn SeekableReader, header: &TIFFHeader, offset: LONG8) -> Result<IFD> {
+        let ifd = match header.byte_order {
+            TIFFByteOrder::LittleEndian =>
+                self.read_IFD::<LittleEndian>(reader, header.byte_order, header.version, offset)?,
+            TIFFByteOrder::BigEndian =>
+                self.read_IFD::<BigEndian>(reader, header.byte_order, header.version, offset)?,
+        };
+        Ok(*ifd)
+    }
 
-        try!(self.read_IFD::<Endian>(reader));
+    // Read the IFDs listed in an IFD's SubIFDs field, and any chained after
+    // them, along with their own SubIFDs.  IFDs already read are skipped, so
+    // that a loop of offsets ends.  SubIFDs are auxiliary images, so one that
+    // cannot be read ends its chain rather than failing the whole file, and
+    // nesting stops at MAX_SUB_IFD_DEPTH.
+    fn read_sub_ifds<Endian: ByteOrder>(&self, reader: &mut dyn SeekableReader, byte_order: TIFFByteOrder,
+                                        version: TIFFVersion, ifd: &mut IFD,
+                                        visited: &mut HashSet<LONG8>, depth: usize) {
+
+        if depth > MAX_SUB_IFD_DEPTH {
+            return;
+        }
 
-        Ok(header)
+        for offset in ifd.get_u64s(TIFFTag::SubIFDsTag).unwrap_or_default() {
+            let mut next_offset = offset;
+            while next_offset != 0 && visited.insert(next_offset) {
+                let mut sub_ifd = match self.read_IFD::<Endian>(reader, byte_order, version, next_offset) {
+                    Ok(sub_ifd) => *sub_ifd,
+                    Err(_) => break,
+                };
+                self.read_sub_ifds::<Endian>(reader, byte_order, version, &mut sub_ifd, visited, depth + 1);
+                next_offset = sub_ifd.next_offset;
+                ifd.sub_ifds.push(sub_ifd);
+            }
+        }
+    }
+
+    // Read the private IFD, such as the EXIF IFD, at the offset given by a
+    // field of `ifd`, if present.
+    pub(crate) fn read_pointed_ifd(&self, reader: &mut dyn SeekableReader, header: &TIFFHeader, ifd: &IFD,
+                                   tag: TIFFTag) -> Result<Option<IFD>> {
+        match ifd.get_u64s(tag).and_then(|v| v.first().cloned()) {
+            Some(offset) if offset != 0 => Ok(Some(self.read_ifd(reader, header, offset)?)),
+            _ => Ok(None),
+        }
     }
 
     #[allow(non_snake_case)]
-    fn read_IFD<Endian: ByteOrder>(&self, reader: &mut SeekableReader) -> Result<Box<IFD>> {
+    fn read_IFD<Endian: ByteOrder>(&self, reader: &mut dyn SeekableReader, byte_order: TIFFByteOrder,
+                                   version: TIFFVersion, offset: LONG8) -> Result<Box<IFD>> {
 
-        // 2 byte count of IFD entries
-        let entry_count = try!(reader.read_u16::<Endian>());
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(offset))?;
 
-        println!("IFD entry count: {}", entry_count);
+        // 2 byte count of IFD entries (8 bytes for BigTIFF)
+        let (entry_count, count_size, entry_size, next_size) = match version {
+            TIFFVersion::Classic => (reader.read_u16::<Endian>()? as u64, 2u64, 12u64, 4u64),
+            TIFFVersion::BigTIFF => (reader.read_u64::<Endian>()?, 8, 20, 8),
+        };
 
-        let mut ifd = Box::new(IFD { count: entry_count, entries: Vec::with_capacity(entry_count as usize) });
+        // The entries and the next IFD offset must lie within the file
+        let next_position = entry_size.checked_mul(entry_count)
+            .and_then(|size| size.checked_add(offset + count_size))
+            .filter(|&position| position.checked_add(next_size).is_some_and(|e| e <= end))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData,
+                                      format!("IFD at {} with {} entries runs past the end of the file",
+                                              offset, entry_count)))?;
+
+        let mut ifd = Box::new(IFD {
+            offset,
+            count: entry_count,
+            entries: Vec::new(),
+            next_offset: 0,
+            sub_ifds: Vec::new(),
+        });
 
-        for entry_number in 0..entry_count as usize {
-            let entry = self.read_tag::<Endian>(entry_number, reader);
-            match entry {
-                Ok(e) => ifd.entries.push(e),
-                Err(err) => println!("Invalid tag at index {}: {}", entry_number, err),
+        for entry_number in 0..entry_count {
+            reader.seek(SeekFrom::Start(offset + count_size + entry_size * entry_number))?;
+            if let Some(entry) = self.read_tag::<Endian>(byte_order, version, reader)? {
+                ifd.entries.push(entry);
             }
         }
 
+        // 4 byte offset of next IFD, or 0 (8 bytes for BigTIFF)
+        reader.seek(SeekFrom::Start(next_position))?;
+        ifd.next_offset = match version {
+            TIFFVersion::Classic => reader.read_u32::<Endian>()? as LONG8,
+            TIFFVersion::BigTIFF => reader.read_u64::<Endian>()?,
+        };
+
         Ok(ifd)
     }
 
-    fn read_tag<Endian: ByteOrder>(&self, entry_number: usize, reader: &mut SeekableReader) -> Result<IFDEntry> {
-        
+    fn read_tag<Endian: ByteOrder>(&self, byte_order: TIFFByteOrder, version: TIFFVersion,
+                                   reader: &mut dyn SeekableReader) -> Result<Option<IFDEntry>> {
+
         // Bytes 0..1: u16 tag ID
-        let tag_value = try!(reader.read_u16::<Endian>());
+        let tag_value = reader.read_u16::<Endian>()?;
 
         // Bytes 2..3: u16 field Type
-        let typ_value = try!(reader.read_u16::<Endian>());
+        let typ_value = reader.read_u16::<Endian>()?;
+
+        // Classic:
+        //   Bytes 4..7: u32 number of Values of type
+        //   Bytes 8..11: u32 offset in file to Value
+        // BigTIFF:
+        //   Bytes 4..11: u64 number of Values of type
+        //   Bytes 12..19: u64 offset in file to Value
+        let mut value_offset_bytes = [0u8; 8];
+        let (count_value, value_offset_value, inline_size) = match version {
+            TIFFVersion::Classic => {
+                let count = reader.read_u32::<Endian>()? as u64;
+                reader.read_exact(&mut value_offset_bytes[..4])?;
+                (count, Endian::read_u32(&value_offset_bytes[..4]) as u64, 4)
+            },
+            TIFFVersion::BigTIFF => {
+                let count = reader.read_u64::<Endian>()?;
+                reader.read_exact(&mut value_offset_bytes)?;
+                (count, Endian::read_u64(&value_offset_bytes), 8)
+            },
+        };
+
+        // Decode type; p15: readers should skip over fields of unexpected type
+        let typ = match decode_tag_type(typ_value) {
+            Some(t) => t,
+            None => return Ok(None),
+        };
 
-        // Bytes 4..7: u32 number of Values of type
-        let count_value = try!(reader.read_u32::<Endian>());
+        /*
+            p15: Value/Offset
 
-        // Bytes 8..11: u32 offset in file to Value
-        let value_offset_value = try!(reader.read_u32::<Endian>());
+            To save time and space the Value Offset contains the Value instead
+            of pointing to the Value if and only if the Value fits into 4
+            bytes. If the Value is shorter than 4 bytes, it is left-justified
+            within the 4-byte Value Offset, i.e., stored in the lower- numbered
+            bytes. Whether the Value fits within 4 bytes is determined by the
+            Type and Count of the field.
 
-        // Decode tag
-        let tag_msg = format!("Invalid tag {:x}", tag_value);
-        let tag = decode_tag(tag_value).expect(&tag_msg);
+            BigTIFF extends this to values that fit in 8 bytes.
+        */
 
-        // Decode type
-        let typ_msg = format!("Invalid tag type {:x}", typ_value);
-        let typ = decode_tag_type(typ_value).expect(&typ_msg);
+        let size = count_value.checked_mul(size_of_tag_type(typ) as u64)
+            .ok_or_else(|| Error::other("Tag value too large"))?;
+        let data = if size <= inline_size {
+            value_offset_bytes[..size as usize].to_vec()
+        } else {
+            read_bytes(reader, value_offset_value, size)?
+        };
 
-        // Create entry
-        let mut e0 = IFDEntry {
-            tag: tag,
-            typ: typ,
+        let e0 = IFDEntry {
+            tag: tag_value,
+            typ,
             count: count_value,
             value_offset: value_offset_value,
-            value: None,
+            byte_order,
+            data,
         };
 
-        let maybe_tac = type_and_count_for_tag(e0.tag);
+        Ok(Some(e0))
+    }
+
+    //------------------------------------------------------------------------
+    // Image data
 
-        if maybe_tac.is_none() {
-            return Err(Error::new(ErrorKind::Other,
-                                  format!("Unknown tag {:?} in IFD", e0.tag)));
+    // Decode the image described by an IFD into one value per sample, with
+    // samples interleaved (chunky) regardless of the PlanarConfiguration.
+    // Samples of fewer than 8 bits are widened to one byte each.
+    pub fn read_image(&self, reader: &mut dyn SeekableReader, ifd: &IFD) -> Result<Image> {
+        self.read_image_with(reader, ifd, &DecodeOptions::default())
+    }
+
+    pub fn read_image_with(&self, reader: &mut dyn SeekableReader, ifd: &IFD,
+                           options: &DecodeOptions) -> Result<Image> {
+
+        let width = ifd.get_u32(TIFFTag::ImageWidthTag)
+            .ok_or_else(|| Error::other("Missing ImageWidth"))?;
+        let height = ifd.get_u32(TIFFTag::ImageLengthTag)
+            .ok_or_else(|| Error::other("Missing ImageLength"))?;
+        let samples_per_pixel = ifd.get_u32(TIFFTag::SamplesPerPixelTag).unwrap_or(1) as usize;
+
+        let bits = ifd.get_u32s(TIFFTag::BitsPerSampleTag).unwrap_or_else(|| vec![1]);
+        let bits_per_sample = match bits.first() {
+            Some(&b) if b != 0 => b,
+            _ => return Err(Error::new(ErrorKind::InvalidData, "BitsPerSample must be at least 1")),
+        };
+        if bits.iter().any(|&b| b != bits_per_sample) {
+            return Err(Error::other("Differing BitsPerSample values are not supported"));
         }
 
-        let (expected_typ, expected_count) = maybe_tac.unwrap();
+        let compression_field = ifd.get_u32(TIFFTag::CompressionTag).unwrap_or(1);
+        let compression = decode_compression(compression_field as u16)
+            .ok_or_else(|| Error::other(format!("Unknown compression {}", compression_field)))?;
+        let predictor_field = ifd.get_u32(TIFFTag::PredictorTag).unwrap_or(1);
+        let predictor = decode_predictor(predictor_field as u16)
+            .ok_or_else(|| Error::other(format!("Unknown predictor {}", predictor_field)))?;
+
+        let byte_order = ifd.entries.first()
+            .map(|e| e.byte_order)
+            .unwrap_or(TIFFByteOrder::LittleEndian);
+
+        let photometric = ifd.get_u32(TIFFTag::PhotometricInterpretationTag)
+            .and_then(|p| decode_photometric_interpretation(p as u16))
+            .unwrap_or(PhotometricInterpretation::BlackIsZero);
+        let sample_format = ifd.get_u32(TIFFTag::SampleFormatTag)
+            .and_then(|f| decode_sample_format(f as u16))
+            .unwrap_or(SampleFormat::UnsignedInteger);
+
+        // p31: ExtraSamples describes the trailing samples of each pixel
+        let mut extra_samples: Vec<_> = ifd.get_u32s(TIFFTag::ExtraSamplesTag)
+            .unwrap_or_default()
+            .iter()
+            .map(|&x| decode_extra_samples(x as u16))
+            .collect();
+        extra_samples.truncate(samples_per_pixel);
+
+        let orientation = ifd.get_u32(TIFFTag::OrientationTag)
+            .and_then(|o| decode_orientation(o as u16))
+            .unwrap_or(ImageOrientation::TopLeft);
+
+        let planar = ifd.get_u32(TIFFTag::PlanarConfigurationTag).unwrap_or(1);
+        // Each plane holds either all samples (chunky) or a single sample
+        let (planes, samples_per_plane) = if planar == 2 {
+            (samples_per_pixel, 1)
+        } else {
+            (1, samples_per_pixel)
+        };
 
-        println!("IFD[{:?}] tag: {:?} type: {:?} count: {} offset: {:08x}",
-                 entry_number, e0.tag, e0.typ, e0.count, e0.value_offset);
+        let layout = Layout {
+            width: width as usize,
+            height: height as usize,
+            samples_per_plane,
+            bits_per_sample,
+            compression,
+            predictor,
+            byte_order,
+        };
+
+        let plane_data = if ifd.get(TIFFTag::TileWidthTag).is_some() {
+            read_tiles(reader, ifd, &layout, planes)?
+        } else {
+            read_strips(reader, ifd, &layout, planes)?
+        };
+
+        let bits_per_sample = if bits_per_sample < 8 { 8 } else { bits_per_sample };
+        let bytes = interleave_planes(&plane_data, bits_per_sample as usize / 8);
+
+        let data = match byte_order {
+            TIFFByteOrder::BigEndian => decode_samples::<BigEndian>(&bytes, bits_per_sample as u16, sample_format)?,
+            TIFFByteOrder::LittleEndian => decode_samples::<LittleEndian>(&bytes, bits_per_sample as u16, sample_format)?,
+        };
 
-        let valid_short_or_long = expected_typ == TagType::ShortOrLongTag &&
-            (e0.typ == TagType::ShortTag ||
-             e0.typ == TagType::LongTag);
+        let mut image = Image {
+            width,
+            height,
+            samples_per_pixel: samples_per_pixel as u16,
+            bits_per_sample: bits[0] as u16,
+            sample_format,
+            photometric,
+            extra_samples,
+            orientation,
+            source_orientation: orientation,
+            icc_profile: ifd.icc_profile().map(|p| p.to_vec()),
+            data,
+        };
 
-        if  ! valid_short_or_long && e0.typ != expected_typ {
-            println!("    *** ERROR: expected typ: {:?} found: {:?}", expected_typ, e0.typ);
+        if options.normalize_orientation {
+            image.normalize_orientation();
         }
 
-        if expected_count != 0 && e0.count != expected_count {
-            println!("    *** ERROR: expected count: {:?} found: {:?}", expected_count, e0.count);
+        Ok(image)
+    }
+}
+
+//----------------------------------------------------------------------------
+// Strips and tiles
+
+// How the samples of one plane are stored.
+struct Layout {
+    width: usize,
+    height: usize,
+    samples_per_plane: usize,
+    bits_per_sample: u32,
+    compression: Compression,
+    predictor: Predictor,
+    byte_order: TIFFByteOrder,
+}
+
+impl Layout {
+
+    // The decompressed size of `rows` rows of `width` pixels.
+    fn block_size(&self, width: usize, rows: usize) -> Result<usize> {
+        width.checked_mul(self.samples_per_plane)
+            .and_then(|n| n.checked_mul(self.bits_per_sample as usize))
+            .map(|bits| bits.div_ceil(8))
+            .and_then(|row_bytes| row_bytes.checked_mul(rows))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Image dimensions overflow"))
+    }
+
+    // Check that `stored` bytes can decompress to `size`, so that sizes
+    // from corrupt dimensions are not allocated.
+    fn check_size(&self, size: usize, stored: u64) -> Result<()> {
+        if size as u64 > stored.saturating_mul(max_expansion(self.compression)) {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  format!("{} stored bytes cannot hold {} bytes of image data", stored, size)));
         }
+        Ok(())
+    }
 
-        /*
-            p15: Value/Offset
+    // Read, decompress and undo the predictor for a strip or tile of `rows`
+    // rows of `width` pixels.
+    fn read_block(&self, reader: &mut dyn SeekableReader, offset: u64, byte_count: u64,
+                  width: usize, rows: usize) -> Result<Vec<u8>> {
+        let size = self.block_size(width, rows)?;
+        self.check_size(size, byte_count)?;
+        let raw = read_bytes(reader, offset, byte_count)?;
+        let mut bytes = decompress(self.compression, &raw, size)?;
+        bytes.resize(size, 0);
+        undo_predictor(self.predictor, &mut bytes, width * self.samples_per_plane,
+                       self.samples_per_plane, self.bits_per_sample as u16, self.byte_order)?;
+        Ok(bytes)
+    }
+}
 
-            To save time and space the Value Offset contains the Value instead
-            of pointing to the Value if and only if the Value fits into 4
-            bytes. If the Value is shorter than 4 bytes, it is left-justified
-            within the 4-byte Value Offset, i.e., stored in the lower- numbered
-            bytes. Whether the Value fits within 4 bytes is determined by the
-            Type and Count of the field.
-        */
+// Read each plane from strips, unpacking sub-byte samples.
+fn read_strips(reader: &mut dyn SeekableReader, ifd: &IFD, layout: &Layout,
+               planes: usize) -> Result<Vec<Vec<u8>>> {
 
-        // Try to read values
-        if e0.count == 1 {
-            e0.value = match e0.typ {
-                TagType::ByteTag => Some(TagValue::ByteValue(e0.value_offset as BYTE)),
-                TagType::ShortTag => Some(TagValue::ShortValue(e0.value_offset as SHORT)),
-                TagType::LongTag => Some(TagValue::LongValue(e0.value_offset)),
-                TagType::SignedByteTag => Some(TagValue::SignedByteValue(e0.value_offset as SBYTE)),
-                TagType::SignedShortTag => Some(TagValue::SignedShortValue(e0.value_offset as SSHORT)),
-                TagType::SignedLongTag => Some(TagValue::SignedLongValue(e0.value_offset as SLONG)),
-                TagType::FloatTag => Some(TagValue::FloatValue(e0.value_offset as FLOAT)),
-                TagType::ShortOrLongTag => Some(TagValue::LongValue(e0.value_offset as LONG)), // @todo FIXME
-                _ => None
-            };
+    let rows_per_strip = ifd.get_u32(TIFFTag::RowsPerStripTag)
+        .map(|r| r as usize)
+        .unwrap_or(layout.height)
+        .clamp(1, layout.height.max(1));
+
+    let offsets = ifd.get_u64s(TIFFTag::StripOffsetsTag)
+        .ok_or_else(|| Error::other("Missing StripOffsets"))?;
+    let byte_counts = ifd.get_u64s(TIFFTag::StripByteCountsTag)
+        .ok_or_else(|| Error::other("Missing StripByteCounts"))?;
+
+    let strips_per_plane = layout.height.div_ceil(rows_per_strip);
+    if offsets.len() < strips_per_plane * planes || byte_counts.len() < offsets.len() {
+        return Err(Error::other("Too few strips for image"));
+    }
+
+    let plane_size = layout.block_size(layout.width, layout.height)?;
+    let end = reader.seek(SeekFrom::End(0))?;
+
+    let mut plane_data = Vec::with_capacity(planes);
+    for plane in 0..planes {
+        // Reserve what the strips hold, which no byte count beyond the end
+        // of the file can inflate
+        let strips = &byte_counts[plane * strips_per_plane..(plane + 1) * strips_per_plane];
+        let stored = strips.iter().fold(0u64, |total, &count| total.saturating_add(count.min(end)));
+        layout.check_size(plane_size, stored)?;
+        let mut data = Vec::with_capacity(stored.min(plane_size as u64) as usize);
+        for strip in 0..strips_per_plane {
+            let index = plane * strips_per_plane + strip;
+            let rows = rows_per_strip.min(layout.height - strip * rows_per_strip);
+            let bytes = layout.read_block(reader, offsets[index], byte_counts[index], layout.width, rows)?;
+            data.extend_from_slice(&bytes);
         }
+        plane_data.push(unpack_samples(&data, layout.width * layout.samples_per_plane, layout.bits_per_sample));
+    }
+    Ok(plane_data)
+}
+
+// Read each plane from tiles, unpacking sub-byte samples.  Tiles are stored
+// left to right, then top to bottom, and are cropped to the image (p67).
+fn read_tiles(reader: &mut dyn SeekableReader, ifd: &IFD, layout: &Layout,
+              planes: usize) -> Result<Vec<Vec<u8>>> {
 
-        println!("    {:?}", e0.value);
+    let tile_width = ifd.get_u32(TIFFTag::TileWidthTag).unwrap_or(0) as usize;
+    let tile_length = ifd.get_u32(TIFFTag::TileLengthTag).unwrap_or(0) as usize;
+    if tile_width == 0 || tile_length == 0 {
+        return Err(Error::other("Invalid TileWidth or TileLength"));
+    }
 
-        Ok(e0)
+    let offsets = ifd.get_u64s(TIFFTag::TileOffsetsTag)
+        .ok_or_else(|| Error::other("Missing TileOffsets"))?;
+    let byte_counts = ifd.get_u64s(TIFFTag::TileByteCountsTag)
+        .ok_or_else(|| Error::other("Missing TileByteCounts"))?;
+
+    let across = layout.width.div_ceil(tile_width);
+    let down = layout.height.div_ceil(tile_length);
+    let tiles_per_plane = across.checked_mul(down)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Image dimensions overflow"))?;
+    if offsets.len() / planes.max(1) < tiles_per_plane || byte_counts.len() < offsets.len() {
+        return Err(Error::other("Too few tiles for image"));
     }
+
+    // Unpacked samples are whole bytes
+    let sample_bytes = (layout.bits_per_sample as usize).div_ceil(8);
+    let pixel_bytes = layout.samples_per_plane * sample_bytes;
+    let row_bytes = layout.width.checked_mul(pixel_bytes)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Image dimensions overflow"))?;
+    let plane_bytes = row_bytes.checked_mul(layout.height)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Image dimensions overflow"))?;
+
+    let plane_size = layout.block_size(layout.width, layout.height)?;
+    let end = reader.seek(SeekFrom::End(0))?;
+
+    let mut plane_data = Vec::with_capacity(planes);
+    for plane in 0..planes {
+        // The whole plane is allocated up front, so check that the tiles
+        // can hold it first
+        let tiles = &byte_counts[plane * tiles_per_plane..(plane + 1) * tiles_per_plane];
+        let stored = tiles.iter().fold(0u64, |total, &count| total.saturating_add(count.min(end)));
+        layout.check_size(plane_size, stored)?;
+        let mut data = vec![0u8; plane_bytes];
+        for tile in 0..tiles_per_plane {
+            let index = plane * tiles_per_plane + tile;
+            let bytes = layout.read_block(reader, offsets[index], byte_counts[index], tile_width, tile_length)?;
+            let bytes = unpack_samples(&bytes, tile_width * layout.samples_per_plane, layout.bits_per_sample);
+
+            let x = (tile % across) * tile_width;
+            let y = (tile / across) * tile_length;
+            let copy = (tile_width.min(layout.width - x)) * pixel_bytes;
+            let rows = tile_length.min(layout.height - y);
+            for (r, source) in bytes.chunks(tile_width * pixel_bytes).take(rows).enumerate() {
+                let start = (y + r) * row_bytes + x * pixel_bytes;
+                data[start..start + copy].copy_from_slice(&source[..copy]);
+            }
+        }
+        plane_data.push(data);
+    }
+    Ok(plane_data)
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+// Read `count` bytes at `offset` without trusting `count` for allocation.
+pub(crate) fn read_bytes(reader: &mut dyn SeekableReader, offset: u64, count: u64) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::new();
+    reader.take(count).read_to_end(&mut data)?;
+    if (data.len() as u64) < count {
+        return Err(Error::new(ErrorKind::UnexpectedEof,
+                              format!("Expected {} bytes at offset {}", count, offset)));
+    }
+    Ok(data)
+}
+
+// Expand packed rows of sub-byte samples to one byte per sample.  Each row
+// begins on a byte boundary (p16).
+fn unpack_samples(data: &[u8], samples_per_row: usize, bits: u32) -> Vec<u8> {
+    if bits >= 8 {
+        return data.to_vec();
+    }
+    let bits = bits as usize;
+    let row_bytes = (samples_per_row * bits).div_ceil(8);
+    let mask = (1u16 << bits) - 1;
+    let mut result = Vec::with_capacity(data.len() * 8 / bits);
+    for row in data.chunks(row_bytes) {
+        for i in 0..samples_per_row {
+            // A sample may straddle two bytes; read both, most significant
+            // bit first
+            let bit = i * bits;
+            let byte = |n: usize| row.get(n).cloned().unwrap_or(0) as u16;
+            let window = (byte(bit / 8) << 8) | byte(bit / 8 + 1);
+            let shift = 16 - bits - bit % 8;
+            result.push(((window >> shift) & mask) as u8);
+        }
+    }
+    result
+}
+
+fn interleave_planes(planes: &[Vec<u8>], bytes_per_sample: usize) -> Vec<u8> {
+    if planes.len() == 1 {
+        return planes[0].clone();
+    }
+    let samples = planes[0].len() / bytes_per_sample;
+    let mut result = Vec::with_capacity(samples * bytes_per_sample * planes.len());
+    for i in 0..samples {
+        for plane in planes {
+            result.extend_from_slice(&plane[i * bytes_per_sample..(i + 1) * bytes_per_sample]);
+        }
+    }
+    result
+}
+
+fn decode_samples<Endian: ByteOrder>(bytes: &[u8], bits: u16, format: SampleFormat) -> Result<ImageData> {
+    let data = match (bits, format) {
+        (8, SampleFormat::TwosComplementSignedInteger) =>
+            ImageData::I8(bytes.iter().map(|&b| b as i8).collect()),
+        (8, _) =>
+            ImageData::U8(bytes.to_vec()),
+        (16, SampleFormat::TwosComplementSignedInteger) =>
+            ImageData::I16(bytes.chunks(2).map(Endian::read_i16).collect()),
+        (16, SampleFormat::IEEEFloatingPoint) =>
+            return Err(Error::other("16-bit floating point samples are not supported")),
+        (16, _) =>
+            ImageData::U16(bytes.chunks(2).map(Endian::read_u16).collect()),
+        (32, SampleFormat::TwosComplementSignedInteger) =>
+            ImageData::I32(bytes.chunks(4).map(Endian::read_i32).collect()),
+        (32, SampleFormat::IEEEFloatingPoint) =>
+            ImageData::F32(bytes.chunks(4).map(Endian::read_f32).collect()),
+        (32, _) =>
+            ImageData::U32(bytes.chunks(4).map(Endian::read_u32).collect()),
+        (64, SampleFormat::TwosComplementSignedInteger) =>
+            ImageData::I64(bytes.chunks(8).map(Endian::read_i64).collect()),
+        (64, SampleFormat::IEEEFloatingPoint) =>
+            ImageData::F64(bytes.chunks(8).map(Endian::read_f64).collect()),
+        (64, _) =>
+            ImageData::U64(bytes.chunks(8).map(Endian::read_u64).collect()),
+        _ =>
+            return Err(Error::other(format!("Unsupported BitsPerSample {}", bits))),
+    };
+    Ok(data)
 }
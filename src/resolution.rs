@@ -0,0 +1,88 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use {IFD, TIFFTag, ResolutionUnit, decode_resolution_unit};
+
+//----------------------------------------------------------------------------
+// Constants
+
+const CM_PER_INCH: f64 = 2.54;
+
+//----------------------------------------------------------------------------
+// Types
+
+// Pixels per ResolutionUnit in each direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resolution {
+    pub x:    f64,
+    pub y:    f64,
+    pub unit: ResolutionUnit,
+}
+
+//----------------------------------------------------------------------------
+
+impl Resolution {
+
+    // Dots per inch, or None if the unit is not absolute.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            ResolutionUnit::Inch => Some((self.x, self.y)),
+            ResolutionUnit::Centimetre => Some((self.x * CM_PER_INCH, self.y * CM_PER_INCH)),
+            ResolutionUnit::None => None,
+        }
+    }
+
+    // Dots per centimetre, or None if the unit is not absolute.
+    pub fn dots_per_cm(&self) -> Option<(f64, f64)> {
+        self.dpi().map(|(x, y)| (x / CM_PER_INCH, y / CM_PER_INCH))
+    }
+
+    // Ratio of pixel width to height; valid for any unit.
+    pub fn pixel_aspect_ratio(&self) -> f64 {
+        self.y / self.x
+    }
+}
+
+impl IFD {
+
+    // p38: ResolutionUnit defaults to inches.  A missing YResolution is taken
+    // to match XResolution.  Zero or zero-denominator values are treated as
+    // absent.
+    pub fn resolution(&self) -> Option<Resolution> {
+
+        let valid = |v: f64| if v.is_finite() && v > 0.0 { Some(v) } else { None };
+
+        let x = self.get_f64(TIFFTag::XResolutionTag).and_then(valid)?;
+        let y = self.get_f64(TIFFTag::YResolutionTag).and_then(valid).unwrap_or(x);
+        let unit = self.get_u32(TIFFTag::ResolutionUnitTag)
+            .and_then(|u| decode_resolution_unit(u as u16))
+            .unwrap_or(ResolutionUnit::Inch);
+
+        Some(Resolution { x, y, unit })
+    }
+
+    // Printed width and height of the image in the given unit (Inch or
+    // Centimetre), or None if the size cannot be determined.
+    pub fn physical_size(&self, unit: ResolutionUnit) -> Option<(f64, f64)> {
+
+        let width = self.get_u32(TIFFTag::ImageWidthTag)? as f64;
+        let height = self.get_u32(TIFFTag::ImageLengthTag)? as f64;
+        let (x, y) = match unit {
+            ResolutionUnit::Inch => self.resolution()?.dpi()?,
+            ResolutionUnit::Centimetre => self.resolution()?.dots_per_cm()?,
+            ResolutionUnit::None => return None,
+        };
+
+        Some((width / x, height / y))
+    }
+}
@@ -11,14 +11,1184 @@
 //
 //============================================================================
 
-#[license = "MIT"];
+use std::io::{Result, Error, ErrorKind, SeekFrom, Cursor};
+use std::path::Path;
+use std::fs::File;
 
-use tiff;
+use byteorder::{WriteBytesExt, ByteOrder, BigEndian, LittleEndian};
 
-struct TIFFWriter;
+use {IFDEntry, TIFFByteOrder, TIFFVersion, TIFFTag, TagType, ImageOrientation, SampleFormat, ResolutionUnit, Compression, Predictor,
+     SeekableWriter, size_of_tag_type, PhotometricInterpretation, SUBFILE_PAGE, SUBFILE_REDUCED_RESOLUTION, BYTE, SHORT, LONG, LONG8, RATIONAL, SRATIONAL, DOUBLE};
+use image::{Image, ImageData, Resampling};
+use resolution::Resolution;
+use compression::{compress, apply_predictor};
+
+//----------------------------------------------------------------------------
+// Constants
+
+const TARGET_STRIP_BYTES: usize = 8192;
+
+//----------------------------------------------------------------------------
+// Types
+
+// Whether to write a classic TIFF, or a BigTIFF with 64-bit offsets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BigTIFFMode {
+    Never,
+    Always,
+    // BigTIFF only if the estimated file size exceeds 4GB.  Multi-page files
+    // are estimated from their first page; a later page that needs offsets
+    // beyond 4GB in a classic file fails to append.
+    Auto,
+}
+
+pub struct TIFFWriter {
+    pub byte_order:  TIFFByteOrder,
+    pub bigtiff:     BigTIFFMode,
+    pub resolution:  Resolution,
+    pub compression: Compression,
+    pub predictor:   Predictor,
+}
+
+// A tiled image followed by reduced resolution overviews, laid out as a
+// Cloud Optimized GeoTIFF: every IFD precedes the image data, which runs
+// from the smallest overview to the full resolution image.
+#[derive(Clone, Debug)]
+pub struct PyramidOptions {
+    pub tile_width:  u32,
+    pub tile_length: u32,
+    // Reduction of each overview relative to the full image, in increasing
+    // order.  If empty, the image is halved until it fits in one tile.
+    pub factors:     Vec<u32>,
+    pub resampling:  Resampling,
+}
+
+// Writes an image a few rows at a time.  Tags may be set until the first
+// rows are pushed, when the IFD is written; the strip offsets and byte
+// counts in it are patched by `finish`.
+pub struct StripWriter<'a> {
+    writer: &'a mut dyn SeekableWriter,
+    settings: TIFFWriter,
+    version: TIFFVersion,
+    layout: Image,
+    rows_per_strip: u32,
+    entries: Vec<IFDEntry>,
+    // Where the StripOffsets and StripByteCounts values are, once written
+    positions: Option<(u64, u64)>,
+    // Encoded rows of the strip in progress
+    pending: Vec<u8>,
+    pending_rows: u32,
+    rows_written: u32,
+    offsets: Vec<u64>,
+    byte_counts: Vec<u64>,
+}
+
+// Writes a tiled image one tile at a time, in any order.  The image is
+// described by a layout whose data is unused.
+pub struct TileWriter<'a> {
+    writer: &'a mut dyn SeekableWriter,
+    settings: TIFFWriter,
+    version: TIFFVersion,
+    layout: Image,
+    tile_width: u32,
+    tile_length: u32,
+    // Offset and byte count of each tile written so far, in TileOffsets order
+    offsets: Vec<Option<(u64, u64)>>,
+}
+
+// Writes a multi-page file one page at a time, each page with the settings
+// of the TIFFWriter given to `append`.
+pub struct PageWriter<'a> {
+    writer: &'a mut dyn SeekableWriter,
+    byte_order: TIFFByteOrder,
+    // Unknown, and the header unwritten, until the first page with Auto
+    version: Option<TIFFVersion>,
+    // Where the offset of the next IFD is to be written
+    next_offset_position: u64,
+    // Position of the PageNumber total for each page so far
+    page_totals: Vec<u64>,
+}
+
+//----------------------------------------------------------------------------
+// Field construction
+//
+// Entries are built in little-endian order and converted to the output byte
+// order when written.
+
+impl IFDEntry {
+
+    pub fn new(tag: SHORT, typ: TagType, count: LONG8, data: Vec<BYTE>) -> IFDEntry {
+        IFDEntry {
+            tag,
+            typ,
+            count,
+            value_offset: 0,
+            byte_order: TIFFByteOrder::LittleEndian,
+            data,
+        }
+    }
+
+    pub fn bytes(tag: TIFFTag, typ: TagType, values: &[BYTE]) -> IFDEntry {
+        IFDEntry::new(tag as u16, typ, values.len() as u64, values.to_vec())
+    }
+
+    pub fn ascii(tag: TIFFTag, value: &str) -> IFDEntry {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        IFDEntry::new(tag as u16, TagType::ASCIITag, data.len() as u64, data)
+    }
+
+    pub fn shorts(tag: TIFFTag, values: &[SHORT]) -> IFDEntry {
+        let mut data = vec![0; values.len() * 2];
+        for (c, &v) in data.chunks_mut(2).zip(values) {
+            LittleEndian::write_u16(c, v);
+        }
+        IFDEntry::new(tag as u16, TagType::ShortTag, values.len() as u64, data)
+    }
+
+    pub fn longs(tag: TIFFTag, values: &[LONG]) -> IFDEntry {
+        let mut data = vec![0; values.len() * 4];
+        for (c, &v) in data.chunks_mut(4).zip(values) {
+            LittleEndian::write_u32(c, v);
+        }
+        IFDEntry::new(tag as u16, TagType::LongTag, values.len() as u64, data)
+    }
+
+    pub fn long8s(tag: TIFFTag, values: &[LONG8]) -> IFDEntry {
+        let mut data = vec![0; values.len() * 8];
+        for (c, &v) in data.chunks_mut(8).zip(values) {
+            LittleEndian::write_u64(c, v);
+        }
+        IFDEntry::new(tag as u16, TagType::Long8, values.len() as u64, data)
+    }
+
+    pub fn rationals(tag: TIFFTag, values: &[RATIONAL]) -> IFDEntry {
+        let mut data = vec![0; values.len() * 8];
+        for (c, &(n, d)) in data.chunks_mut(8).zip(values) {
+            LittleEndian::write_u32(&mut c[0..4], n);
+            LittleEndian::write_u32(&mut c[4..8], d);
+        }
+        IFDEntry::new(tag as u16, TagType::RationalTag, values.len() as u64, data)
+    }
+
+    pub fn srationals(tag: TIFFTag, values: &[SRATIONAL]) -> IFDEntry {
+        let mut data = vec![0; values.len() * 8];
+        for (c, &(n, d)) in data.chunks_mut(8).zip(values) {
+            LittleEndian::write_i32(&mut c[0..4], n);
+            LittleEndian::write_i32(&mut c[4..8], d);
+        }
+        IFDEntry::new(tag as u16, TagType::SignedRationalTag, values.len() as u64, data)
+    }
+
+    pub fn doubles(tag: TIFFTag, values: &[DOUBLE]) -> IFDEntry {
+        let mut data = vec![0; values.len() * 8];
+        for (c, &v) in data.chunks_mut(8).zip(values) {
+            LittleEndian::write_f64(c, v);
+        }
+        IFDEntry::new(tag as u16, TagType::DoubleTag, values.len() as u64, data)
+    }
+
+    // Copy of this entry with its data in the given byte order.  Each value
+    // is swapped according to its type; rationals swap each half.
+    pub fn with_byte_order(&self, byte_order: TIFFByteOrder) -> IFDEntry {
+        let mut entry = self.clone();
+        if byte_order != self.byte_order {
+            let size = match self.typ {
+                TagType::RationalTag | TagType::SignedRationalTag => 4,
+                typ => size_of_tag_type(typ),
+            };
+            if size > 1 {
+                for c in entry.data.chunks_mut(size) {
+                    c.reverse();
+                }
+            }
+            entry.byte_order = byte_order;
+        }
+        entry
+    }
+}
+
+//----------------------------------------------------------------------------
+
+impl Default for PyramidOptions {
+    fn default() -> PyramidOptions {
+        PyramidOptions {
+            tile_width: 256,
+            tile_length: 256,
+            factors: Vec::new(),
+            resampling: Resampling::Average,
+        }
+    }
+}
+
+impl PyramidOptions {
+
+    // The overview factors for an image, choosing powers of 2 if none are given.
+    pub fn factors_for(&self, width: u32, height: u32) -> Vec<u32> {
+        if !self.factors.is_empty() {
+            return self.factors.clone();
+        }
+        let mut factors = Vec::new();
+        let mut factor = 1u32;
+        while width.div_ceil(factor) > self.tile_width || height.div_ceil(factor) > self.tile_length {
+            factor *= 2;
+            factors.push(factor);
+        }
+        factors
+    }
+}
+
+impl Default for TIFFWriter {
+    fn default() -> TIFFWriter {
+        TIFFWriter::new(TIFFByteOrder::LittleEndian)
+    }
+}
 
 impl TIFFWriter {
 
-    fn save(&self, filename: &str) -> IoResult<u32> {
+    pub fn new(byte_order: TIFFByteOrder) -> TIFFWriter {
+        TIFFWriter {
+            byte_order,
+            bigtiff: BigTIFFMode::Auto,
+            resolution: Resolution { x: 72.0, y: 72.0, unit: ResolutionUnit::Inch },
+            compression: Compression::None,
+            predictor: Predictor::None,
+        }
+    }
+
+    pub fn save(&self, filename: &str, image: &Image) -> Result<()> {
+
+        let filepath = Path::new(filename);
+        let mut writer = File::create(filepath)?;
+
+        self.write(&mut writer, image)
+    }
+
+    pub fn write(&self, writer: &mut dyn SeekableWriter, image: &Image) -> Result<()> {
+        match self.byte_order {
+            TIFFByteOrder::LittleEndian => self.write_::<LittleEndian>(writer, image),
+            TIFFByteOrder::BigEndian => self.write_::<BigEndian>(writer, image),
+        }
+    }
+
+    // Start a multi-page file, to which pages are added with `append`.
+    pub fn pages<'a>(&self, writer: &'a mut dyn SeekableWriter) -> Result<PageWriter<'a>> {
+        let version = match self.bigtiff {
+            BigTIFFMode::Never => Some(TIFFVersion::Classic),
+            BigTIFFMode::Always => Some(TIFFVersion::BigTIFF),
+            BigTIFFMode::Auto => None,
+        };
+        if let Some(version) = version {
+            write_header(writer, self.byte_order, version)?;
+        }
+        Ok(PageWriter {
+            writer,
+            byte_order: self.byte_order,
+            version,
+            next_offset_position: version.map_or(0, first_ifd_position),
+            page_totals: Vec::new(),
+        })
+    }
+
+    // Start an image whose rows are added with `push_rows`.  The layout
+    // gives the dimensions and sample type; its data is unused.
+    pub fn strips<'a>(&self, writer: &'a mut dyn SeekableWriter, layout: &Image) -> Result<StripWriter<'a>> {
+
+        self.check_image(layout)?;
+
+        let version = self.version_for(layout, layout.width, layout.height);
+        write_header(writer, self.byte_order, version)?;
+
+        let rows_per_strip = rows_per_strip(layout);
+        let strips = layout.height.div_ceil(rows_per_strip) as usize;
+
+        // Placeholders for the strip offsets and byte counts
+        let mut entries = self.image_entries(layout);
+        entries.push(offsets_entry(TIFFTag::StripOffsetsTag, &vec![0; strips], version)?);
+        entries.push(IFDEntry::longs(TIFFTag::RowsPerStripTag, &[rows_per_strip]));
+        entries.push(offsets_entry(TIFFTag::StripByteCountsTag, &vec![0; strips], version)?);
+
+        Ok(StripWriter {
+            writer,
+            settings: TIFFWriter { ..*self },
+            version,
+            layout: Image { data: layout.data.empty(), extra_samples: layout.extra_samples.clone(),
+                            icc_profile: layout.icc_profile.clone(), ..*layout },
+            rows_per_strip,
+            entries,
+            positions: None,
+            pending: Vec::new(),
+            pending_rows: 0,
+            rows_written: 0,
+            offsets: Vec::with_capacity(strips),
+            byte_counts: Vec::with_capacity(strips),
+        })
+    }
+
+    // Start a tiled image, whose tiles are added with `write_tile`.  The
+    // layout gives the dimensions and sample type; its data is unused.
+    pub fn tiles<'a>(&self, writer: &'a mut dyn SeekableWriter, layout: &Image,
+                     tile_width: u32, tile_length: u32) -> Result<TileWriter<'a>> {
+
+        check_tile_size(tile_width, tile_length)?;
+        self.check_image(layout)?;
+
+        let across = layout.width.div_ceil(tile_width);
+        let down = layout.height.div_ceil(tile_length);
+
+        let version = self.version_for(layout, across * tile_width, down * tile_length);
+        write_header(writer, self.byte_order, version)?;
+
+        Ok(TileWriter {
+            writer,
+            settings: TIFFWriter { ..*self },
+            version,
+            layout: Image { data: layout.data.empty(), extra_samples: layout.extra_samples.clone(),
+                            icc_profile: layout.icc_profile.clone(), ..*layout },
+            tile_width,
+            tile_length,
+            offsets: vec![None; across as usize * down as usize],
+        })
+    }
+
+    // Write a tiled image followed by its overviews.
+    pub fn write_pyramid(&self, writer: &mut dyn SeekableWriter, image: &Image,
+                         options: &PyramidOptions) -> Result<()> {
+
+        check_tile_size(options.tile_width, options.tile_length)?;
+        self.check_image(image)?;
+        check_samples(image)?;
+
+        let factors = options.factors_for(image.width, image.height);
+        if factors.iter().any(|&f| f < 2) || factors.windows(2).any(|f| f[0] >= f[1]) {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("Overview factors {:?} must be increasing and above 1", factors)));
+        }
+        if options.resampling == Resampling::Average && image.photometric == PhotometricInterpretation::Palette {
+            return Err(Error::new(ErrorKind::InvalidInput, "Palette images cannot be averaged"));
+        }
+
+        let overviews: Vec<Image> = factors.iter().map(|&f| image.downsample(f, options.resampling)).collect();
+        let mut levels = vec![image];
+        levels.extend(overviews.iter());
+
+        match self.byte_order {
+            TIFFByteOrder::LittleEndian => self.write_pyramid_::<LittleEndian>(writer, &levels, options),
+            TIFFByteOrder::BigEndian => self.write_pyramid_::<BigEndian>(writer, &levels, options),
+        }
+    }
+
+    fn write_pyramid_<Endian: ByteOrder>(&self, writer: &mut dyn SeekableWriter, levels: &[&Image],
+                                         options: &PyramidOptions) -> Result<()> {
+
+        let (tile_width, tile_length) = (options.tile_width, options.tile_length);
+
+        // Encode every tile first, so the IFDs can be written ahead of them
+        let mut tiles = Vec::with_capacity(levels.len());
+        let mut estimated_size = 0;
+        for level in levels {
+            let across = level.width.div_ceil(tile_width);
+            let down = level.height.div_ceil(tile_length);
+            let mut level_tiles = Vec::with_capacity((across * down) as usize);
+            for row in 0..down {
+                for column in 0..across {
+                    let tile = level.crop(column * tile_width, row * tile_length, tile_width, tile_length);
+                    level_tiles.push(self.encode_tile::<Endian>(level, (tile_width, tile_length),
+                                                                column, row, &tile.data)?);
+                }
+            }
+            tiles.push(level_tiles);
+            estimated_size += self.estimated_size(level, across * tile_width, down * tile_length);
+        }
+        let version = self.version_for_size(estimated_size);
+
+        // The IFDs are the same size whatever the offsets they hold
+        let size = self.pyramid_ifds::<Endian>(version, levels, &tiles, options, 0)?.len() as u64;
+        let ifds = self.pyramid_ifds::<Endian>(version, levels, &tiles, options, size)?;
+
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(&ifds)?;
+        for tile in tiles.iter().rev().flat_map(|level| level.iter()) {
+            writer.write_all(tile)?;
+        }
+
+        Ok(())
+    }
+
+    // The header and IFDs of a pyramid whose tile data starts at `data_start`.
+    fn pyramid_ifds<Endian: ByteOrder>(&self, version: TIFFVersion, levels: &[&Image], tiles: &[Vec<Vec<u8>>],
+                                       options: &PyramidOptions, data_start: u64) -> Result<Vec<u8>> {
+
+        // Tile data runs from the smallest overview to the full image
+        let mut offsets = vec![Vec::new(); levels.len()];
+        let mut position = data_start;
+        for (level, level_tiles) in tiles.iter().enumerate().rev() {
+            for tile in level_tiles {
+                offsets[level].push(position);
+                position += tile.len() as u64;
+            }
+        }
+
+        let mut buffer = Cursor::new(Vec::new());
+        write_header(&mut buffer, self.byte_order, version)?;
+
+        let mut link_position = first_ifd_position(version);
+        for (level, image) in levels.iter().enumerate() {
+            let byte_counts: Vec<u64> = tiles[level].iter().map(|t| t.len() as u64).collect();
+
+            let mut entries = self.image_entries(image);
+            if level > 0 {
+                entries.push(IFDEntry::longs(TIFFTag::NewSubfileTypeTag, &[SUBFILE_REDUCED_RESOLUTION]));
+            }
+            entries.push(IFDEntry::longs(TIFFTag::TileWidthTag, &[options.tile_width]));
+            entries.push(IFDEntry::longs(TIFFTag::TileLengthTag, &[options.tile_length]));
+            entries.push(offsets_entry(TIFFTag::TileOffsetsTag, &offsets[level], version)?);
+            entries.push(offsets_entry(TIFFTag::TileByteCountsTag, &byte_counts, version)?);
+
+            let ifd = write_ifd::<Endian>(&mut buffer, self.byte_order, version, &entries)?;
+            write_offset::<Endian>(&mut buffer, link_position, ifd.offset, version)?;
+            link_position = ifd.next_offset_position;
+        }
+
+        Ok(buffer.into_inner())
+    }
+
+    // The version written for image data of the given dimensions.
+    pub fn version_for(&self, image: &Image, width: u32, height: u32) -> TIFFVersion {
+        self.version_for_size(self.estimated_size(image, width, height))
+    }
+
+    fn version_for_size(&self, estimated_size: u64) -> TIFFVersion {
+        let bigtiff = match self.bigtiff {
+            BigTIFFMode::Never => false,
+            BigTIFFMode::Always => true,
+            BigTIFFMode::Auto => estimated_size > LONG::MAX as u64,
+        };
+        if bigtiff { TIFFVersion::BigTIFF } else { TIFFVersion::Classic }
+    }
+
+    // An upper estimate of the size of a file holding image data of the
+    // given dimensions, allowing for compression expanding the data.
+    pub fn estimated_size(&self, image: &Image, width: u32, height: u32) -> u64 {
+        let row_bytes = (width as u64 * image.samples_per_pixel as u64 * image.bits_per_sample as u64).div_ceil(8);
+        let size = row_bytes * height as u64;
+        let compressed = match self.compression {
+            Compression::None => size,
+            // A literal run header for each 128 bytes
+            Compression::PackBits => size + size.div_ceil(128),
+            // Codes of at most 12 bits per byte
+            Compression::LZW => size + size.div_ceil(2),
+            // Stored blocks of at most 64K, and the zlib header
+            _ => size + size / 1024 + 64,
+        };
+        // Strip or tile offsets and byte counts, the IFD and tag values
+        compressed + compressed / 512 + 65536
+    }
+
+    fn write_<Endian: ByteOrder>(&self, writer: &mut dyn SeekableWriter, image: &Image) -> Result<()> {
+
+        let version = self.version_for(image, image.width, image.height);
+        write_header(writer, self.byte_order, version)?;
+
+        let entries = self.write_strips::<Endian>(writer, version, image)?;
+        let ifd = write_ifd::<Endian>(writer, self.byte_order, version, &entries)?;
+        write_offset::<Endian>(writer, first_ifd_position(version), ifd.offset, version)?;
+
+        Ok(())
+    }
+
+    // Write the image data as strips, returning the fields describing it.
+    fn write_strips<Endian: ByteOrder>(&self, writer: &mut dyn SeekableWriter, version: TIFFVersion,
+                                       image: &Image) -> Result<Vec<IFDEntry>> {
+
+        self.check_image(image)?;
+        check_samples(image)?;
+
+        let spp = image.samples_per_pixel as usize;
+        let bits = image.bits_per_sample;
+        let samples_per_row = image.width as usize * spp;
+        let row_bytes = (samples_per_row * bits as usize).div_ceil(8);
+        let rows_per_strip = rows_per_strip(image) as usize;
+
+        let bytes = encode_samples::<Endian>(&image.data);
+        let bytes = if bits < 8 { pack_samples(&bytes, samples_per_row, bits) } else { bytes };
+
+        let mut offsets = Vec::new();
+        let mut byte_counts = Vec::new();
+        for strip in bytes.chunks(rows_per_strip * row_bytes) {
+            let mut strip = strip.to_vec();
+            apply_predictor(self.predictor, &mut strip, samples_per_row, spp, bits, self.byte_order)?;
+            let strip = compress(self.compression, &strip)?;
+            offsets.push(writer.stream_position()?);
+            byte_counts.push(strip.len() as u64);
+            writer.write_all(&strip)?;
+        }
+
+        let mut entries = self.image_entries(image);
+        entries.push(offsets_entry(TIFFTag::StripOffsetsTag, &offsets, version)?);
+        entries.push(IFDEntry::longs(TIFFTag::RowsPerStripTag, &[rows_per_strip as u32]));
+        entries.push(offsets_entry(TIFFTag::StripByteCountsTag, &byte_counts, version)?);
+
+        Ok(entries)
+    }
+
+    // The fields describing an image, other than where its data is stored.
+    fn image_entries(&self, image: &Image) -> Vec<IFDEntry> {
+
+        let spp = image.samples_per_pixel as usize;
+
+        let mut entries = vec![
+            IFDEntry::longs(TIFFTag::ImageWidthTag, &[image.width]),
+            IFDEntry::longs(TIFFTag::ImageLengthTag, &[image.height]),
+            IFDEntry::shorts(TIFFTag::BitsPerSampleTag, &vec![image.bits_per_sample; spp]),
+            IFDEntry::shorts(TIFFTag::CompressionTag, &[self.compression as u16]),
+            IFDEntry::shorts(TIFFTag::PhotometricInterpretationTag, &[image.photometric as u16]),
+            IFDEntry::shorts(TIFFTag::SamplesPerPixelTag, &[spp as u16]),
+            IFDEntry::rationals(TIFFTag::XResolutionTag, &[to_rational(self.resolution.x)]),
+            IFDEntry::rationals(TIFFTag::YResolutionTag, &[to_rational(self.resolution.y)]),
+            IFDEntry::shorts(TIFFTag::ResolutionUnitTag, &[self.resolution.unit as u16]),
+            IFDEntry::shorts(TIFFTag::PlanarConfigurationTag, &[1]),
+        ];
+
+        if self.predictor != Predictor::None {
+            entries.push(IFDEntry::shorts(TIFFTag::PredictorTag, &[self.predictor as u16]));
+        }
+        if image.orientation != ImageOrientation::TopLeft {
+            entries.push(IFDEntry::shorts(TIFFTag::OrientationTag, &[image.orientation as u16]));
+        }
+        if !image.extra_samples.is_empty() {
+            let extra: Vec<u16> = image.extra_samples.iter().map(|&e| e as u16).collect();
+            entries.push(IFDEntry::shorts(TIFFTag::ExtraSamplesTag, &extra));
+        }
+        if image.sample_format != SampleFormat::UnsignedInteger {
+            entries.push(IFDEntry::shorts(TIFFTag::SampleFormatTag, &vec![image.sample_format as u16; spp]));
+        }
+        if let Some(ref profile) = image.icc_profile {
+            entries.push(IFDEntry::bytes(TIFFTag::InterColorProfileTag, TagType::UndefinedTag, profile));
+        }
+
+        entries
+    }
+
+    // Encode the tile at the given column and row, padding a partial tile.
+    fn encode_tile<Endian: ByteOrder>(&self, layout: &Image, tile_size: (u32, u32),
+                                      column: u32, row: u32, data: &ImageData) -> Result<Vec<u8>> {
+
+        if data.bits_per_sample() != layout.data.bits_per_sample()
+            || data.sample_format() != layout.data.sample_format() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Tile samples differ from the image layout"));
+        }
+
+        let spp = layout.samples_per_pixel as usize;
+        let bits = layout.bits_per_sample;
+        let (tile_width, tile_length) = (tile_size.0 as usize, tile_size.1 as usize);
+        let width = tile_width.min((layout.width - column * tile_size.0) as usize);
+        let length = tile_length.min((layout.height - row * tile_size.1) as usize);
+
+        let bytes = encode_samples::<Endian>(data);
+        let sample_bytes = bytes.len() / data.len().max(1);
+        let tile_row_bytes = tile_width * spp * sample_bytes;
+
+        let bytes = if data.len() == tile_width * tile_length * spp {
+            bytes
+        } else if data.len() == width * length * spp {
+            let mut padded = vec![0u8; tile_row_bytes * tile_length];
+            for (r, source) in bytes.chunks(width * spp * sample_bytes).enumerate() {
+                padded[r * tile_row_bytes..r * tile_row_bytes + source.len()].copy_from_slice(source);
+            }
+            padded
+        } else {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("Expected {} or {} samples for tile, found {}",
+                                          tile_width * tile_length * spp, width * length * spp, data.len())));
+        };
+
+        let mut bytes = if bits < 8 { pack_samples(&bytes, tile_width * spp, bits) } else { bytes };
+        apply_predictor(self.predictor, &mut bytes, tile_width * spp, spp, bits, self.byte_order)?;
+        compress(self.compression, &bytes)
+    }
+
+    // Check the image's samples can be written with these settings.
+    fn check_image(&self, image: &Image) -> Result<()> {
+
+        // Samples of fewer than 8 bits are given one per byte
+        let bits = image.bits_per_sample;
+        let data_bits = if bits < 8 && bits > 0 { 8 } else { bits };
+        if image.width == 0 || image.height == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("Image of {}x{} pixels is empty", image.width, image.height)));
+        }
+        if image.samples_per_pixel == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "Image has no samples per pixel"));
+        }
+        if data_bits != image.data.bits_per_sample() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("BitsPerSample {} does not match sample data", bits)));
+        }
+
+        self.check_predictor(image)
+    }
+
+    // The horizontal predictor suits integer samples, and the floating point
+    // predictor IEEE floating point samples; both need whole byte samples.
+    fn check_predictor(&self, image: &Image) -> Result<()> {
+        let valid = match self.predictor {
+            Predictor::None => true,
+            Predictor::Horizontal =>
+                image.bits_per_sample >= 8 && image.sample_format != SampleFormat::IEEEFloatingPoint,
+            Predictor::FloatingPoint =>
+                image.sample_format == SampleFormat::IEEEFloatingPoint,
+        };
+        if !valid {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("{:?} predictor cannot be used with {} bit {:?} samples",
+                                          self.predictor, image.bits_per_sample, image.sample_format)));
+        }
+        Ok(())
+    }
+}
+
+//----------------------------------------------------------------------------
+
+impl<'a> StripWriter<'a> {
+
+    pub fn rows_per_strip(&self) -> u32 {
+        self.rows_per_strip
+    }
+
+    // Add a field, replacing any with the same tag.  Fields describing the
+    // image layout and strips are managed by the writer.
+    pub fn set_tag(&mut self, entry: IFDEntry) -> Result<()> {
+        if self.positions.is_some() {
+            return Err(Error::other("Tags cannot be set once rows have been written"));
+        }
+        let managed = [TIFFTag::ImageWidthTag, TIFFTag::ImageLengthTag, TIFFTag::BitsPerSampleTag,
+                       TIFFTag::CompressionTag, TIFFTag::SamplesPerPixelTag, TIFFTag::StripOffsetsTag,
+                       TIFFTag::RowsPerStripTag, TIFFTag::StripByteCountsTag, TIFFTag::PlanarConfigurationTag,
+                       TIFFTag::PredictorTag, TIFFTag::SampleFormatTag];
+        if managed.iter().any(|&t| t as u16 == entry.tag) {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("Tag {:#06x} is set by the writer", entry.tag)));
+        }
+        self.entries.retain(|e| e.tag != entry.tag);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    // Add one or more whole rows of samples.
+    pub fn push_rows(&mut self, data: &ImageData) -> Result<()> {
+        match self.settings.byte_order {
+            TIFFByteOrder::LittleEndian => self.push_rows_::<LittleEndian>(data),
+            TIFFByteOrder::BigEndian => self.push_rows_::<BigEndian>(data),
+        }
+    }
+
+    // Write the last strip and fill in the strip offsets and byte counts.
+    pub fn finish(mut self) -> Result<()> {
+        match self.settings.byte_order {
+            TIFFByteOrder::LittleEndian => self.finish_::<LittleEndian>(),
+            TIFFByteOrder::BigEndian => self.finish_::<BigEndian>(),
+        }
+    }
+
+    fn push_rows_<Endian: ByteOrder>(&mut self, data: &ImageData) -> Result<()> {
+
+        if data.bits_per_sample() != self.layout.data.bits_per_sample()
+            || data.sample_format() != self.layout.data.sample_format() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Row samples differ from the image layout"));
+        }
+        let samples_per_row = self.layout.width as usize * self.layout.samples_per_pixel as usize;
+        if samples_per_row == 0 || !data.len().is_multiple_of(samples_per_row) {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("{} samples is not a whole number of rows", data.len())));
+        }
+        let rows = (data.len() / samples_per_row) as u32;
+        if self.rows_written + self.pending_rows + rows > self.layout.height {
+            return Err(Error::new(ErrorKind::InvalidInput, "More rows than the image height"));
+        }
+        if rows == 0 {
+            return Ok(());
+        }
+
+        self.write_ifd_::<Endian>()?;
+
+        let bits = self.layout.bits_per_sample;
+        let bytes = encode_samples::<Endian>(data);
+        let bytes = if bits < 8 { pack_samples(&bytes, samples_per_row, bits) } else { bytes };
+        let row_bytes = bytes.len() / rows as usize;
+
+        for row in bytes.chunks(row_bytes) {
+            self.pending.extend_from_slice(row);
+            self.pending_rows += 1;
+            if self.pending_rows == self.rows_per_strip {
+                self.write_strip()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Write the IFD ahead of the first strip.
+    fn write_ifd_<Endian: ByteOrder>(&mut self) -> Result<()> {
+        if self.positions.is_none() {
+            let ifd = write_ifd::<Endian>(self.writer, self.settings.byte_order, self.version, &self.entries)?;
+            write_offset::<Endian>(self.writer, first_ifd_position(self.version), ifd.offset, self.version)?;
+            let offsets = ifd.value_position(TIFFTag::StripOffsetsTag);
+            let byte_counts = ifd.value_position(TIFFTag::StripByteCountsTag);
+            self.positions = offsets.and_then(|o| byte_counts.map(|b| (o, b)));
+        }
+        Ok(())
+    }
+
+    fn write_strip(&mut self) -> Result<()> {
+        let spp = self.layout.samples_per_pixel as usize;
+        apply_predictor(self.settings.predictor, &mut self.pending, self.layout.width as usize * spp, spp,
+                        self.layout.bits_per_sample, self.settings.byte_order)?;
+        let strip = compress(self.settings.compression, &self.pending)?;
+
+        let offset = self.writer.seek(SeekFrom::End(0))?;
+        self.writer.write_all(&strip)?;
+        self.offsets.push(offset);
+        self.byte_counts.push(strip.len() as u64);
+
+        self.rows_written += self.pending_rows;
+        self.pending_rows = 0;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn finish_<Endian: ByteOrder>(&mut self) -> Result<()> {
+
+        self.write_ifd_::<Endian>()?;
+        if self.pending_rows > 0 {
+            self.write_strip()?;
+        }
+        if self.rows_written != self.layout.height {
+            return Err(Error::other(format!("Only {} of {} rows were written",
+                                            self.rows_written, self.layout.height)));
+        }
+
+        let (offsets_position, byte_counts_position) = self.positions
+            .ok_or_else(|| Error::other("Strip fields were not written"))?;
+        let fields = [(offsets_position, &self.offsets), (byte_counts_position, &self.byte_counts)];
+        for &(position, values) in &fields {
+            self.writer.seek(SeekFrom::Start(position))?;
+            for &value in values.iter() {
+                match self.version {
+                    TIFFVersion::Classic => self.writer.write_u32::<Endian>(to_long(value)?)?,
+                    TIFFVersion::BigTIFF => self.writer.write_u64::<Endian>(value)?,
+                }
+            }
+        }
+        self.writer.seek(SeekFrom::End(0))?;
+
+        Ok(())
+    }
+}
+
+//----------------------------------------------------------------------------
+
+impl<'a> TileWriter<'a> {
+
+    // Number of tiles across and down the image.
+    pub fn tiles_across(&self) -> u32 {
+        self.layout.width.div_ceil(self.tile_width)
+    }
+
+    pub fn tiles_down(&self) -> u32 {
+        self.layout.height.div_ceil(self.tile_length)
+    }
+
+    // Write the tile at the given column and row of tiles.  The data holds
+    // either a whole tile, or only the part within the image, in which case
+    // the rest of the tile is padded with zeros.
+    pub fn write_tile(&mut self, column: u32, row: u32, data: &ImageData) -> Result<()> {
+        match self.settings.byte_order {
+            TIFFByteOrder::LittleEndian => self.write_tile_::<LittleEndian>(column, row, data),
+            TIFFByteOrder::BigEndian => self.write_tile_::<BigEndian>(column, row, data),
+        }
+    }
+
+    // Write the IFD once every tile has been written.
+    pub fn finish(self) -> Result<()> {
+        match self.settings.byte_order {
+            TIFFByteOrder::LittleEndian => self.finish_::<LittleEndian>(),
+            TIFFByteOrder::BigEndian => self.finish_::<BigEndian>(),
+        }
+    }
+
+    fn write_tile_<Endian: ByteOrder>(&mut self, column: u32, row: u32, data: &ImageData) -> Result<()> {
+
+        if column >= self.tiles_across() || row >= self.tiles_down() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("Tile ({}, {}) is outside the image", column, row)));
+        }
+        let bytes = self.settings.encode_tile::<Endian>(&self.layout, (self.tile_width, self.tile_length),
+                                                        column, row, data)?;
+
+        let offset = self.writer.seek(SeekFrom::End(0))?;
+        self.writer.write_all(&bytes)?;
+
+        let index = (row * self.tiles_across() + column) as usize;
+        self.offsets[index] = Some((offset, bytes.len() as u64));
+
+        Ok(())
+    }
+
+    fn finish_<Endian: ByteOrder>(self) -> Result<()> {
+
+        let mut offsets = Vec::with_capacity(self.offsets.len());
+        let mut byte_counts = Vec::with_capacity(self.offsets.len());
+        for (index, tile) in self.offsets.iter().enumerate() {
+            let (offset, byte_count) = tile.ok_or_else(|| {
+                Error::other(format!("Tile {} has not been written", index))
+            })?;
+            offsets.push(offset);
+            byte_counts.push(byte_count);
+        }
+
+        let mut entries = self.settings.image_entries(&self.layout);
+        entries.push(IFDEntry::longs(TIFFTag::TileWidthTag, &[self.tile_width]));
+        entries.push(IFDEntry::longs(TIFFTag::TileLengthTag, &[self.tile_length]));
+        entries.push(offsets_entry(TIFFTag::TileOffsetsTag, &offsets, self.version)?);
+        entries.push(offsets_entry(TIFFTag::TileByteCountsTag, &byte_counts, self.version)?);
+
+        let ifd = write_ifd::<Endian>(self.writer, self.settings.byte_order, self.version, &entries)?;
+        write_offset::<Endian>(self.writer, first_ifd_position(self.version), ifd.offset, self.version)?;
+
+        Ok(())
+    }
+}
+
+//----------------------------------------------------------------------------
+
+impl<'a> PageWriter<'a> {
+
+    // Append a page, written with the compression, predictor and resolution
+    // of `settings`.  Pages must all share the file's byte order.
+    pub fn append(&mut self, settings: &TIFFWriter, image: &Image) -> Result<()> {
+        if settings.byte_order != self.byte_order {
+            return Err(Error::new(ErrorKind::InvalidInput, "Page byte order differs from the file"));
+        }
+        match self.byte_order {
+            TIFFByteOrder::LittleEndian => self.append_::<LittleEndian>(settings, image),
+            TIFFByteOrder::BigEndian => self.append_::<BigEndian>(settings, image),
+        }
+    }
+
+    // Fill in the page count of every page.  Until this is called each
+    // PageNumber total is 0, meaning unknown.  A file needs at least one page.
+    pub fn finish(self) -> Result<()> {
+        if self.page_totals.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "No pages were appended"));
+        }
+        let total = self.page_totals.len() as u16;
+        for &position in &self.page_totals {
+            self.writer.seek(SeekFrom::Start(position))?;
+            match self.byte_order {
+                TIFFByteOrder::LittleEndian => self.writer.write_u16::<LittleEndian>(total)?,
+                TIFFByteOrder::BigEndian => self.writer.write_u16::<BigEndian>(total)?,
+            }
+        }
+        self.writer.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+
+    fn append_<Endian: ByteOrder>(&mut self, settings: &TIFFWriter, image: &Image) -> Result<()> {
+
+        let page = self.page_totals.len();
+        if page >= SHORT::MAX as usize {
+            return Err(Error::other("Too many pages for PageNumber"));
+        }
+
+        let version = match self.version {
+            Some(version) => version,
+            None => {
+                let version = settings.version_for_size(settings.estimated_size(image, image.width, image.height));
+                write_header(self.writer, self.byte_order, version)?;
+                self.version = Some(version);
+                self.next_offset_position = first_ifd_position(version);
+                version
+            },
+        };
+
+        self.writer.seek(SeekFrom::End(0))?;
+        let mut entries = settings.write_strips::<Endian>(self.writer, version, image)?;
+        entries.push(IFDEntry::longs(TIFFTag::NewSubfileTypeTag, &[SUBFILE_PAGE]));
+        entries.push(IFDEntry::shorts(TIFFTag::PageNumberTag, &[page as u16, 0]));
+
+        let ifd = write_ifd::<Endian>(self.writer, self.byte_order, version, &entries)?;
+
+        // The total is the second SHORT of the value
+        let total_position = ifd.value_position(TIFFTag::PageNumberTag)
+            .ok_or_else(|| Error::other("PageNumber was not written"))?;
+        self.page_totals.push(total_position + 2);
+
+        write_offset::<Endian>(self.writer, self.next_offset_position, ifd.offset, version)?;
+        self.next_offset_position = ifd.next_offset_position;
+
+        Ok(())
+    }
+}
+
+//----------------------------------------------------------------------------
+// Helpers
+
+// Write the header, leaving the offset of the first IFD as 0.
+pub(crate) fn write_header(writer: &mut dyn SeekableWriter, byte_order: TIFFByteOrder, version: TIFFVersion) -> Result<()> {
+    match byte_order {
+        TIFFByteOrder::LittleEndian => write_header_::<LittleEndian>(writer, byte_order, version),
+        TIFFByteOrder::BigEndian => write_header_::<BigEndian>(writer, byte_order, version),
+    }
+}
+
+fn write_header_<Endian: ByteOrder>(writer: &mut dyn SeekableWriter, byte_order: TIFFByteOrder,
+                                    version: TIFFVersion) -> Result<()> {
+
+    // Bytes 0-1: "II" or "MM"
+    // Bytes 2-3: 42, or 43 for BigTIFF
+    // Bytes 4-7: offset to first IFD, filled in once known
+    //
+    // BigTIFF:
+    // Bytes 4-5: offset size, 8
+    // Bytes 6-7: 0
+    // Bytes 8-15: offset to first IFD
+
+    writer.seek(SeekFrom::Start(0))?;
+    writer.write_u16::<LittleEndian>(byte_order as u16)?;
+    writer.write_u16::<Endian>(version as u16)?;
+    match version {
+        TIFFVersion::Classic => writer.write_u32::<Endian>(0)?,
+        TIFFVersion::BigTIFF => {
+            writer.write_u16::<Endian>(8)?;
+            writer.write_u16::<Endian>(0)?;
+            writer.write_u64::<Endian>(0)?;
+        },
+    }
+
+    Ok(())
+}
+
+// p39: Strips of about 8K are recommended
+fn rows_per_strip(image: &Image) -> u32 {
+    let row_bytes = (image.width as usize * image.samples_per_pixel as usize * image.bits_per_sample as usize)
+        .div_ceil(8);
+    (TARGET_STRIP_BYTES / row_bytes.max(1)).clamp(1, image.height.max(1) as usize) as u32
+}
+
+fn check_samples(image: &Image) -> Result<()> {
+    let expected = image.width as usize * image.height as usize * image.samples_per_pixel as usize;
+    if image.data.len() != expected {
+        return Err(Error::new(ErrorKind::InvalidInput,
+                              format!("Expected {} samples, found {}", expected, image.data.len())));
+    }
+    Ok(())
+}
+
+// p67: tile dimensions must be a multiple of 16
+fn check_tile_size(tile_width: u32, tile_length: u32) -> Result<()> {
+    if tile_width == 0 || tile_length == 0
+        || !tile_width.is_multiple_of(16) || !tile_length.is_multiple_of(16) {
+        return Err(Error::new(ErrorKind::InvalidInput,
+                              format!("Tile size {}x{} is not a multiple of 16", tile_width, tile_length)));
+    }
+    Ok(())
+}
+
+// Position in the header of the offset to the first IFD.
+pub(crate) fn first_ifd_position(version: TIFFVersion) -> u64 {
+    match version {
+        TIFFVersion::Classic => 4,
+        TIFFVersion::BigTIFF => 8,
+    }
+}
+
+// Write an IFD offset at `position`, then return to the end of the file.
+pub(crate) fn write_offset<Endian: ByteOrder>(writer: &mut dyn SeekableWriter, position: u64, offset: u64,
+                                   version: TIFFVersion) -> Result<()> {
+    writer.seek(SeekFrom::Start(position))?;
+    match version {
+        TIFFVersion::Classic => writer.write_u32::<Endian>(to_long(offset)?)?,
+        TIFFVersion::BigTIFF => writer.write_u64::<Endian>(offset)?,
+    }
+    writer.seek(SeekFrom::End(0))?;
+    Ok(())
+}
+
+// Strip or tile offsets or byte counts, as LONG8 in BigTIFF.
+fn offsets_entry(tag: TIFFTag, values: &[u64], version: TIFFVersion) -> Result<IFDEntry> {
+    match version {
+        TIFFVersion::Classic => {
+            let values = values.iter().map(|&v| to_long(v)).collect::<Result<Vec<_>>>()?;
+            Ok(IFDEntry::longs(tag, &values))
+        },
+        TIFFVersion::BigTIFF => Ok(IFDEntry::long8s(tag, values)),
+    }
+}
+
+// Where an IFD and its values were written.
+pub(crate) struct WrittenIFD {
+    pub(crate) offset: u64,
+    // Where the offset of the next IFD was written
+    pub(crate) next_offset_position: u64,
+    // Each entry's value, whether within the entry or out of line
+    pub(crate) value_positions: Vec<(SHORT, u64)>,
+}
+
+impl WrittenIFD {
+    pub(crate) fn value_position(&self, tag: TIFFTag) -> Option<u64> {
+        self.value_positions.iter().find(|&&(t, _)| t == tag as u16).map(|&(_, p)| p)
+    }
+}
+
+// Write an IFD, preceded by any values too large to fit in their entry, at
+// the end of the file.
+pub(crate) fn write_ifd<Endian: ByteOrder>(writer: &mut dyn SeekableWriter, byte_order: TIFFByteOrder,
+                                           version: TIFFVersion, entries: &[IFDEntry]) -> Result<WrittenIFD> {
+
+    let mut entries = prepare_entries(entries, byte_order);
+
+    writer.seek(SeekFrom::End(0))?;
+    for e in entries.iter_mut() {
+        if e.data.len() > inline_size(version) {
+            align(writer)?;
+            e.value_offset = writer.stream_position()?;
+            writer.write_all(&e.data)?;
+        }
+    }
+
+    align(writer)?;
+    write_ifd_entries::<Endian>(writer, version, &entries, 0)
+}
+
+// p15: entries must be sorted in ascending order by tag
+pub(crate) fn prepare_entries(entries: &[IFDEntry], byte_order: TIFFByteOrder) -> Vec<IFDEntry> {
+    let mut entries: Vec<IFDEntry> = entries.iter().map(|e| e.with_byte_order(byte_order)).collect();
+    entries.sort_by_key(|e| e.tag);
+    entries
+}
+
+// Values of up to 4 bytes (8 for BigTIFF) are stored in the entry.
+pub(crate) fn inline_size(version: TIFFVersion) -> usize {
+    match version {
+        TIFFVersion::Classic => 4,
+        TIFFVersion::BigTIFF => 8,
+    }
+}
+
+// Write the entry count, entries and next IFD offset at the current
+// position.  Values not stored in their entry must already be written,
+// with `value_offset` giving their position.
+pub(crate) fn write_ifd_entries<Endian: ByteOrder>(writer: &mut dyn SeekableWriter, version: TIFFVersion,
+                                                   entries: &[IFDEntry], next_offset: u64) -> Result<WrittenIFD> {
+
+    let ifd_offset = writer.stream_position()?;
+
+    match version {
+        TIFFVersion::Classic => writer.write_u16::<Endian>(entries.len() as u16)?,
+        TIFFVersion::BigTIFF => writer.write_u64::<Endian>(entries.len() as u64)?,
+    }
+
+    let mut value_positions = Vec::with_capacity(entries.len());
+    for e in entries {
+        writer.write_u16::<Endian>(e.tag)?;
+        writer.write_u16::<Endian>(e.typ as u16)?;
+        match version {
+            TIFFVersion::Classic => writer.write_u32::<Endian>(to_long(e.count)?)?,
+            TIFFVersion::BigTIFF => writer.write_u64::<Endian>(e.count)?,
+        }
+        if e.data.len() > inline_size(version) {
+            value_positions.push((e.tag, e.value_offset));
+            match version {
+                TIFFVersion::Classic => writer.write_u32::<Endian>(to_long(e.value_offset)?)?,
+                TIFFVersion::BigTIFF => writer.write_u64::<Endian>(e.value_offset)?,
+            }
+        } else {
+            value_positions.push((e.tag, writer.stream_position()?));
+            let mut value = vec![0u8; inline_size(version)];
+            value[..e.data.len()].copy_from_slice(&e.data);
+            writer.write_all(&value)?;
+        }
+    }
+
+    let next_offset_position = writer.stream_position()?;
+    match version {
+        TIFFVersion::Classic => writer.write_u32::<Endian>(to_long(next_offset)?)?,
+        TIFFVersion::BigTIFF => writer.write_u64::<Endian>(next_offset)?,
+    }
+
+    Ok(WrittenIFD {
+        offset: ifd_offset,
+        next_offset_position,
+        value_positions,
+    })
+}
+
+// p15: values must begin on a word boundary
+pub(crate) fn align(writer: &mut dyn SeekableWriter) -> Result<()> {
+    if writer.stream_position()? % 2 != 0 {
+        writer.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+fn to_long(value: u64) -> Result<LONG> {
+    if value > LONG::MAX as u64 {
+        return Err(Error::other("Offset exceeds 4GB, which requires BigTIFF"));
+    }
+    Ok(value as LONG)
+}
+
+// Approximate a positive value as a rational with a power of 10 denominator.
+fn to_rational(value: f64) -> RATIONAL {
+    let mut denominator = 1u32;
+    while denominator < 10000 && (value * denominator as f64).fract() != 0.0
+        && value * (denominator as f64) * 10.0 < LONG::MAX as f64 {
+        denominator *= 10;
+    }
+    ((value * denominator as f64).round() as u32, denominator)
+}
+
+fn encode_samples<Endian: ByteOrder>(data: &ImageData) -> Vec<u8> {
+
+    fn encode<T: Copy>(values: &[T], size: usize, write: fn(&mut [u8], T)) -> Vec<u8> {
+        let mut bytes = vec![0; values.len() * size];
+        for (c, &v) in bytes.chunks_mut(size).zip(values) {
+            write(c, v);
+        }
+        bytes
+    }
+
+    match *data {
+        ImageData::U8(ref v) => v.clone(),
+        ImageData::I8(ref v) => v.iter().map(|&x| x as u8).collect(),
+        ImageData::U16(ref v) => encode(v, 2, Endian::write_u16),
+        ImageData::I16(ref v) => encode(v, 2, Endian::write_i16),
+        ImageData::U32(ref v) => encode(v, 4, Endian::write_u32),
+        ImageData::I32(ref v) => encode(v, 4, Endian::write_i32),
+        ImageData::U64(ref v) => encode(v, 8, Endian::write_u64),
+        ImageData::I64(ref v) => encode(v, 8, Endian::write_i64),
+        ImageData::F32(ref v) => encode(v, 4, Endian::write_f32),
+        ImageData::F64(ref v) => encode(v, 8, Endian::write_f64),
+    }
+}
+
+// Pack one byte per sample into rows of `bits` wide samples, each row
+// starting on a byte boundary.
+fn pack_samples(bytes: &[u8], samples_per_row: usize, bits: u16) -> Vec<u8> {
+    let row_bytes = (samples_per_row * bits as usize).div_ceil(8);
+    let mask = ((1u16 << bits) - 1) as u8;
+    let mut result = Vec::with_capacity(row_bytes * bytes.len() / samples_per_row.max(1));
+    for row in bytes.chunks(samples_per_row) {
+        // One spare byte, for a sample straddling the last two
+        let mut packed = vec![0u8; row_bytes + 1];
+        for (i, &v) in row.iter().enumerate() {
+            let bit = i * bits as usize;
+            let shifted = ((v & mask) as u16) << (16 - bits as usize - bit % 8);
+            packed[bit / 8] |= (shifted >> 8) as u8;
+            packed[bit / 8 + 1] |= shifted as u8;
+        }
+        packed.truncate(row_bytes);
+        result.extend_from_slice(&packed);
     }
+    result
 }
@@ -0,0 +1,289 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+// A small namespace-aware XML reader for the metadata formats embedded in
+// TIFF fields.  DTDs are skipped and only the predefined entities are known.
+
+use std::io::{Result, Error, ErrorKind};
+use std::collections::BTreeMap;
+
+//----------------------------------------------------------------------------
+// Constants
+
+// The namespace of the xml prefix, which is always declared
+pub const NS_XML: &str = "http://www.w3.org/XML/1998/namespace";
+
+// Deepest nesting of elements accepted, so that parsing cannot exhaust the
+// stack.
+const MAX_DEPTH: usize = 256;
+
+//----------------------------------------------------------------------------
+// Types
+
+// A name qualified by its namespace URI, which is empty for no namespace.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct XMLName {
+    pub namespace: String,
+    pub name:      String,
+}
+
+// An XML element, with its character data gathered into `text`.
+pub(crate) struct Element {
+    pub name:       XMLName,
+    pub attributes: Vec<(XMLName, String)>,
+    pub children:   Vec<Element>,
+    pub text:       String,
+}
+
+struct Parser<'a> {
+    xml:    &'a str,
+    pos:    usize,
+    // Namespaces declared by each open element, innermost last
+    scopes: Vec<Vec<(String, String)>>,
+    // Every namespace URI seen, with the prefix it was declared with
+    prefixes: BTreeMap<String, String>,
+}
+
+//----------------------------------------------------------------------------
+
+impl XMLName {
+
+    pub fn new(namespace: &str, name: &str) -> XMLName {
+        XMLName { namespace: namespace.to_string(), name: name.to_string() }
+    }
+
+    pub(crate) fn is(&self, namespace: &str, name: &str) -> bool {
+        self.namespace == namespace && self.name == name
+    }
+}
+
+// Parse a document, returning its root element and the prefix each
+// namespace was declared with.
+pub(crate) fn parse(xml: &str) -> Result<(Element, BTreeMap<String, String>)> {
+    let mut parser = Parser { xml, pos: 0, scopes: Vec::new(), prefixes: BTreeMap::new() };
+    let root = parser.parse_document()?;
+    Ok((root, parser.prefixes))
+}
+
+pub(crate) fn find_element<'a>(element: &'a Element, namespace: &str, name: &str) -> Option<&'a Element> {
+    if element.name.is(namespace, name) {
+        return Some(element);
+    }
+    element.children.iter().filter_map(|e| find_element(e, namespace, name)).next()
+}
+
+pub(crate) fn attribute<'a>(element: &'a Element, namespace: &str, name: &str) -> Option<&'a str> {
+    element.attributes.iter().find(|&(n, _)| n.is(namespace, name)).map(|(_, v)| v.as_str())
+}
+
+//----------------------------------------------------------------------------
+
+impl<'a> Parser<'a> {
+
+    fn rest(&self) -> &'a str {
+        &self.xml[self.pos..]
+    }
+
+    fn skip_to(&mut self, end: &str) -> Result<&'a str> {
+        let rest = self.rest();
+        let index = rest.find(end).ok_or_else(|| invalid(&format!("Missing {}", end)))?;
+        self.pos += index + end.len();
+        Ok(&rest[..index])
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    // Skip a processing instruction, comment or DOCTYPE, if one is next.
+    fn skip_markup(&mut self) -> Result<bool> {
+        if self.rest().starts_with("<?") {
+            self.skip_to("?>")?;
+        } else if self.rest().starts_with("<!--") {
+            self.skip_to("-->")?;
+        } else if self.rest().starts_with("<!") && !self.rest().starts_with("<![CDATA[") {
+            self.skip_to(">")?;
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn parse_document(&mut self) -> Result<Element> {
+        loop {
+            self.skip_whitespace();
+            if !self.skip_markup()? {
+                break;
+            }
+        }
+        if !self.rest().starts_with('<') {
+            return Err(invalid("Expected an element"));
+        }
+        self.parse_element()
+    }
+
+    fn parse_name(&mut self) -> Result<&'a str> {
+        let rest = self.rest();
+        let end = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=').unwrap_or(rest.len());
+        if end == 0 {
+            return Err(invalid("Expected a name"));
+        }
+        self.pos += end;
+        Ok(&rest[..end])
+    }
+
+    fn parse_element(&mut self) -> Result<Element> {
+
+        if self.scopes.len() >= MAX_DEPTH {
+            return Err(invalid(&format!("Elements nested more than {} deep", MAX_DEPTH)));
+        }
+
+        // Start tag, at '<'
+        self.pos += 1;
+        let tag = self.parse_name()?;
+        let mut raw_attributes = Vec::new();
+        let mut declarations = Vec::new();
+        let empty = loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                break true;
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                break false;
+            }
+            let name = self.parse_name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(invalid(&format!("Attribute {} has no value", name)));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(q) if q == '"' || q == '\'' => q,
+                _ => return Err(invalid(&format!("Attribute {} is not quoted", name))),
+            };
+            self.pos += 1;
+            let value = unescape(self.skip_to(&quote.to_string())?)?;
+
+            if name == "xmlns" {
+                declarations.push((String::new(), value));
+            } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+                declarations.push((prefix.to_string(), value));
+            } else {
+                raw_attributes.push((name, value));
+            }
+        };
+
+        for (prefix, namespace) in &declarations {
+            self.prefixes.entry(namespace.clone()).or_insert_with(|| prefix.clone());
+        }
+        self.scopes.push(declarations);
+
+        let name = self.resolve(tag, true)?;
+        let attributes = raw_attributes.into_iter()
+            .map(|(n, v)| Ok((self.resolve(n, false)?, v)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut element = Element { name, attributes, children: Vec::new(), text: String::new() };
+
+        // Content, up to the matching end tag
+        if !empty {
+            loop {
+                let text = self.rest().split('<').next().unwrap_or("");
+                self.pos += text.len();
+                element.text.push_str(&unescape(text)?);
+
+                if self.rest().is_empty() {
+                    return Err(invalid(&format!("Element {} is not closed", tag)));
+                } else if self.rest().starts_with("</") {
+                    self.pos += 2;
+                    let end = self.skip_to(">")?.trim_end();
+                    if end != tag {
+                        return Err(invalid(&format!("Element {} closed by {}", tag, end)));
+                    }
+                    break;
+                } else if self.rest().starts_with("<![CDATA[") {
+                    self.pos += 9;
+                    element.text.push_str(self.skip_to("]]>")?);
+                } else if !self.skip_markup()? {
+                    let child = self.parse_element()?;
+                    element.children.push(child);
+                }
+            }
+        }
+
+        self.scopes.pop();
+        Ok(element)
+    }
+
+    // Resolve a qualified name against the declarations in scope.  Only
+    // elements take the default namespace, and are in no namespace if none
+    // is declared.
+    fn resolve(&self, qname: &str, is_element: bool) -> Result<XMLName> {
+        let (prefix, name) = match qname.find(':') {
+            Some(i) => (&qname[..i], &qname[i + 1..]),
+            None if is_element => ("", qname),
+            None => return Ok(XMLName::new("", qname)),
+        };
+        let default = if prefix.is_empty() { Some(XMLName::new("", name)) } else { None };
+        if prefix == "xml" {
+            return Ok(XMLName::new(NS_XML, name));
+        }
+        self.scopes.iter().rev().flat_map(|s| s.iter()).find(|&(p, _)| p == prefix)
+            .map(|(_, namespace)| XMLName::new(namespace, name))
+            .or(default)
+            .ok_or_else(|| invalid(&format!("Undeclared prefix in {}", qname)))
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid XML: {}", message))
+}
+
+fn unescape(text: &str) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find(';').ok_or_else(|| invalid("Unterminated entity"))? + start;
+        let entity = &rest[start + 1..end];
+        let c = match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(decimal) = entity.strip_prefix('#') {
+                    decimal.parse().ok()
+                } else {
+                    None
+                };
+                code.and_then(std::char::from_u32)
+                    .ok_or_else(|| invalid(&format!("Unknown entity &{};", entity)))?
+            },
+        };
+        out.push(c);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
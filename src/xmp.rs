@@ -0,0 +1,347 @@
+//============================================================================
+//
+//  A Tagged Image File Format (TIFF) Library for Rust
+//
+//  Based on the TIFF 6.0 specification:
+//
+//      https://partners.adobe.com/public/developer/en/tiff/TIFF6.pdf
+//
+//  and the XMP specification, Part 1 (ISO 16684-1) and Part 3.
+//
+//  Copyright (c) 2014 by Gavin Baker <gavinb@antonym.org>
+//  Published under the MIT License
+//
+//============================================================================
+
+use std::io::{Result, Error, ErrorKind};
+use std::collections::{BTreeMap, BTreeSet};
+
+use {IFD, IFDEntry, TIFFTag, TagType};
+use xml::{self, Element, find_element, attribute, escape};
+
+// A property name: its namespace URI and local name
+pub use xml::{XMLName as XMPName, NS_XML};
+
+//----------------------------------------------------------------------------
+// Constants
+
+pub const NS_RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+pub const NS_X: &str = "adobe:ns:meta/";
+pub const NS_DC: &str = "http://purl.org/dc/elements/1.1/";
+pub const NS_XMP: &str = "http://ns.adobe.com/xap/1.0/";
+pub const NS_XMP_MM: &str = "http://ns.adobe.com/xap/1.0/mm/";
+pub const NS_PHOTOSHOP: &str = "http://ns.adobe.com/photoshop/1.0/";
+pub const NS_TIFF: &str = "http://ns.adobe.com/tiff/1.0/";
+pub const NS_EXIF: &str = "http://ns.adobe.com/exif/1.0/";
+
+// Prefixes used when writing namespaces the packet did not declare
+const WELL_KNOWN_PREFIXES: [(&str, &str); 6] = [
+    (NS_DC, "dc"),
+    (NS_XMP, "xmp"),
+    (NS_XMP_MM, "xmpMM"),
+    (NS_PHOTOSHOP, "photoshop"),
+    (NS_TIFF, "tiff"),
+    (NS_EXIF, "exif"),
+];
+
+// Whitespace after the packet, so that it can be edited in place
+const PADDING_LINES: usize = 20;
+
+//----------------------------------------------------------------------------
+// Types
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum XMPValue {
+    Text(String),
+    // Unordered and ordered arrays
+    Bag(Vec<XMPValue>),
+    Seq(Vec<XMPValue>),
+    // Alternatives, each with its xml:lang qualifier if any
+    Alt(Vec<(Option<String>, XMPValue)>),
+    Struct(BTreeMap<XMPName, XMPValue>),
+}
+
+// The properties of an XMP packet.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct XMP {
+    pub properties: BTreeMap<XMPName, XMPValue>,
+    // The prefix each namespace was declared with, used when writing
+    pub prefixes:   BTreeMap<String, String>,
+}
+
+//----------------------------------------------------------------------------
+
+impl XMPValue {
+
+    // The value as a single string: the text itself, the default (or
+    // first) alternative, or the first item of an array.
+    pub fn as_text(&self) -> Option<&str> {
+        match *self {
+            XMPValue::Text(ref s) => Some(s),
+            XMPValue::Bag(ref items) | XMPValue::Seq(ref items) => items.first().and_then(|v| v.as_text()),
+            XMPValue::Alt(ref items) => items.iter()
+                .find(|&(lang, _)| lang.as_ref().is_some_and(|l| l == "x-default"))
+                .or_else(|| items.first())
+                .and_then(|(_, v)| v.as_text()),
+            XMPValue::Struct(_) => None,
+        }
+    }
+
+    // The text of each item of an array, or of a single value.
+    pub fn as_texts(&self) -> Vec<&str> {
+        match *self {
+            XMPValue::Bag(ref items) | XMPValue::Seq(ref items) => items.iter().filter_map(|v| v.as_text()).collect(),
+            XMPValue::Alt(ref items) => items.iter().filter_map(|(_, v)| v.as_text()).collect(),
+            _ => self.as_text().into_iter().collect(),
+        }
+    }
+}
+
+impl XMP {
+
+    pub fn new() -> XMP {
+        XMP::default()
+    }
+
+    // Parse the properties of every rdf:Description in a packet.
+    pub fn parse(packet: &str) -> Result<XMP> {
+        let (root, prefixes) = xml::parse(packet)?;
+        let rdf = find_element(&root, NS_RDF, "RDF").ok_or_else(|| invalid("No rdf:RDF element"))?;
+
+        let mut properties = BTreeMap::new();
+        for description in rdf.children.iter().filter(|e| e.name.is(NS_RDF, "Description")) {
+            properties.append(&mut fields_of(description));
+        }
+
+        Ok(XMP { properties, prefixes })
+    }
+
+    pub fn get(&self, namespace: &str, name: &str) -> Option<&XMPValue> {
+        self.properties.get(&XMPName::new(namespace, name))
+    }
+
+    pub fn get_text(&self, namespace: &str, name: &str) -> Option<&str> {
+        self.get(namespace, name).and_then(|v| v.as_text())
+    }
+
+    pub fn set(&mut self, namespace: &str, name: &str, value: XMPValue) {
+        self.properties.insert(XMPName::new(namespace, name), value);
+    }
+
+    pub fn remove(&mut self, namespace: &str, name: &str) -> Option<XMPValue> {
+        self.properties.remove(&XMPName::new(namespace, name))
+    }
+
+    // A complete packet holding every property in one rdf:Description,
+    // followed by padding.  Every property needs a namespace.
+    pub fn to_packet(&self) -> Result<String> {
+
+        let prefixes = self.prefixes_for_writing()?;
+        let mut out = String::new();
+
+        out.push_str("<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+        out.push_str(&format!("<x:xmpmeta xmlns:x=\"{}\">\n", NS_X));
+        out.push_str(&format!(" <rdf:RDF xmlns:rdf=\"{}\">\n", NS_RDF));
+        out.push_str("  <rdf:Description rdf:about=\"\"");
+        for (namespace, prefix) in &prefixes {
+            out.push_str(&format!("\n    xmlns:{}=\"{}\"", prefix, escape(namespace)));
+        }
+        out.push_str(">\n");
+        for (name, value) in &self.properties {
+            write_value(&mut out, &prefixes, &qualified(&prefixes, name), "", value, 3);
+        }
+        out.push_str("  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n");
+        for _ in 0..PADDING_LINES {
+            out.push_str(&" ".repeat(99));
+            out.push('\n');
+        }
+        out.push_str("<?xpacket end=\"w\"?>");
+        Ok(out)
+    }
+
+    // A unique prefix for every namespace used by a property.  A namespace
+    // declared as the default has no prefix to reuse.
+    fn prefixes_for_writing(&self) -> Result<BTreeMap<String, String>> {
+
+        let mut namespaces = BTreeSet::new();
+        for (name, value) in &self.properties {
+            collect_namespaces(name, value, &mut namespaces);
+        }
+        if namespaces.contains("") {
+            return Err(Error::new(ErrorKind::InvalidInput, "XMP properties must have a namespace"));
+        }
+        namespaces.remove(NS_RDF);
+        namespaces.remove(NS_XML);
+
+        let mut prefixes = BTreeMap::new();
+        let mut taken: BTreeSet<String> = ["x", "rdf", "xml", "xmlns"].iter().map(|s| s.to_string()).collect();
+        for namespace in namespaces {
+            let preferred = self.prefixes.get(&namespace).filter(|p| !p.is_empty()).cloned()
+                .or_else(|| WELL_KNOWN_PREFIXES.iter().find(|&&(n, _)| n == namespace).map(|&(_, p)| p.to_string()))
+                .unwrap_or_else(|| "ns".to_string());
+            let mut prefix = preferred.clone();
+            let mut n = 1;
+            while taken.contains(&prefix) {
+                prefix = format!("{}{}", preferred, n);
+                n += 1;
+            }
+            taken.insert(prefix.clone());
+            prefixes.insert(namespace, prefix);
+        }
+        Ok(prefixes)
+    }
+}
+
+impl IFD {
+
+    // The XMP packet, decoded from UTF-8 (or UTF-16 if it starts with a
+    // byte order mark), without trailing NULs.
+    pub fn xmp_packet(&self) -> Option<String> {
+        let data = &self.get(TIFFTag::XMPTag)?.data;
+        let packet = match data.get(..2) {
+            Some([0xfe, 0xff]) | Some([0xff, 0xfe]) => {
+                let big_endian = data[0] == 0xfe;
+                let units: Vec<u16> = data[2..].chunks_exact(2).map(|c| if big_endian {
+                    u16::from_be_bytes([c[0], c[1]])
+                } else {
+                    u16::from_le_bytes([c[0], c[1]])
+                }).collect();
+                String::from_utf16_lossy(&units)
+            },
+            _ => String::from_utf8_lossy(data).into_owned(),
+        };
+        Some(packet.trim_start_matches('\u{feff}').trim_end_matches('\0').to_string())
+    }
+
+    pub fn xmp(&self) -> Result<Option<XMP>> {
+        match self.xmp_packet() {
+            Some(packet) => Ok(Some(XMP::parse(&packet)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Replace the XMP packet, to be written when the file is next saved.
+    pub fn set_xmp_packet(&mut self, packet: &str) {
+        self.set(IFDEntry::bytes(TIFFTag::XMPTag, TagType::ByteTag, packet.as_bytes()));
+    }
+
+    pub fn set_xmp(&mut self, xmp: &XMP) -> Result<()> {
+        self.set_xmp_packet(&xmp.to_packet()?);
+        Ok(())
+    }
+}
+
+//----------------------------------------------------------------------------
+// RDF
+
+// Properties given as attributes or child elements, skipping RDF syntax.
+fn fields_of(element: &Element) -> BTreeMap<XMPName, XMPValue> {
+    let mut fields = BTreeMap::new();
+    for (name, value) in &element.attributes {
+        if name.namespace != NS_RDF && name.namespace != NS_XML {
+            fields.insert(name.clone(), XMPValue::Text(value.clone()));
+        }
+    }
+    for child in &element.children {
+        fields.insert(child.name.clone(), value_of(child));
+    }
+    fields
+}
+
+fn value_of(element: &Element) -> XMPValue {
+
+    if let Some(resource) = attribute(element, NS_RDF, "resource") {
+        return XMPValue::Text(resource.to_string());
+    }
+    if attribute(element, NS_RDF, "parseType") == Some("Resource") {
+        return XMPValue::Struct(fields_of(element));
+    }
+
+    if let Some(child) = element.children.first() {
+        let items = || child.children.iter().filter(|e| e.name.is(NS_RDF, "li"));
+        return match child.name.name.as_str() {
+            "Bag" if child.name.namespace == NS_RDF => XMPValue::Bag(items().map(value_of).collect()),
+            "Seq" if child.name.namespace == NS_RDF => XMPValue::Seq(items().map(value_of).collect()),
+            "Alt" if child.name.namespace == NS_RDF => XMPValue::Alt(items().map(|li| {
+                (attribute(li, NS_XML, "lang").map(|l| l.to_string()), value_of(li))
+            }).collect()),
+            "Description" if child.name.namespace == NS_RDF => XMPValue::Struct(fields_of(child)),
+            _ => XMPValue::Struct(fields_of(element)),
+        };
+    }
+
+    let fields = fields_of(element);
+    if fields.is_empty() {
+        XMPValue::Text(element.text.clone())
+    } else {
+        XMPValue::Struct(fields)
+    }
+}
+
+fn collect_namespaces(name: &XMPName, value: &XMPValue, namespaces: &mut BTreeSet<String>) {
+    namespaces.insert(name.namespace.clone());
+    match *value {
+        XMPValue::Text(_) => {},
+        XMPValue::Bag(ref items) | XMPValue::Seq(ref items) => {
+            for item in items {
+                collect_namespaces(name, item, namespaces);
+            }
+        },
+        XMPValue::Alt(ref items) => {
+            for (_, item) in items {
+                collect_namespaces(name, item, namespaces);
+            }
+        },
+        XMPValue::Struct(ref fields) => {
+            for (field, item) in fields {
+                collect_namespaces(field, item, namespaces);
+            }
+        },
+    }
+}
+
+fn qualified(prefixes: &BTreeMap<String, String>, name: &XMPName) -> String {
+    match prefixes.get(&name.namespace) {
+        Some(prefix) => format!("{}:{}", prefix, name.name),
+        None => name.name.clone(),
+    }
+}
+
+// Write a value as the element `tag`, with any extra attributes.
+fn write_value(out: &mut String, prefixes: &BTreeMap<String, String>, tag: &str, attributes: &str,
+               value: &XMPValue, depth: usize) {
+
+    let indent = " ".repeat(depth);
+    match *value {
+        XMPValue::Text(ref s) => {
+            out.push_str(&format!("{}<{}{}>{}</{}>\n", indent, tag, attributes, escape(s), tag));
+        },
+        XMPValue::Struct(ref fields) => {
+            out.push_str(&format!("{}<{}{} rdf:parseType=\"Resource\">\n", indent, tag, attributes));
+            for (name, field) in fields {
+                write_value(out, prefixes, &qualified(prefixes, name), "", field, depth + 1);
+            }
+            out.push_str(&format!("{}</{}>\n", indent, tag));
+        },
+        XMPValue::Bag(ref items) | XMPValue::Seq(ref items) => {
+            let kind = if let XMPValue::Bag(_) = *value { "rdf:Bag" } else { "rdf:Seq" };
+            out.push_str(&format!("{}<{}{}>\n{} <{}>\n", indent, tag, attributes, indent, kind));
+            for item in items {
+                write_value(out, prefixes, "rdf:li", "", item, depth + 2);
+            }
+            out.push_str(&format!("{} </{}>\n{}</{}>\n", indent, kind, indent, tag));
+        },
+        XMPValue::Alt(ref items) => {
+            out.push_str(&format!("{}<{}{}>\n{} <rdf:Alt>\n", indent, tag, attributes, indent));
+            for (lang, item) in items {
+                let lang = lang.as_ref().map(|l| format!(" xml:lang=\"{}\"", escape(l))).unwrap_or_default();
+                write_value(out, prefixes, "rdf:li", &lang, item, depth + 2);
+            }
+            out.push_str(&format!("{} </rdf:Alt>\n{}</{}>\n", indent, indent, tag));
+        },
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid XMP: {}", message))
+}
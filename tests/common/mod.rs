@@ -0,0 +1,239 @@
+// Helpers for building small TIFF files in memory.
+
+#![allow(dead_code)]
+
+// Build a little-endian TIFF with one IFD.  Entries are (tag, type, count,
+// value) with the value held inline; the pixel data follows the header.
+pub fn build_tiff(entries: &[(u16, u16, u32, u32)], pixels: &[u8]) -> Vec<u8> {
+    let ifd_offset = 8 + pixels.len() as u32;
+    let mut data = vec![0x49, 0x49, 0x2a, 0x00];
+    data.extend_from_slice(&ifd_offset.to_le_bytes());
+    data.extend_from_slice(pixels);
+    data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for &(tag, typ, count, value) in entries {
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&typ.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data
+}
+
+// As build_tiff, but producing a little-endian BigTIFF with 8-byte counts
+// and values.  The pixel data follows the 16 byte header.
+pub fn build_bigtiff(entries: &[(u16, u16, u64, u64)], pixels: &[u8]) -> Vec<u8> {
+    let ifd_offset = 16 + pixels.len() as u64;
+    let mut data = vec![0x49, 0x49, 0x2b, 0x00, 0x08, 0x00, 0x00, 0x00];
+    data.extend_from_slice(&ifd_offset.to_le_bytes());
+    data.extend_from_slice(pixels);
+    data.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    for &(tag, typ, count, value) in entries {
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&typ.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data
+}
+
+//----------------------------------------------------------------------------
+// Building IFDs by hand
+
+// (tag, type, count, value); values over 4 bytes follow the IFD
+pub type Field = (u16, u16, u32, Vec<u8>);
+
+pub fn short(value: u16) -> Vec<u8> {
+    value.to_le_bytes().to_vec()
+}
+
+pub fn long(value: u32) -> Vec<u8> {
+    value.to_le_bytes().to_vec()
+}
+
+pub fn shorts(values: &[u16]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect()
+}
+
+pub fn doubles(values: &[f64]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect()
+}
+
+pub fn rationals(values: &[(u32, u32)]) -> Vec<u8> {
+    values.iter().flat_map(|&(n, d)| [n.to_le_bytes(), d.to_le_bytes()].concat()).collect()
+}
+
+pub fn srationals(values: &[(i32, i32)]) -> Vec<u8> {
+    values.iter().flat_map(|&(n, d)| [n.to_le_bytes(), d.to_le_bytes()].concat()).collect()
+}
+
+// Append a little-endian IFD and its values, returning its offset.
+pub fn push_ifd(file: &mut Vec<u8>, fields: &[Field], next: u32) -> u32 {
+    let offset = file.len() as u32;
+    let mut value_offset = offset + 2 + 12 * fields.len() as u32 + 4;
+    let mut values = Vec::new();
+
+    file.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+    for (tag, typ, count, value) in fields {
+        file.extend_from_slice(&tag.to_le_bytes());
+        file.extend_from_slice(&typ.to_le_bytes());
+        file.extend_from_slice(&count.to_le_bytes());
+        if value.len() <= 4 {
+            let mut inline = value.clone();
+            inline.resize(4, 0);
+            file.extend_from_slice(&inline);
+        } else {
+            file.extend_from_slice(&value_offset.to_le_bytes());
+            values.extend_from_slice(value);
+            values.resize(values.len().next_multiple_of(2), 0);
+            value_offset = offset + 2 + 12 * fields.len() as u32 + 4 + values.len() as u32;
+        }
+    }
+    file.extend_from_slice(&next.to_le_bytes());
+    file.extend_from_slice(&values);
+    offset
+}
+
+// A little-endian file with one IFD holding `fields`.  Byte 8 may serve as
+// the pixel of a 1x1 image.
+pub fn single_ifd_file(fields: &[Field]) -> Vec<u8> {
+    file_with(|_| fields.to_vec())
+}
+
+// As single_ifd_file, with `before` appending data or other IFDs, such as
+// an EXIF IFD, and returning the fields of the IFD that refer to them.
+pub fn file_with<F: FnOnce(&mut Vec<u8>) -> Vec<Field>>(before: F) -> Vec<u8> {
+    let mut file = vec![0x49, 0x49, 0x2a, 0x00, 0, 0, 0, 0, 0x80, 0];
+    let fields = before(&mut file);
+    let main = push_ifd(&mut file, &fields, 0);
+    file[4..8].copy_from_slice(&main.to_le_bytes());
+    file
+}
+
+// The fields of a 1x1 image whose pixel is byte 8, with `extra`.
+pub fn one_pixel(extra: &[Field]) -> Vec<Field> {
+    let mut fields = vec![
+        (0x0100, 3, 1, short(1)),
+        (0x0101, 3, 1, short(1)),
+        (0x0111, 4, 1, long(8)),
+        (0x0117, 4, 1, long(1)),
+    ];
+    fields.extend_from_slice(extra);
+    fields.sort_by_key(|f| f.0);
+    fields
+}
+
+pub fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
+
+//----------------------------------------------------------------------------
+// Round trips
+
+use std::io::Cursor;
+
+use tiff::{Image, TIFFWriter, TIFF};
+
+// Write `image` to memory, returning the file read back and its first image.
+pub fn round_trip(writer: &TIFFWriter, image: &Image) -> (Box<TIFF>, Image) {
+    let mut cursor = Cursor::new(Vec::new());
+    writer.write(&mut cursor, image).unwrap();
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let decoded = TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap();
+    (t, decoded)
+}
+
+//----------------------------------------------------------------------------
+// Comparing files
+
+use std::io::SeekFrom;
+
+use tiff::{IFD, IFDEntry, SeekableReader, TIFFHeader, TIFFReader, TagType};
+
+// Offset fields paired with their byte counts
+const DATA_FIELDS: [(u16, u16); 4] = [(0x0111, 0x0117), (0x0144, 0x0145), (0x0120, 0x0121), (0x0201, 0x0202)];
+// SubIFDs, EXIF, GPS and Interoperability IFD pointers
+const IFD_FIELDS: [u16; 4] = [0x014a, 0x8769, 0x8825, 0xa005];
+
+// Check that two files have the same IFDs, with the same fields (tag, type,
+// count and value) and the same bytes at every data offset, following child
+// IFDs.  Only where things are stored may differ.
+pub fn assert_same_structure(a: &mut dyn SeekableReader, b: &mut dyn SeekableReader) {
+    let ta = TIFFReader.read(a).unwrap();
+    let tb = TIFFReader.read(b).unwrap();
+    assert_eq!(ta.header.byte_order, tb.header.byte_order);
+    assert_eq!(ta.header.version, tb.header.version);
+    assert_eq!(ta.ifds.len(), tb.ifds.len(), "number of IFDs");
+
+    let mut files = Files { a, b, a_header: &ta.header, b_header: &tb.header };
+    for (i, (x, y)) in ta.ifds.iter().zip(&tb.ifds).enumerate() {
+        files.compare(x, y, &format!("IFD {}", i));
+    }
+}
+
+struct Files<'a> {
+    a: &'a mut dyn SeekableReader,
+    b: &'a mut dyn SeekableReader,
+    a_header: &'a TIFFHeader,
+    b_header: &'a TIFFHeader,
+}
+
+impl<'a> Files<'a> {
+
+    fn compare(&mut self, x: &IFD, y: &IFD, path: &str) {
+        let (x, y) = (sorted(x), sorted(y));
+        let fields = |entries: &[IFDEntry]| {
+            entries.iter().map(|e| (e.tag, e.typ as u16, e.count)).collect::<Vec<_>>()
+        };
+        assert_eq!(fields(&x.entries), fields(&y.entries), "{}: fields", path);
+
+        for (e, f) in x.entries.iter().zip(&y.entries) {
+            let path = format!("{} field {:04x}", path, e.tag);
+            let offsets = || e.as_u64s().unwrap().into_iter().zip(f.as_u64s().unwrap());
+
+            if let Some(&(_, counts_tag)) = DATA_FIELDS.iter().find(|&&(tag, _)| tag == e.tag) {
+                let counts = x.get_raw(counts_tag).and_then(|c| c.as_u64s()).unwrap();
+                for ((o, p), n) in offsets().zip(counts) {
+                    assert_eq!(read_at(self.a, o, n), read_at(self.b, p, n), "{}: data", path);
+                }
+            } else if is_child_ifd(e) {
+                for (o, p) in offsets() {
+                    self.compare_chains(o, p, &path);
+                }
+            } else {
+                assert_eq!(e.data, f.data, "{}: value", path);
+            }
+        }
+    }
+
+    fn compare_chains(&mut self, mut o: u64, mut p: u64, path: &str) {
+        let mut depth = 0;
+        while o != 0 && p != 0 {
+            let x = TIFFReader.read_ifd(self.a, self.a_header, o).unwrap();
+            let y = TIFFReader.read_ifd(self.b, self.b_header, p).unwrap();
+            self.compare(&x, &y, &format!("{} child {}", path, depth));
+            o = x.next_offset;
+            p = y.next_offset;
+            depth += 1;
+        }
+        assert_eq!((o == 0), (p == 0), "{}: chain length", path);
+    }
+}
+
+fn is_child_ifd(entry: &IFDEntry) -> bool {
+    IFD_FIELDS.contains(&entry.tag) || entry.typ == TagType::IFDTag || entry.typ == TagType::IFD8
+}
+
+fn sorted(ifd: &IFD) -> IFD {
+    let mut ifd = ifd.clone();
+    ifd.entries.sort_by_key(|e| e.tag);
+    ifd
+}
+
+fn read_at(reader: &mut dyn SeekableReader, offset: u64, count: u64) -> Vec<u8> {
+    let mut data = vec![0; count as usize];
+    reader.seek(SeekFrom::Start(offset)).unwrap();
+    reader.read_exact(&mut data).unwrap();
+    data
+}
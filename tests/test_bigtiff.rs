@@ -0,0 +1,184 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use tiff::{BigTIFFMode, Compression, HeaderMagic, Image, ImageData, PhotometricInterpretation, TagType, TagValue,
+           TIFFByteOrder, TIFFReader, TIFFTag, TIFFVersion, TIFFWriter};
+
+#[test]
+fn test_classic_header() {
+    let t = TIFFReader.load("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    assert_eq!(t.header.version, TIFFVersion::Classic);
+    assert_eq!(t.header.ifd_offset, 8);
+}
+
+#[test]
+fn test_read_bigtiff() {
+    // 2 x 2 grayscale pixels, then a 16 byte description at offset 20
+    let mut payload = vec![10, 20, 30, 40];
+    payload.extend_from_slice(b"A BigTIFF file\0\0");
+
+    let resolution = 300u64 | (1u64 << 32);
+    let data = common::build_bigtiff(&[
+        (0x0100, 3, 1, 2),              // ImageWidth
+        (0x0101, 3, 1, 2),              // ImageLength
+        (0x0102, 3, 1, 8),              // BitsPerSample
+        (0x0106, 3, 1, 1),              // PhotometricInterpretation
+        (0x010e, 2, 16, 20),            // ImageDescription
+        (0x0111, 16, 1, 16),            // StripOffsets (LONG8)
+        (0x0117, 16, 1, 4),             // StripByteCounts (LONG8)
+        (0x011a, 5, 1, resolution),     // XResolution, held inline
+    ], &payload);
+
+    let mut cursor = Cursor::new(data);
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.header.version, TIFFVersion::BigTIFF);
+    assert_eq!(t.header.magic, HeaderMagic::BigTIFFLittleEndian);
+    assert_eq!(t.header.ifd_offset, 36);
+
+    let ifd = &t.ifds[0];
+    assert_eq!(ifd.count, 8);
+
+    let offsets = ifd.get(TIFFTag::StripOffsetsTag).unwrap();
+    assert_eq!(offsets.typ, TagType::Long8);
+    assert_eq!(offsets.value(), Some(TagValue::Long8Value(16)));
+    assert_eq!(ifd.get_u64s(TIFFTag::StripByteCountsTag), Some(vec![4]));
+    assert_eq!(ifd.get_ascii(TIFFTag::ImageDescriptionTag), Some("A BigTIFF file".to_string()));
+    assert_eq!(ifd.get(TIFFTag::XResolutionTag).unwrap().value(),
+               Some(TagValue::RationalValue((300, 1))));
+
+    let image = TIFFReader.read_image(&mut cursor, ifd).unwrap();
+    assert_eq!(image.data, ImageData::U8(vec![10, 20, 30, 40]));
+}
+
+#[test]
+fn test_bigtiff_bad_offset_size() {
+    let mut data = common::build_bigtiff(&[], &[]);
+    data[4] = 4;
+    assert!(TIFFReader.read(&mut Cursor::new(data)).is_err());
+}
+
+#[test]
+fn test_bigtiff_bad_entries() {
+    // An entry count far beyond the end of the file
+    let mut data = common::build_bigtiff(&[], &[]);
+    data[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert_eq!(data.len(), 32);
+    assert!(TIFFReader.read(&mut Cursor::new(data)).is_err());
+
+    // An entry whose value lies past the end of the file
+    let data = common::build_bigtiff(&[(0x010e, 2, 16, 1000)], &[]);
+    assert!(TIFFReader.read(&mut Cursor::new(data)).is_err());
+
+    // An entry of unknown type is skipped
+    let data = common::build_bigtiff(&[(0x0100, 3, 1, 2), (0x0101, 99, 1, 2)], &[]);
+    let t = TIFFReader.read(&mut Cursor::new(data)).unwrap();
+    assert_eq!(t.ifds[0].entries.len(), 1);
+    assert_eq!(t.ifds[0].count, 2);
+}
+
+fn grey_image() -> Image {
+    Image::new(90, 60, 1, PhotometricInterpretation::BlackIsZero,
+               ImageData::U16((0..90 * 60).map(|i| (i * 11) as u16).collect()))
+}
+
+#[test]
+fn test_write_bigtiff() {
+    let image = grey_image();
+    for &byte_order in &[TIFFByteOrder::LittleEndian, TIFFByteOrder::BigEndian] {
+        let writer = TIFFWriter { bigtiff: BigTIFFMode::Always, compression: Compression::Deflate,
+                                  ..TIFFWriter::new(byte_order) };
+        let mut cursor = Cursor::new(Vec::new());
+        writer.write(&mut cursor, &image).unwrap();
+
+        let t = TIFFReader.read(&mut cursor).unwrap();
+        assert_eq!(t.header.version, TIFFVersion::BigTIFF);
+        let ifd = &t.ifds[0];
+        assert_eq!(ifd.get(TIFFTag::StripOffsetsTag).unwrap().typ, TagType::Long8);
+        assert_eq!(ifd.get(TIFFTag::StripByteCountsTag).unwrap().typ, TagType::Long8);
+        assert_eq!(ifd.resolution().unwrap().dpi(), Some((72.0, 72.0)));
+        assert_eq!(TIFFReader.read_image(&mut cursor, ifd).unwrap().data, image.data);
+    }
+}
+
+#[test]
+fn test_write_bigtiff_pages_and_tiles() {
+    let image = grey_image();
+    let writer = TIFFWriter { bigtiff: BigTIFFMode::Always, ..TIFFWriter::new(TIFFByteOrder::BigEndian) };
+
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut pages = writer.pages(&mut cursor).unwrap();
+        pages.append(&writer, &image).unwrap();
+        pages.append(&writer, &image).unwrap();
+        pages.finish().unwrap();
+    }
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.header.version, TIFFVersion::BigTIFF);
+    assert_eq!(t.ifds.len(), 2);
+    assert_eq!(t.ifds[1].get_u32s(TIFFTag::PageNumberTag), Some(vec![1, 2]));
+    assert_eq!(TIFFReader.read_image(&mut cursor, &t.ifds[1]).unwrap().data, image.data);
+
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut tiles = writer.tiles(&mut cursor, &image, 64, 64).unwrap();
+        tiles.write_tile(0, 0, &ImageData::U16(vec![7; 64 * 64])).unwrap();
+        tiles.write_tile(1, 0, &ImageData::U16(vec![7; 26 * 60])).unwrap();
+        tiles.finish().unwrap();
+    }
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.header.version, TIFFVersion::BigTIFF);
+    assert_eq!(TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap().data, ImageData::U16(vec![7; 90 * 60]));
+}
+
+#[test]
+fn test_bigtiff_auto() {
+    let small = grey_image();
+    let large = Image::new(70000, 70000, 3, PhotometricInterpretation::RGB, ImageData::U8(Vec::new()));
+
+    let auto = TIFFWriter::default();
+    assert_eq!(auto.bigtiff, BigTIFFMode::Auto);
+    assert_eq!(auto.version_for(&small, small.width, small.height), TIFFVersion::Classic);
+    assert_eq!(auto.version_for(&large, large.width, large.height), TIFFVersion::BigTIFF);
+
+    // Compression may expand the data past 4GB
+    let near = Image::new(50000, 30000, 2, PhotometricInterpretation::BlackIsZero, ImageData::U8(Vec::new()));
+    assert_eq!(auto.version_for(&near, near.width, near.height), TIFFVersion::Classic);
+    let lzw = TIFFWriter { compression: Compression::LZW, ..TIFFWriter::default() };
+    assert_eq!(lzw.version_for(&near, near.width, near.height), TIFFVersion::BigTIFF);
+
+    let never = TIFFWriter { bigtiff: BigTIFFMode::Never, ..TIFFWriter::default() };
+    assert_eq!(never.version_for(&large, large.width, large.height), TIFFVersion::Classic);
+
+    // Small images are written as classic TIFF
+    let mut cursor = Cursor::new(Vec::new());
+    auto.write(&mut cursor, &small).unwrap();
+    assert_eq!(TIFFReader.read(&mut cursor).unwrap().header.version, TIFFVersion::Classic);
+
+    // A large tiled image is promoted before any tiles are written
+    let mut cursor = Cursor::new(Vec::new());
+    auto.tiles(&mut cursor, &large, 256, 256).unwrap();
+    assert_eq!(&cursor.get_ref()[..4], b"II\x2b\x00");
+
+    // Pages are promoted by the size of the first
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut pages = auto.pages(&mut cursor).unwrap();
+        assert!(pages.append(&auto, &large).is_err());
+    }
+    assert_eq!(&cursor.get_ref()[..4], b"II\x2b\x00");
+
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut pages = auto.pages(&mut cursor).unwrap();
+        pages.append(&auto, &small).unwrap();
+        pages.append(&auto, &small).unwrap();
+        pages.finish().unwrap();
+    }
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!((t.header.version, t.ifds.len()), (TIFFVersion::Classic, 2));
+
+}
@@ -0,0 +1,100 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use tiff::{Compression, Image, ImageData, PhotometricInterpretation, Predictor, SampleFormat,
+           TIFFByteOrder, TIFFReader, TIFFTag, TIFFWriter};
+use tiff::compression::{compress, decompress};
+
+const BYTE_ORDERS: [TIFFByteOrder; 2] = [TIFFByteOrder::LittleEndian, TIFFByteOrder::BigEndian];
+const COMPRESSIONS: [Compression; 3] = [Compression::PackBits, Compression::LZW, Compression::Deflate];
+
+// Deterministic noise, so the LZW table fills and is cleared several times.
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x1234_5678u32;
+    (0..len).map(|_| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (state >> 16) as u8
+    }).collect()
+}
+
+fn check(image: &Image, predictor: Predictor) {
+    for &byte_order in BYTE_ORDERS.iter() {
+        for &compression in COMPRESSIONS.iter() {
+            let mut writer = TIFFWriter::new(byte_order);
+            writer.compression = compression;
+            writer.predictor = predictor;
+
+            let (t, decoded) = common::round_trip(&writer, image);
+            let ifd = &t.ifds[0];
+            assert_eq!(ifd.get_u32(TIFFTag::CompressionTag), Some(compression as u32));
+            assert_eq!(ifd.get_u32(TIFFTag::PredictorTag),
+                       if predictor == Predictor::None { None } else { Some(predictor as u32) });
+            assert!(decoded.data == image.data, "{:?} {:?} {:?}", compression, predictor, byte_order);
+        }
+    }
+}
+
+#[test]
+fn test_codecs_round_trip() {
+    let mut inputs = vec![Vec::new(), vec![7], vec![0; 1000], noise(100_000)];
+    inputs.push((0..50_000).map(|i| (i / 7 % 256) as u8).collect());
+
+    for &compression in COMPRESSIONS.iter() {
+        for input in &inputs {
+            let encoded = compress(compression, input).unwrap();
+            assert_eq!(&decompress(compression, &encoded, input.len()).unwrap(), input,
+                       "{:?} of {} bytes", compression, input.len());
+        }
+    }
+}
+
+#[test]
+fn test_packbits_spec_example() {
+    // p42: the example from the PackBits description
+    let packed = [0xfe, 0xaa, 0x02, 0x80, 0x00, 0x2a, 0xfd, 0xaa, 0x03, 0x80, 0x00, 0x2a, 0x22,
+                  0xf7, 0xaa];
+    let unpacked = decompress(Compression::PackBits, &packed, 24).unwrap();
+    assert_eq!(unpacked, vec![0xaa, 0xaa, 0xaa, 0x80, 0x00, 0x2a, 0xaa, 0xaa, 0xaa, 0xaa, 0x80, 0x00,
+                              0x2a, 0x22, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa]);
+}
+
+#[test]
+fn test_write_compressed_rgb() {
+    let mut file = std::fs::File::open("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut file).unwrap();
+    let image = TIFFReader.read_image(&mut file, &t.ifds[0]).unwrap();
+
+    check(&image, Predictor::None);
+    check(&image, Predictor::Horizontal);
+}
+
+#[test]
+fn test_write_compressed_u16() {
+    let pixels: Vec<u16> = noise(2 * 130 * 90).into_iter().enumerate()
+        .map(|(i, n)| (i as u16).wrapping_mul(31) ^ (n as u16 & 0x7)).collect();
+    let image = Image::new(130, 90, 2, PhotometricInterpretation::BlackIsZero, ImageData::U16(pixels));
+
+    check(&image, Predictor::None);
+    check(&image, Predictor::Horizontal);
+}
+
+#[test]
+fn test_write_compressed_float() {
+    let pixels: Vec<f32> = (0..64 * 50).map(|i| (i as f32 * 0.01).sin() * 1000.0).collect();
+    let mut image = Image::new(64, 50, 1, PhotometricInterpretation::BlackIsZero, ImageData::F32(pixels));
+    image.sample_format = SampleFormat::IEEEFloatingPoint;
+
+    check(&image, Predictor::None);
+    check(&image, Predictor::FloatingPoint);
+}
+
+#[test]
+fn test_write_rejects_mismatched_predictor() {
+    let image = Image::new(4, 4, 1, PhotometricInterpretation::BlackIsZero, ImageData::U8(vec![0; 16]));
+    let writer = TIFFWriter { predictor: Predictor::FloatingPoint, ..TIFFWriter::default() };
+    assert!(writer.write(&mut Cursor::new(Vec::new()), &image).is_err());
+}
@@ -0,0 +1,165 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use common::{assert_same_structure, doubles, file_with, push_ifd, short, long, Field};
+use tiff::{copy_tiff, BigTIFFMode, Compression, IFDEntry, Image, ImageData, PhotometricInterpretation,
+           Predictor, PyramidOptions, TIFFReader, TIFFTag, TIFFVersion, TIFFWriter};
+
+// An uncompressed 8-bit greyscale image in a single strip.
+fn image_fields(width: u16, height: u16, strip_offset: u32) -> Vec<Field> {
+    vec![
+        (0x0100, 3, 1, short(width)),
+        (0x0101, 3, 1, short(height)),
+        (0x0102, 3, 1, short(8)),
+        (0x0103, 3, 1, short(1)),
+        (0x0106, 3, 1, short(1)),
+        (0x0111, 4, 1, long(strip_offset)),
+        (0x0116, 3, 1, short(height)),
+        (0x0117, 4, 1, long(width as u32 * height as u32)),
+    ]
+}
+
+// A 4x2 image with a reduced resolution SubIFD, an EXIF IFD pointing to an
+// Interoperability IFD, and private fields.  With `exif_loop` the EXIF IFD
+// names itself as the next IFD.
+fn file_with_children(exif_loop: bool) -> Vec<u8> {
+    file_with(|file| {
+        file.extend((0..8).map(|i| i * 30));
+        file.extend_from_slice(&[200, 100]);
+
+        let interop = push_ifd(file, &[(0x0001, 2, 4, b"R98\0".to_vec())], 0);
+        let exif_offset = file.len() as u32;
+        let exif = push_ifd(file, &[
+            (0x9000, 7, 4, b"0230".to_vec()),
+            (0x9286, 7, 12, b"ASCII\0\0\0note".to_vec()),
+            (0xa005, 4, 1, long(interop)),
+        ], if exif_loop { exif_offset } else { 0 });
+
+        let mut child_fields = vec![(0x00fe, 4, 1, long(1))];
+        child_fields.extend(image_fields(2, 1, 18));
+        let child = push_ifd(file, &child_fields, 0);
+
+        let mut fields = image_fields(4, 2, 10);
+        fields.extend(vec![
+            (0x014a, 13, 1, long(child)),
+            (0x8769, 4, 1, long(exif)),
+            (0xc000, 12, 2, doubles(&[1.5, -2.25])),
+            (0xc001, 7, 5, vec![1, 2, 3, 4, 5]),
+        ]);
+        fields
+    })
+}
+
+fn copy(original: &[u8]) -> Vec<u8> {
+    let mut source = Cursor::new(original.to_vec());
+    let t = TIFFReader.read(&mut source).unwrap();
+    let mut output = Cursor::new(Vec::new());
+    copy_tiff(&mut source, &t, &mut output).unwrap();
+    output.into_inner()
+}
+
+#[test]
+fn test_copy_fixture() {
+    let original = std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let copied = copy(&original);
+    assert_same_structure(&mut Cursor::new(&original), &mut Cursor::new(&copied));
+
+    let mut source = Cursor::new(original);
+    let t = TIFFReader.read(&mut source).unwrap();
+    let expected = TIFFReader.read_image(&mut source, &t.ifds[0]).unwrap();
+
+    let mut cursor = Cursor::new(copied);
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert!(t.ifds[0].get(TIFFTag::EXIFTag).is_some());
+    assert_eq!(TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap().data, expected.data);
+}
+
+#[test]
+fn test_copy_child_ifds() {
+    let original = file_with_children(false);
+    let copied = copy(&original);
+    assert_ne!(copied, original);
+    assert_same_structure(&mut Cursor::new(&original), &mut Cursor::new(&copied));
+
+    // The reduced resolution image is still reachable and intact
+    let mut cursor = Cursor::new(copied);
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let child_offset = t.ifds[0].get_u64s(TIFFTag::SubIFDsTag).unwrap()[0];
+    let child = TIFFReader.read_ifd(&mut cursor, &t.header, child_offset).unwrap();
+    let image = TIFFReader.read_image(&mut cursor, &child).unwrap();
+    assert_eq!(image.data, ImageData::U8(vec![200, 100]));
+}
+
+#[test]
+fn test_copy_compressed_tiles() {
+    let pixels = (0..3 * 300 * 200).map(|i| (i % 7 * 31 + i / 900) as u8).collect();
+    let image = Image::new(300, 200, 3, PhotometricInterpretation::RGB, ImageData::U8(pixels));
+    let writer = TIFFWriter { compression: Compression::LZW, predictor: Predictor::Horizontal,
+                              bigtiff: BigTIFFMode::Always, ..TIFFWriter::default() };
+    let options = PyramidOptions { tile_width: 64, tile_length: 64, ..PyramidOptions::default() };
+
+    let mut original = Cursor::new(Vec::new());
+    writer.write_pyramid(&mut original, &image, &options).unwrap();
+    let original = original.into_inner();
+    let copied = copy(&original);
+    assert_same_structure(&mut Cursor::new(&original), &mut Cursor::new(&copied));
+
+    let mut cursor = Cursor::new(copied);
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.header.version, TIFFVersion::BigTIFF);
+    assert!(t.ifds.len() > 1);
+    assert_eq!(TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap().data, image.data);
+}
+
+#[test]
+fn test_copy_after_edit() {
+    let original = file_with_children(false);
+    let mut source = Cursor::new(original);
+    let mut t = TIFFReader.read(&mut source).unwrap();
+    t.ifds[0].set(IFDEntry::ascii(TIFFTag::ArtistTag, "A. Photographer"));
+    t.ifds[0].remove(0xc001);
+    t.ifds[0].sub_ifds[0].set(IFDEntry::ascii(TIFFTag::PageNameTag, "reduced"));
+
+    let mut output = Cursor::new(Vec::new());
+    copy_tiff(&mut source, &t, &mut output).unwrap();
+
+    let copied = TIFFReader.read(&mut output).unwrap();
+    let ifd = &copied.ifds[0];
+    assert_eq!(ifd.get_ascii(TIFFTag::ArtistTag), Some("A. Photographer".to_string()));
+    assert!(ifd.get_raw(0xc001).is_none());
+    assert!(ifd.get_raw(0xc000).is_some());
+    assert_eq!(TIFFReader.read_image(&mut output, ifd).unwrap().data,
+               ImageData::U8((0..8).map(|i| i * 30).collect()));
+
+    // The edited SubIFD keeps its image data
+    let child = &ifd.sub_ifds[0];
+    assert_eq!(child.get_ascii(TIFFTag::PageNameTag), Some("reduced".to_string()));
+    assert_eq!(TIFFReader.read_image(&mut output, child).unwrap().data, ImageData::U8(vec![200, 100]));
+}
+
+#[test]
+fn test_copy_rejects_ifd_loop() {
+    let original = file_with_children(true);
+    let mut source = Cursor::new(original);
+    let t = TIFFReader.read(&mut source).unwrap();
+    assert!(copy_tiff(&mut source, &t, &mut Cursor::new(Vec::new())).is_err());
+}
+
+#[test]
+#[should_panic(expected = "field c000: value")]
+fn test_structure_differs() {
+    let original = file_with_children(false);
+    let mut source = Cursor::new(original.clone());
+    let mut t = TIFFReader.read(&mut source).unwrap();
+    let mut entry = t.ifds[0].get_raw(0xc000).unwrap().clone();
+    entry.data[0] ^= 1;
+    t.ifds[0].set(entry);
+
+    let mut output = Cursor::new(Vec::new());
+    copy_tiff(&mut source, &t, &mut output).unwrap();
+    assert_same_structure(&mut Cursor::new(&original), &mut output);
+}
@@ -0,0 +1,116 @@
+extern crate rust_tiff as tiff;
+
+use std::io::ErrorKind;
+
+use tiff::{CFAPattern, Demosaic, ImageData, PhotometricInterpretation, RawImage};
+
+const ALGORITHMS: [Demosaic; 2] = [Demosaic::Bilinear, Demosaic::MalvarHeCutler];
+
+// An RGGB sensor of the given size, each sample given by its position and
+// plane.
+fn raw<F: Fn(usize, usize, u8) -> f32>(width: u32, height: u32, pattern: [u8; 4], sample: F) -> RawImage {
+    let cfa = CFAPattern { rows: 2, cols: 2, pattern: pattern.to_vec(), plane_colors: vec![0, 1, 2], layout: 1 };
+    let mut data = Vec::new();
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            data.push(sample(x, y, cfa.plane_at(y, x)));
+        }
+    }
+    RawImage {
+        width,
+        height,
+        samples_per_pixel: 1,
+        photometric: PhotometricInterpretation::CFA,
+        cfa: Some(cfa),
+        crop_origin: (0.0, 0.0),
+        crop_size: (width as f64, height as f64),
+        data,
+    }
+}
+
+fn samples(data: &ImageData) -> &[u16] {
+    match *data {
+        ImageData::U16(ref v) => v,
+        _ => panic!("Expected 16-bit samples"),
+    }
+}
+
+//----------------------------------------------------------------------------
+
+#[test]
+fn test_flat_field() {
+    // A grey scene through a sensor whose red channel is half as sensitive
+    let sensor = raw(6, 4, [0, 1, 1, 2], |_, _, plane| if plane == 0 { 0.25 } else { 0.5 });
+
+    for &algorithm in ALGORITHMS.iter() {
+        let image = sensor.demosaic(algorithm, &[2.0, 1.0, 1.0]).unwrap();
+        assert_eq!((image.width, image.height, image.samples_per_pixel), (6, 4, 3));
+        assert_eq!(image.photometric, PhotometricInterpretation::RGB);
+        assert_eq!(image.bits_per_sample, 16);
+        assert!(samples(&image.data).iter().all(|&s| s == 32768), "{:?}", algorithm);
+
+        // Unbalanced, red is darker everywhere
+        let image = sensor.demosaic(algorithm, &[1.0, 1.0, 1.0]).unwrap();
+        assert!(samples(&image.data).chunks(3).all(|p| p == [16384, 32768, 32768]), "{:?}", algorithm);
+    }
+}
+
+#[test]
+fn test_gradient() {
+    // Both algorithms reproduce a linear ramp away from the edges, for each
+    // arrangement of the Bayer pattern
+    for &pattern in [[0, 1, 1, 2], [2, 1, 1, 0], [1, 0, 2, 1], [1, 2, 0, 1]].iter() {
+        let sensor = raw(10, 10, pattern, |x, y, _| (x + 2 * y) as f32 / 32.0);
+        for &algorithm in ALGORITHMS.iter() {
+            let image = sensor.demosaic(algorithm, &[1.0; 3]).unwrap();
+            let data = samples(&image.data);
+            for y in 2..8 {
+                for x in 2..8 {
+                    let expected = ((x + 2 * y) as f64 / 32.0 * 65535.0).round() as i32;
+                    for &s in &data[(y * 10 + x) * 3..(y * 10 + x + 1) * 3] {
+                        assert!((s as i32 - expected).abs() <= 1, "{:?} {:?} at {},{}", algorithm, pattern, x, y);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_edges_and_clipping() {
+    // Malvar-He-Cutler can overshoot at a sharp edge; results are clipped
+    let sensor = raw(8, 8, [0, 1, 1, 2], |x, _, _| if x < 4 { 0.0 } else { 1.0 });
+    for &algorithm in ALGORITHMS.iter() {
+        let image = sensor.demosaic(algorithm, &[1.0; 3]).unwrap();
+        let data = samples(&image.data);
+        assert_eq!(&data[0..3], [0, 0, 0]);
+        assert_eq!(&data[7 * 3..8 * 3], [65535, 65535, 65535]);
+    }
+
+    // Tiny images still interpolate
+    let tiny = raw(1, 2, [0, 1, 1, 2], |_, _, _| 1.0);
+    for &algorithm in ALGORITHMS.iter() {
+        let image = tiny.demosaic(algorithm, &[1.0; 3]).unwrap();
+        assert_eq!(samples(&image.data).len(), 6);
+    }
+}
+
+#[test]
+fn test_unsupported_patterns() {
+    // Greens in a column: bilinear copes, Malvar-He-Cutler does not
+    let sensor = raw(4, 4, [0, 1, 2, 1], |_, _, _| 0.5);
+    assert!(sensor.demosaic(Demosaic::Bilinear, &[1.0; 3]).is_ok());
+    let error = sensor.demosaic(Demosaic::MalvarHeCutler, &[1.0; 3]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+
+    assert_eq!(sensor.demosaic(Demosaic::Bilinear, &[1.0; 2]).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    let mut linear = sensor.clone();
+    linear.cfa = None;
+    linear.photometric = PhotometricInterpretation::LinearRaw;
+    assert_eq!(linear.demosaic(Demosaic::Bilinear, &[1.0; 3]).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    let mut cmy = sensor.clone();
+    cmy.cfa.as_mut().unwrap().plane_colors = vec![3, 4, 5];
+    assert!(cmy.demosaic(Demosaic::Bilinear, &[1.0; 3]).is_err());
+}
@@ -0,0 +1,172 @@
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::{Cursor, ErrorKind};
+
+use common::{file_with, one_pixel, push_ifd, short, shorts, long, rationals, srationals, Field};
+use tiff::{Demosaic, ImageData, PhotometricInterpretation, TIFFReader, TIFFTag};
+
+// A DNG whose first IFD is a 1x1 preview, with the raw image in a SubIFD.
+// The raw image has the given samples and fields besides its layout.
+fn dng_file(width: u16, height: u16, bits: u16, photometric: u16, samples: &[u8], raw_fields: &[Field]) -> Vec<u8> {
+    file_with(|file| {
+        file.extend_from_slice(samples);
+        file.resize(file.len().next_multiple_of(2), 0);
+
+        let mut fields = vec![
+            (0x00fe, 4, 1, long(0)),
+            (0x0100, 3, 1, short(width)),
+            (0x0101, 3, 1, short(height)),
+            (0x0102, 3, 1, short(bits)),
+            (0x0106, 3, 1, short(photometric)),
+            (0x0111, 4, 1, long(10)),
+            (0x0117, 4, 1, long(samples.len() as u32)),
+        ];
+        fields.extend_from_slice(raw_fields);
+        let raw = push_ifd(file, &fields, 0);
+
+        let model = b"Test Camera\0";
+        one_pixel(&[
+            (0x00fe, 4, 1, long(1)),
+            (0x014a, 13, 1, long(raw)),
+            (0xc612, 1, 4, vec![1, 4, 0, 0]),
+            (0xc614, 2, model.len() as u32, model.to_vec()),
+            (0xc621, 10, 9, srationals(&[(8, 10), (-2, 10), (-1, 10), (-4, 10), (12, 10),
+                                         (2, 10), (0, 10), (1, 10), (6, 10)])),
+            (0xc628, 5, 3, rationals(&[(1, 2), (1, 1), (2, 3)])),
+            (0xc62a, 10, 1, srationals(&[(-1, 2)])),
+            (0xc65a, 3, 1, short(21)),
+        ])
+    })
+}
+
+// A 4x2 RGGB sensor with a masked column either side.
+fn cfa_file(extra: &[Field]) -> Vec<u8> {
+    let samples = shorts(&[
+        90, 1100, 2100, 90,
+        90, 3100, 4100, 90,
+    ]);
+    let mut fields = vec![
+        (0x828d, 3, 2, shorts(&[2, 2])),
+        (0x828e, 1, 4, vec![0, 1, 1, 2]),
+        (0xc61a, 3, 1, short(100)),
+        (0xc61d, 3, 1, short(4100)),
+    ];
+    if !extra.iter().any(|f| f.0 == 0xc68d) {
+        fields.push((0xc68d, 3, 4, shorts(&[0, 1, 2, 3])));
+    }
+    fields.extend_from_slice(extra);
+    dng_file(4, 2, 16, 32803, &samples, &fields)
+}
+
+//----------------------------------------------------------------------------
+
+#[test]
+fn test_dng_metadata() {
+    let t = TIFFReader.read(&mut Cursor::new(cfa_file(&[]))).unwrap();
+    let dng = t.ifds[0].dng().unwrap();
+
+    assert_eq!(dng.version, [1, 4, 0, 0]);
+    assert_eq!(dng.backward_version, [1, 4, 0, 0]);
+    assert_eq!(dng.unique_camera_model.as_deref(), Some("Test Camera"));
+    assert_eq!(dng.color_planes(), Some(3));
+    assert_eq!(dng.color_matrix1.as_ref().unwrap()[1], -0.2);
+    assert_eq!(dng.color_matrix2, None);
+    assert_eq!(dng.as_shot_neutral, Some(vec![0.5, 1.0, 2.0 / 3.0]));
+    assert_eq!(dng.baseline_exposure, -0.5);
+    assert_eq!(dng.calibration_illuminant1, Some(21));
+    assert_eq!(dng.calibration_illuminant2, None);
+
+    // The raw image is the full resolution SubIFD, not the preview
+    let raw = t.dng_raw_ifd().unwrap();
+    assert_eq!(raw.get_u32(TIFFTag::ImageWidthTag), Some(4));
+    assert!(t.ifds[0].sub_ifds[0].dng().is_none());
+
+    let fixture = std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut Cursor::new(fixture)).unwrap();
+    assert!(t.ifds[0].dng().is_none());
+    assert!(t.dng_raw_ifd().is_none());
+}
+
+#[test]
+fn test_cfa_raw() {
+    let mut cursor = Cursor::new(cfa_file(&[(0xc61f, 3, 2, shorts(&[0, 0])), (0xc620, 3, 2, shorts(&[2, 1]))]));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let raw = TIFFReader.read_dng_raw(&mut cursor, t.dng_raw_ifd().unwrap()).unwrap();
+
+    assert_eq!((raw.width, raw.height, raw.samples_per_pixel), (2, 2, 1));
+    assert_eq!(raw.photometric, PhotometricInterpretation::CFA);
+    assert_eq!(raw.data, [0.25, 0.5, 0.75, 1.0]);
+    assert_eq!(raw.pixel(1, 1), [1.0]);
+    assert_eq!((raw.crop_origin, raw.crop_size), ((0.0, 0.0), (2.0, 1.0)));
+
+    let cfa = raw.cfa.unwrap();
+    assert_eq!((cfa.rows, cfa.cols, cfa.layout), (2, 2, 1));
+    assert_eq!(cfa.plane_colors, [0, 1, 2]);
+    assert_eq!([cfa.plane_at(0, 0), cfa.plane_at(0, 1), cfa.plane_at(1, 0), cfa.plane_at(3, 3)], [0, 1, 1, 2]);
+
+    // The preview is not raw data
+    let error = TIFFReader.read_dng_raw(&mut cursor, &t.ifds[0]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_linear_raw() {
+    // Linearised through the table, then a black level repeating down
+    // two rows plus a per-column delta
+    let mut cursor = Cursor::new(dng_file(2, 2, 8, 34892, &[0, 1, 2, 3], &[
+        (0xc618, 3, 4, shorts(&[0, 100, 200, 1000])),
+        (0xc619, 3, 2, shorts(&[2, 1])),
+        (0xc61a, 3, 2, shorts(&[0, 50])),
+        (0xc61b, 10, 2, srationals(&[(0, 1), (10, 1)])),
+        (0xc61d, 3, 1, short(1000)),
+    ]));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let raw = TIFFReader.read_dng_raw(&mut cursor, t.dng_raw_ifd().unwrap()).unwrap();
+
+    assert_eq!(raw.photometric, PhotometricInterpretation::LinearRaw);
+    assert_eq!(raw.cfa, None);
+    assert_eq!(raw.data, [0.0, (90.0f64 / 990.0) as f32, (150.0f64 / 950.0) as f32, 1.0]);
+    assert_eq!(raw.crop_size, (2.0, 2.0));
+}
+
+#[test]
+fn test_invalid_raw() {
+    let read = |file: Vec<u8>| {
+        let mut cursor = Cursor::new(file);
+        let t = TIFFReader.read(&mut cursor).unwrap();
+        TIFFReader.read_dng_raw(&mut cursor, t.dng_raw_ifd().unwrap())
+    };
+
+    // BlackLevel count not matching the repeat pattern
+    assert!(read(cfa_file(&[(0xc619, 3, 2, shorts(&[2, 2]))])).is_err());
+    // ActiveArea beyond the image
+    assert!(read(cfa_file(&[(0xc68d, 3, 4, shorts(&[0, 0, 3, 4]))])).is_err());
+    // A delta for every column of the full image, not the active area
+    assert!(read(cfa_file(&[(0xc61b, 10, 4, srationals(&[(0, 1); 4]))])).is_err());
+    // A pattern colour missing from CFAPlaneColor
+    assert!(read(cfa_file(&[(0xc616, 1, 3, vec![0, 1, 3])])).is_err());
+    // No samples per pixel
+    assert!(read(cfa_file(&[(0x0115, 3, 1, short(0))])).is_err());
+    // A repeat pattern vastly larger than BlackLevel
+    assert!(read(cfa_file(&[(0xc619, 4, 2, [long(u32::MAX), long(u32::MAX)].concat())])).is_err());
+}
+
+#[test]
+fn test_demosaic_dng() {
+    let mut cursor = Cursor::new(cfa_file(&[]));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let white_balance = t.ifds[0].dng().unwrap().white_balance().unwrap();
+    assert_eq!(white_balance, [2.0, 1.0, 1.5]);
+
+    let raw = TIFFReader.read_dng_raw(&mut cursor, t.dng_raw_ifd().unwrap()).unwrap();
+    let image = raw.demosaic(Demosaic::Bilinear, &white_balance).unwrap();
+    assert_eq!((image.width, image.height, image.samples_per_pixel), (2, 2, 3));
+    // Red doubled, blue clipped, and green between its two samples at the
+    // red and blue pixels
+    assert_eq!(image.data, ImageData::U16(vec![
+        32768, 40959, 65535, 32768, 32768, 65535,
+        32768, 49151, 65535, 32768, 40959, 65535,
+    ]));
+}
@@ -0,0 +1,114 @@
+
+extern crate rust_tiff as tiff;
+
+use std::io::{Cursor, ErrorKind};
+
+use tiff::{IFDEntry, Image, ImageData, PhotometricInterpretation, TIFFByteOrder, TIFFEditor, TIFFReader,
+           TIFFTag, TIFFWriter};
+
+fn fixture() -> Vec<u8> {
+    std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap()
+}
+
+fn decode(data: Vec<u8>, index: usize) -> (Box<tiff::TIFF>, Image) {
+    let mut cursor = Cursor::new(data);
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let image = TIFFReader.read_image(&mut cursor, &t.ifds[index]).unwrap();
+    (t, image)
+}
+
+#[test]
+fn test_add_tags_relinks() {
+    let original = fixture();
+    let (before, image) = decode(original.clone(), 0);
+
+    let mut editor = TIFFEditor::open(Cursor::new(original.clone())).unwrap();
+    editor.tiff.ifds[0].set(IFDEntry::ascii(TIFFTag::ArtistTag, "A. Photographer"));
+    editor.tiff.ifds[0].set(IFDEntry::ascii(TIFFTag::CopyrightTag, "(c) 2014"));
+    editor.save().unwrap();
+    let edited = editor.into_inner().into_inner();
+
+    // The larger IFD is written at the end of the file
+    let (after, decoded) = decode(edited.clone(), 0);
+    assert!(after.header.ifd_offset > before.header.ifd_offset);
+    assert_eq!(after.ifds[0].entries.len(), before.ifds[0].entries.len() + 2);
+    assert_eq!(after.ifds[0].get_ascii(TIFFTag::ArtistTag), Some("A. Photographer".to_string()));
+    assert_eq!(after.ifds[0].get_ascii(TIFFTag::CopyrightTag), Some("(c) 2014".to_string()));
+    assert_eq!(after.ifds[0].get_u32s(TIFFTag::StripOffsetsTag), before.ifds[0].get_u32s(TIFFTag::StripOffsetsTag));
+    assert_eq!(decoded.data, image.data);
+
+    // Everything before the old end of file, other than the header, is unchanged
+    assert_eq!(&edited[8..original.len()], &original[8..]);
+}
+
+#[test]
+fn test_edit_in_place() {
+    let original = fixture();
+    let (before, image) = decode(original.clone(), 0);
+
+    let mut editor = TIFFEditor::open(Cursor::new(original.clone())).unwrap();
+    let ifd = &mut editor.tiff.ifds[0];
+    ifd.set(IFDEntry::ascii(TIFFTag::DateTimeTag, "2014:01:02 03:04:05"));
+    assert!(ifd.remove(TIFFTag::XMPTag as u16).is_some());
+    assert!(ifd.remove(0x1234).is_none());
+    // Shorter than the fixture's Software, so rewritten over it
+    assert!(ifd.get_ascii(TIFFTag::SoftwareTag).unwrap().len() > "editor".len());
+    ifd.set(IFDEntry::ascii(TIFFTag::SoftwareTag, "editor"));
+    editor.save().unwrap();
+    let edited = editor.into_inner().into_inner();
+
+    let (after, decoded) = decode(edited.clone(), 0);
+    assert_eq!(edited.len(), original.len());
+    assert_eq!(after.header.ifd_offset, before.header.ifd_offset);
+    assert_eq!(after.ifds[0].get_ascii(TIFFTag::DateTimeTag), Some("2014:01:02 03:04:05".to_string()));
+    assert!(after.ifds[0].get(TIFFTag::XMPTag).is_none());
+    assert_eq!(after.ifds[0].get_ascii(TIFFTag::SoftwareTag), Some("editor".to_string()));
+    assert_eq!(decoded.data, image.data);
+}
+
+#[test]
+fn test_edit_later_page() {
+    let pages: Vec<Image> = (1..4u32).map(|n| {
+        Image::new(n * 10, n * 5, 1, PhotometricInterpretation::BlackIsZero,
+                   ImageData::U8((0..n * n * 50).map(|i| i as u8).collect()))
+    }).collect();
+
+    let settings = TIFFWriter::new(TIFFByteOrder::BigEndian);
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = settings.pages(&mut cursor).unwrap();
+        for page in &pages {
+            writer.append(&settings, page).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    let mut editor = TIFFEditor::open(cursor).unwrap();
+    editor.tiff.ifds[1].set(IFDEntry::ascii(TIFFTag::PageNameTag, "second"));
+    editor.save().unwrap();
+    // Saving again without changes leaves the file as it is
+    let saved = editor.into_inner().into_inner();
+    let mut editor = TIFFEditor::open(Cursor::new(saved.clone())).unwrap();
+    editor.save().unwrap();
+    let edited = editor.into_inner().into_inner();
+    assert_eq!(edited, saved);
+
+    let mut cursor = Cursor::new(edited);
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds.len(), 3);
+    assert_eq!(t.ifds[1].get_ascii(TIFFTag::PageNameTag), Some("second".to_string()));
+    for (ifd, page) in t.ifds.iter().zip(&pages) {
+        assert_eq!(TIFFReader.read_image(&mut cursor, ifd).unwrap().data, page.data);
+    }
+}
+
+#[test]
+fn test_added_ifd_rejected() {
+    let original = fixture();
+    let mut editor = TIFFEditor::open(Cursor::new(original.clone())).unwrap();
+    let copy = editor.tiff.ifds[0].clone();
+    editor.tiff.ifds.push(copy);
+    assert_eq!(editor.save().unwrap_err().kind(), ErrorKind::InvalidInput);
+    // Nothing was written
+    assert_eq!(editor.into_inner().into_inner(), original);
+}
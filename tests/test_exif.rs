@@ -0,0 +1,98 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use common::{file_with, one_pixel, push_ifd, short, long, rationals, srationals};
+use tiff::{EXIFTag, TIFFReader, TIFFTag};
+
+// A 1x1 image whose EXIF IFD holds the common picture-taking fields.
+fn photo() -> Vec<u8> {
+    let mut comment = b"UNICODE\0".to_vec();
+    comment.extend("Café".encode_utf16().flat_map(|u| u.to_le_bytes()));
+
+    file_with(|file| {
+        let exif = push_ifd(file, &[
+            (0x829a, 5, 1, rationals(&[(1, 250)])),
+            (0x829d, 5, 1, rationals(&[(28, 10)])),
+            (0x8822, 3, 1, short(3)),
+            (0x8827, 3, 1, short(400)),
+            (0x9000, 7, 4, b"0232".to_vec()),
+            (0x9003, 2, 20, b"2024:05:17 14:03:22\0".to_vec()),
+            (0x9011, 2, 7, b"+02:00\0".to_vec()),
+            (0x9204, 10, 1, srationals(&[(-2, 3)])),
+            (0x9207, 3, 1, short(5)),
+            (0x9209, 3, 1, short(0x19)),
+            (0x920a, 5, 1, rationals(&[(35, 1)])),
+            (0x9286, 7, comment.len() as u32, comment),
+            (0xa405, 3, 1, short(52)),
+            (0xa432, 5, 4, rationals(&[(24, 1), (70, 1), (28, 10), (28, 10)])),
+            (0xa433, 2, 6, b"Lensy\0".to_vec()),
+            (0xa434, 2, 16, b"24-70mm F2.8   \0".to_vec()),
+        ], 0);
+
+        one_pixel(&[
+            (0x0102, 3, 1, short(8)),
+            (0x0106, 3, 1, short(1)),
+            (0x8769, 4, 1, long(exif)),
+        ])
+    })
+}
+
+#[test]
+fn test_exif_fields() {
+    let mut cursor = Cursor::new(photo());
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let exif = TIFFReader.read_exif(&mut cursor, &t.header, &t.ifds[0]).unwrap().unwrap();
+
+    assert_eq!(exif.exif_version(), Some("0232".to_string()));
+    assert_eq!(exif.exposure_time(), Some(0.004));
+    assert_eq!(exif.f_number(), Some(2.8));
+    assert_eq!(exif.exposure_program(), Some(3));
+    assert_eq!(exif.iso(), Some(400));
+    assert_eq!(exif.date_time_original(), Some("2024:05:17 14:03:22".to_string()));
+    assert_eq!(exif.offset_time_original(), Some("+02:00".to_string()));
+    assert_eq!(exif.exposure_bias(), Some(-2.0 / 3.0));
+    assert_eq!(exif.metering_mode(), Some(5));
+    assert_eq!(exif.flash_fired(), Some(true));
+    assert_eq!(exif.focal_length(), Some(35.0));
+    assert_eq!(exif.focal_length_35mm(), Some(52));
+    assert_eq!(exif.lens_specification(), Some([24.0, 70.0, 2.8, 2.8]));
+    assert_eq!(exif.lens_make(), Some("Lensy".to_string()));
+    assert_eq!(exif.lens_model(), Some("24-70mm F2.8".to_string()));
+    assert_eq!(exif.user_comment(), Some("Café".to_string()));
+    assert_eq!(exif.date_time_digitized(), None);
+    assert_eq!(exif.white_balance(), None);
+}
+
+#[test]
+fn test_exif_tags_are_separate() {
+    let mut cursor = Cursor::new(photo());
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let exif = TIFFReader.read_exif(&mut cursor, &t.header, &t.ifds[0]).unwrap().unwrap();
+
+    // The EXIF fields are not mistaken for baseline ones, or vice versa
+    assert!(exif.get(EXIFTag::FNumberTag).is_some());
+    assert!(t.ifds[0].get_raw(EXIFTag::FNumberTag as u16).is_none());
+    assert!(exif.ifd.get(TIFFTag::ImageWidthTag).is_none());
+}
+
+#[test]
+fn test_exif_fixture() {
+    let mut file = std::fs::File::open("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut file).unwrap();
+    let exif = TIFFReader.read_exif(&mut file, &t.header, &t.ifds[0]).unwrap().unwrap();
+
+    assert_eq!(exif.color_space(), Some(1));
+    assert_eq!(exif.pixel_dimensions(), Some((320, 200)));
+    assert_eq!(exif.exposure_time(), None);
+}
+
+#[test]
+fn test_no_exif() {
+    let mut cursor = Cursor::new(common::build_tiff(&[(0x0100, 3, 1, 1), (0x0101, 3, 1, 1)], &[0]));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert!(TIFFReader.read_exif(&mut cursor, &t.header, &t.ifds[0]).unwrap().is_none());
+}
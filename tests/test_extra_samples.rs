@@ -0,0 +1,72 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use tiff::{ExtraSamples, ImageData, TIFFReader};
+
+// Single strip, 8-bit RGBA image.
+fn rgba_tiff(alpha: u16, pixels: &[u8]) -> Vec<u8> {
+    common::build_tiff(&[
+        (0x0100, 3, 1, 2),                      // ImageWidth
+        (0x0101, 3, 1, 1),                      // ImageLength
+        (0x0102, 3, 1, 8),                      // BitsPerSample
+        (0x0106, 3, 1, 2),                      // PhotometricInterpretation
+        (0x0111, 4, 1, 8),                      // StripOffsets
+        (0x0115, 3, 1, 4),                      // SamplesPerPixel
+        (0x0117, 4, 1, pixels.len() as u32),    // StripByteCounts
+        (0x0152, 3, 1, alpha as u32),           // ExtraSamples
+    ], pixels)
+}
+
+#[test]
+fn test_rgb_has_no_extra_samples() {
+    let mut file = std::fs::File::open("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut file).unwrap();
+    let image = TIFFReader.read_image(&mut file, &t.ifds[0]).unwrap();
+    assert_eq!((image.width, image.height), (320, 200));
+    assert_eq!(image.samples_per_pixel, 3);
+    assert!(image.extra_samples.is_empty());
+    assert_eq!(image.alpha_channel(), None);
+    assert_eq!(image.data.len(), 320 * 200 * 3);
+}
+
+#[test]
+fn test_extra_samples_labelled() {
+    for &(value, expected) in &[(0, ExtraSamples::Unspecified),
+                                (1, ExtraSamples::AssociatedAlpha),
+                                (2, ExtraSamples::UnassociatedAlpha)] {
+        let mut cursor = Cursor::new(rgba_tiff(value, &[0; 8]));
+        let t = TIFFReader.read(&mut cursor).unwrap();
+        let image = TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap();
+        assert_eq!(image.extra_samples, vec![expected]);
+        assert_eq!(image.colour_samples(), 3);
+    }
+}
+
+#[test]
+fn test_alpha_conversion() {
+    let pixels = [200, 100, 50, 128,  10, 20, 30, 0];
+    let mut cursor = Cursor::new(rgba_tiff(2, &pixels));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let mut image = TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap();
+    assert_eq!(image.alpha_channel(), Some(3));
+
+    image.premultiply_alpha().unwrap();
+    assert_eq!(image.extra_samples, vec![ExtraSamples::AssociatedAlpha]);
+    assert_eq!(image.data, ImageData::U8(vec![100, 50, 25, 128,  0, 0, 0, 0]));
+
+    image.unpremultiply_alpha().unwrap();
+    assert_eq!(image.extra_samples, vec![ExtraSamples::UnassociatedAlpha]);
+    assert_eq!(image.data, ImageData::U8(vec![199, 100, 50, 128,  0, 0, 0, 0]));
+}
+
+#[test]
+fn test_alpha_conversion_requires_alpha() {
+    let mut cursor = Cursor::new(rgba_tiff(0, &[0; 8]));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let mut image = TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap();
+    assert!(image.premultiply_alpha().is_err());
+}
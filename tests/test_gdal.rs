@@ -0,0 +1,123 @@
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use common::{file_with, short, long, Field};
+use tiff::{GDALMetadata, Image, ImageData, NoData, PhotometricInterpretation, SampleFormat, TIFFReader};
+
+const METADATA: &str = r#"<GDALMetadata>
+  <Item name="AREA_OR_POINT">Area</Item>
+  <Item name="AREA_OR_POINT" domain="OTHER">Point</Item>
+  <Item name="STATISTICS_MEAN" sample="0">12.5</Item>
+  <Item name="SCALE" sample="0" role="scale">0.01</Item>
+  <Item name="OFFSET" sample="0" role="offset">-5</Item>
+  <Item name="UNITTYPE" sample="0" role="unittype">m</Item>
+  <Item name="DESCRIPTION" sample="1" role="description">Quality &amp; flags</Item>
+</GDALMetadata>
+"#;
+
+fn ascii(text: &str) -> Field {
+    let mut data = text.as_bytes().to_vec();
+    data.push(0);
+    (0, 2, data.len() as u32, data)
+}
+
+// A 2x2 signed 16-bit image holding the given samples, with GDAL fields.
+fn gdal_file(samples: &[i16], nodata: &str) -> Vec<u8> {
+    let (_, typ, count, metadata) = ascii(METADATA);
+    let (_, _, nodata_count, nodata) = ascii(nodata);
+    file_with(|file| {
+        file.extend(samples.iter().flat_map(|s| s.to_le_bytes().to_vec()));
+        vec![
+            (0x0100, 3, 1, short(2)),
+            (0x0101, 3, 1, short(2)),
+            (0x0102, 3, 1, short(16)),
+            (0x0106, 3, 1, short(1)),
+            (0x0111, 4, 1, long(10)),
+            (0x0115, 3, 1, short(1)),
+            (0x0117, 4, 1, long(8)),
+            (0x0153, 3, 1, short(2)),
+            (0xa480, typ, count, metadata),
+            (0xa481, typ, nodata_count, nodata),
+        ]
+    })
+}
+
+//----------------------------------------------------------------------------
+
+#[test]
+fn test_gdal_metadata() {
+    let t = TIFFReader.read(&mut Cursor::new(gdal_file(&[0; 4], "-9999"))).unwrap();
+    let metadata = t.ifds[0].gdal_metadata().unwrap().unwrap();
+
+    assert_eq!(metadata.get("AREA_OR_POINT"), Some("Area"));
+    assert_eq!(metadata.get_in_domain("OTHER", "AREA_OR_POINT"), Some("Point"));
+    assert_eq!(metadata.get("STATISTICS_MEAN"), None);
+
+    assert_eq!(metadata.bands.len(), 2);
+    assert_eq!(metadata.band(0).len(), 4);
+    assert_eq!(metadata.band_get(0, "STATISTICS_MEAN"), Some("12.5"));
+    assert_eq!(metadata.band_scale(0), Some(0.01));
+    assert_eq!(metadata.band_offset(0), Some(-5.0));
+    assert_eq!(metadata.band_unit(0), Some("m"));
+    assert_eq!(metadata.band_description(1), Some("Quality & flags"));
+    assert_eq!(metadata.band_scale(1), None);
+    assert!(metadata.band(2).is_empty());
+
+    let fixture = std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut Cursor::new(fixture)).unwrap();
+    assert!(t.ifds[0].gdal_metadata().unwrap().is_none());
+    assert!(t.ifds[0].gdal_nodata().is_none());
+}
+
+#[test]
+fn test_malformed_metadata() {
+    assert!(GDALMetadata::parse("<Other/>").is_err());
+    assert!(GDALMetadata::parse("<GDALMetadata><Item>x</Item></GDALMetadata>").is_err());
+    assert!(GDALMetadata::parse("<GDALMetadata><Item name=\"a\" sample=\"x\">1</Item></GDALMetadata>").is_err());
+    assert!(GDALMetadata::parse("<GDALMetadata>").is_err());
+    assert!(GDALMetadata::parse("<GDALMetadata/>").unwrap().dataset.is_empty());
+}
+
+#[test]
+fn test_nodata_types() {
+    let t = TIFFReader.read(&mut Cursor::new(gdal_file(&[0; 4], "-9999"))).unwrap();
+    assert_eq!(t.ifds[0].gdal_nodata(), Some(NoData::Signed(-9999)));
+
+    assert_eq!(NoData::parse("255", SampleFormat::UnsignedInteger, 8), Some(NoData::Unsigned(255)));
+    assert_eq!(NoData::parse("256", SampleFormat::UnsignedInteger, 8), None);
+    assert_eq!(NoData::parse("-1", SampleFormat::UnsignedInteger, 8), None);
+    assert_eq!(NoData::parse("0.5", SampleFormat::UnsignedInteger, 8), None);
+    assert_eq!(NoData::parse("-128.0", SampleFormat::TwosComplementSignedInteger, 8), Some(NoData::Signed(-128)));
+    assert_eq!(NoData::parse("18446744073709551615", SampleFormat::UnsignedInteger, 64),
+               Some(NoData::Unsigned(u64::MAX)));
+    assert_eq!(NoData::parse("-3.4028234663852886e+38", SampleFormat::IEEEFloatingPoint, 32),
+               Some(NoData::Float(-3.4028234663852886e+38)));
+    assert_eq!(NoData::parse("-inf", SampleFormat::IEEEFloatingPoint, 32), Some(NoData::Float(f64::NEG_INFINITY)));
+    assert!(match NoData::parse(" nan\0", SampleFormat::IEEEFloatingPoint, 64) {
+        Some(NoData::Float(f)) => f.is_nan(),
+        _ => false,
+    });
+    assert_eq!(NoData::parse("none", SampleFormat::IEEEFloatingPoint, 32), None);
+}
+
+#[test]
+fn test_validity_mask() {
+    let mut cursor = Cursor::new(gdal_file(&[5, -9999, -9999, 7], "-9999"));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let image = TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap();
+    let nodata = t.ifds[0].gdal_nodata().unwrap();
+    assert_eq!(image.validity_mask(nodata), [true, false, false, true]);
+
+    // A pixel is nodata only if all of its samples are
+    let rgb = Image::new(2, 1, 3, PhotometricInterpretation::RGB, ImageData::U8(vec![0, 0, 0, 0, 9, 0]));
+    assert_eq!(rgb.validity_mask(NoData::Unsigned(0)), [false, true]);
+
+    // Float samples, matching NaN and at single precision
+    let floats = Image::new(3, 1, 1, PhotometricInterpretation::BlackIsZero,
+                            ImageData::F32(vec![f32::NAN, 0.1, 1.0]));
+    assert_eq!(floats.validity_mask(NoData::Float(f64::NAN)), [false, true, true]);
+    assert_eq!(floats.validity_mask(NoData::Float(0.1)), [true, false, true]);
+}
@@ -0,0 +1,167 @@
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use common::{close, doubles, one_pixel, shorts, single_ifd_file, Field};
+use tiff::{AffineTransform, GeoKey, GeoKeyDirectory, GeoKeyValue, GeoTIFF, Image, ImageData, ModelType,
+           PhotometricInterpretation, RasterType, TIFFByteOrder, TIFFEditor, TIFFReader, TIFFWriter, IFD};
+
+// The first IFD of a 1x1 image with the given extra fields.
+fn with_fields(fields: &[Field]) -> IFD {
+    let file = single_ifd_file(&one_pixel(fields));
+    TIFFReader.read(&mut Cursor::new(file)).unwrap().ifds[0].clone()
+}
+
+// WGS 84 / UTM zone 33N with 30 m pixels
+fn utm_fields() -> Vec<Field> {
+    let directory = [
+        1, 1, 0, 5,
+        1024, 0, 1, 1,
+        1025, 0, 1, 1,
+        1026, 34737, 22, 0,
+        3072, 0, 1, 32633,
+        3076, 0, 1, 9001,
+    ];
+    let ascii = b"WGS 84 / UTM zone 33N|\0";
+    vec![
+        (0x830e, 12, 3, doubles(&[30.0, 30.0, 0.0])),
+        (0x8482, 12, 6, doubles(&[0.0, 0.0, 0.0, 440720.0, 3751320.0, 0.0])),
+        (0x87af, 3, directory.len() as u32, shorts(&directory)),
+        (0x87b1, 2, ascii.len() as u32, ascii.to_vec()),
+    ]
+}
+
+fn close_to(a: (f64, f64), b: (f64, f64)) -> bool {
+    close(a.0, b.0) && close(a.1, b.1)
+}
+
+//----------------------------------------------------------------------------
+
+#[test]
+fn test_read_geokeys() {
+    let geo = with_fields(&utm_fields()).geotiff().unwrap().unwrap();
+    assert_eq!(geo.pixel_scale, Some([30.0, 30.0, 0.0]));
+    assert_eq!(geo.tiepoints.len(), 1);
+    assert_eq!(geo.transformation, None);
+
+    let keys = &geo.keys;
+    assert_eq!((keys.version, keys.revision), (1, (1, 0)));
+    assert_eq!(keys.model_type(), Some(ModelType::Projected));
+    assert_eq!(keys.raster_type(), RasterType::PixelIsArea);
+    assert_eq!(keys.citation(), Some("WGS 84 / UTM zone 33N"));
+    assert_eq!(keys.projected_cs_type(), Some(32633));
+    assert_eq!(keys.geographic_type(), None);
+    assert_eq!(keys.epsg(), Some(32633));
+    assert_eq!(keys.linear_units(), Some(9001));
+
+    assert!(with_fields(&[]).geotiff().unwrap().is_none());
+}
+
+#[test]
+fn test_geokey_locations() {
+    // A user-defined geographic CRS with its parameters out of line
+    let directory = [
+        1, 1, 1, 4,
+        1024, 0, 1, 2,
+        2048, 0, 1, 32767,
+        2057, 34736, 1, 1,
+        4096, 34735, 2, 20,
+        4326, 5773,
+    ];
+    let ifd = with_fields(&[
+        (0x87af, 3, directory.len() as u32, shorts(&directory)),
+        (0x87b0, 12, 2, doubles(&[298.257223563, 6378137.0])),
+    ]);
+    let keys = ifd.geotiff().unwrap().unwrap().keys;
+    assert_eq!(keys.model_type(), Some(ModelType::Geographic));
+    assert_eq!(keys.geographic_type(), None);
+    assert_eq!(keys.get_short(GeoKey::GeographicTypeGeoKey), Some(32767));
+    assert_eq!(keys.get_double(GeoKey::GeogSemiMajorAxisGeoKey), Some(6378137.0));
+    assert_eq!(keys.get(GeoKey::VerticalCSTypeGeoKey), Some(&GeoKeyValue::Shorts(vec![4326, 5773])));
+
+    // Values outside their fields
+    let bad = [1, 1, 0, 1, 2057, 34736, 1, 5];
+    assert!(with_fields(&[(0x87af, 3, 8, shorts(&bad))]).geotiff().is_err());
+    assert!(GeoKeyDirectory::parse(&[1, 1, 0, 2, 1024, 0, 1, 1], &[], &[]).is_err());
+}
+
+#[test]
+fn test_affine_transform() {
+    let geo = with_fields(&utm_fields()).geotiff().unwrap().unwrap();
+    let transform = geo.transform().unwrap();
+    assert_eq!(transform.pixel_to_model(0.0, 0.0), (440720.0, 3751320.0));
+    assert_eq!(transform.pixel_to_model(10.0, 20.0), (441020.0, 3750720.0));
+    assert!(close_to(transform.model_to_pixel(441020.0, 3750720.0).unwrap(), (10.0, 20.0)));
+
+    // A rotated transform, given as ModelTransformation
+    let (sin, cos) = 0.5f64.sin_cos();
+    let rotated = AffineTransform { matrix: [[10.0 * cos, -10.0 * sin, 500.0], [10.0 * sin, 10.0 * cos, 700.0]] };
+    let mut geo = GeoTIFF::new(GeoKeyDirectory::default());
+    geo.set_transform(&rotated);
+    assert!(geo.tiepoints.is_empty());
+    assert!(geo.transformation.is_some());
+    assert_eq!(geo.transform(), Some(rotated));
+    let (x, y) = rotated.pixel_to_model(3.0, 4.0);
+    assert!(close_to(rotated.model_to_pixel(x, y).unwrap(), (3.0, 4.0)));
+
+    // A north-up transform becomes a tiepoint and pixel scale
+    geo.set_transform(&transform);
+    assert_eq!(geo.transformation, None);
+    assert_eq!(geo.pixel_scale, Some([30.0, 30.0, 0.0]));
+    assert_eq!(geo.transform(), Some(transform));
+
+    assert!(AffineTransform { matrix: [[1.0, 2.0, 0.0], [2.0, 4.0, 0.0]] }.inverse().is_none());
+}
+
+#[test]
+fn test_write_geotiff() {
+    let mut keys = GeoKeyDirectory::default();
+    keys.set(GeoKey::GTModelTypeGeoKey, GeoKeyValue::Shorts(vec![1]));
+    keys.set(GeoKey::GTRasterTypeGeoKey, GeoKeyValue::Shorts(vec![2]));
+    keys.set(GeoKey::GTCitationGeoKey, GeoKeyValue::Ascii("Custom".to_string()));
+    keys.set(GeoKey::ProjectedCSTypeGeoKey, GeoKeyValue::Shorts(vec![32633]));
+    keys.set(GeoKey::ProjFalseEastingGeoKey, GeoKeyValue::Doubles(vec![500000.0]));
+    keys.set(GeoKey::VerticalCSTypeGeoKey, GeoKeyValue::Shorts(vec![1, 2, 3]));
+    let mut geo = GeoTIFF::new(keys);
+    geo.set_transform(&AffineTransform { matrix: [[2.0, 0.0, 100.0], [0.0, -2.0, 200.0]] });
+
+    for &byte_order in [TIFFByteOrder::LittleEndian, TIFFByteOrder::BigEndian].iter() {
+        let image = Image::new(2, 2, 1, PhotometricInterpretation::BlackIsZero, ImageData::U8(vec![1, 2, 3, 4]));
+        let mut cursor = Cursor::new(Vec::new());
+        let mut writer = TIFFWriter::new(byte_order).strips(&mut cursor, &image).unwrap();
+        for entry in geo.entries() {
+            writer.set_tag(entry).unwrap();
+        }
+        writer.push_rows(&image.data).unwrap();
+        writer.finish().unwrap();
+
+        let t = TIFFReader.read(&mut cursor).unwrap();
+        let read = t.ifds[0].geotiff().unwrap().unwrap();
+        assert_eq!(read, geo);
+        assert_eq!(read.keys.raster_type(), RasterType::PixelIsPoint);
+    }
+}
+
+#[test]
+fn test_edit_geotiff() {
+    let data = std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let mut editor = TIFFEditor::open(Cursor::new(data)).unwrap();
+    assert!(editor.tiff.ifds[0].geotiff().unwrap().is_none());
+
+    let mut keys = GeoKeyDirectory::default();
+    keys.set(GeoKey::GTModelTypeGeoKey, GeoKeyValue::Shorts(vec![2]));
+    keys.set(GeoKey::GeographicTypeGeoKey, GeoKeyValue::Shorts(vec![4326]));
+    let mut geo = GeoTIFF::new(keys);
+    geo.set_transform(&AffineTransform { matrix: [[0.01, 0.0, 151.0], [0.0, -0.01, -33.0]] });
+    editor.tiff.ifds[0].set_geotiff(&geo);
+    editor.save().unwrap();
+
+    let mut cursor = Cursor::new(editor.into_inner().into_inner());
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let read = t.ifds[0].geotiff().unwrap().unwrap();
+    assert_eq!(read, geo);
+    assert_eq!(read.keys.epsg(), Some(4326));
+    TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap();
+}
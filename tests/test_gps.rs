@@ -0,0 +1,85 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use common::{close, file_with, one_pixel, push_ifd, long, rationals, Field};
+use tiff::{GPSTag, TIFFReader, GPS};
+
+// A 1x1 image with the given GPS fields.
+fn gps(fields: &[Field]) -> GPS {
+    let file = file_with(|file| {
+        let gps = push_ifd(file, fields, 0);
+        one_pixel(&[(0x8825, 4, 1, long(gps))])
+    });
+
+    let mut cursor = Cursor::new(file);
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    TIFFReader.read_gps(&mut cursor, &t.header, &t.ifds[0]).unwrap().unwrap()
+}
+
+#[test]
+fn test_gps_fields() {
+    let gps = gps(&[
+        (0x0000, 1, 4, vec![2, 3, 0, 0]),
+        (0x0001, 2, 2, b"S\0".to_vec()),
+        (0x0002, 5, 3, rationals(&[(33, 1), (51, 1), (5472, 100)])),
+        (0x0003, 2, 2, b"E\0".to_vec()),
+        (0x0004, 5, 3, rationals(&[(151, 1), (12, 1), (3060, 100)])),
+        (0x0005, 1, 1, vec![1]),
+        (0x0006, 5, 1, rationals(&[(125, 10)])),
+        (0x0007, 5, 3, rationals(&[(4, 1), (30, 1), (15, 1)])),
+        (0x000a, 2, 2, b"3\0".to_vec()),
+        (0x000b, 5, 1, rationals(&[(13, 10)])),
+        (0x000c, 2, 2, b"N\0".to_vec()),
+        (0x000d, 5, 1, rationals(&[(10, 1)])),
+        (0x001d, 2, 11, b"2024:05:17\0".to_vec()),
+    ]);
+
+    assert_eq!(gps.version(), Some([2, 3, 0, 0]));
+    assert!(close(gps.latitude().unwrap(), -(33.0 + 51.0 / 60.0 + 54.72 / 3600.0)));
+    assert!(close(gps.longitude().unwrap(), 151.0 + 12.0 / 60.0 + 30.6 / 3600.0));
+    assert_eq!(gps.coordinates(), Some((gps.latitude().unwrap(), gps.longitude().unwrap())));
+    assert_eq!(gps.altitude(), Some(-12.5));
+    assert_eq!(gps.time_stamp(), Some((4.0, 30.0, 15.0)));
+    assert_eq!(gps.date_stamp(), Some("2024:05:17".to_string()));
+    assert_eq!(gps.measure_mode(), Some("3".to_string()));
+    assert_eq!(gps.dop(), Some(1.3));
+    assert!(close(gps.speed_kmh().unwrap(), 18.52));
+    assert!(gps.get(GPSTag::StatusTag).is_none());
+    assert!(gps.get(GPSTag::DOPTag).is_some());
+}
+
+#[test]
+fn test_gps_decimal_minutes() {
+    // Degrees and decimal minutes, west of Greenwich
+    let gps = gps(&[
+        (0x0001, 2, 2, b"N\0".to_vec()),
+        (0x0002, 5, 3, rationals(&[(51, 1), (28_5000, 10000), (0, 1)])),
+        (0x0003, 2, 2, b"W\0".to_vec()),
+        (0x0004, 5, 3, rationals(&[(0, 1), (7_6800, 10000), (0, 1)])),
+    ]);
+    assert!(close(gps.latitude().unwrap(), 51.475));
+    assert!(close(gps.longitude().unwrap(), -0.128));
+    assert_eq!(gps.altitude(), None);
+}
+
+#[test]
+fn test_gps_needs_hemisphere() {
+    let gps = gps(&[
+        (0x0002, 5, 3, rationals(&[(10, 1), (0, 1), (0, 1)])),
+        (0x0003, 2, 2, b"X\0".to_vec()),
+        (0x0004, 5, 3, rationals(&[(10, 1), (0, 1), (0, 0)])),
+    ]);
+    assert_eq!(gps.latitude(), None);
+    assert_eq!(gps.longitude(), None);
+}
+
+#[test]
+fn test_no_gps() {
+    let mut file = std::fs::File::open("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut file).unwrap();
+    assert!(TIFFReader.read_gps(&mut file, &t.header, &t.ifds[0]).unwrap().is_none());
+}
@@ -0,0 +1,121 @@
+extern crate rust_tiff as tiff;
+
+use std::io::Cursor;
+
+use tiff::{ICCColorSpace, ICCProfile, ICCProfileClass, Image, ImageData, PhotometricInterpretation, RenderingIntent,
+           TIFFByteOrder, TIFFEditor, TIFFReader, TIFFWriter};
+
+fn fixture() -> Vec<u8> {
+    std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap()
+}
+
+// A version 4 grey output profile whose only tag is an English and French
+// description.
+fn v4_profile() -> Vec<u8> {
+    let mut mluc = b"mluc\0\0\0\0".to_vec();
+    mluc.extend_from_slice(&2u32.to_be_bytes());
+    mluc.extend_from_slice(&12u32.to_be_bytes());
+    let (fr, en): (Vec<u16>, Vec<u16>) = ("Gris".encode_utf16().collect(), "Grey".encode_utf16().collect());
+    mluc.extend_from_slice(b"frFR");
+    mluc.extend_from_slice(&8u32.to_be_bytes());
+    mluc.extend_from_slice(&40u32.to_be_bytes());
+    mluc.extend_from_slice(b"enUS");
+    mluc.extend_from_slice(&8u32.to_be_bytes());
+    mluc.extend_from_slice(&48u32.to_be_bytes());
+    mluc.extend(fr.iter().chain(&en).flat_map(|u| u.to_be_bytes().to_vec()));
+
+    let mut data = vec![0; 128];
+    data[4..8].copy_from_slice(b"lcms");
+    data[8..10].copy_from_slice(&[4, 0x30]);
+    data[12..16].copy_from_slice(b"prtr");
+    data[16..20].copy_from_slice(b"GRAY");
+    data[20..24].copy_from_slice(b"Lab ");
+    data[36..40].copy_from_slice(b"acsp");
+    data[64..68].copy_from_slice(&2u32.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(b"desc");
+    data.extend_from_slice(&144u32.to_be_bytes());
+    data.extend_from_slice(&(mluc.len() as u32).to_be_bytes());
+    data.extend_from_slice(&mluc);
+    let size = data.len() as u32;
+    data[0..4].copy_from_slice(&size.to_be_bytes());
+    data
+}
+
+//----------------------------------------------------------------------------
+
+#[test]
+fn test_fixture_profile() {
+    let mut cursor = Cursor::new(fixture());
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds[0].icc_profile().unwrap().len(), 3144);
+
+    let profile = t.ifds[0].icc().unwrap().unwrap();
+    assert_eq!(profile.version, (2, 1, 0));
+    assert_eq!(profile.class, ICCProfileClass::Display);
+    assert_eq!(profile.color_space, ICCColorSpace::RGB);
+    assert_eq!(profile.pcs, ICCColorSpace::XYZ);
+    assert_eq!(profile.rendering_intent, RenderingIntent::RelativeColorimetric);
+    assert_eq!(profile.description(), Some("sRGB IEC61966-2.1".to_string()));
+    assert_eq!(profile.tag(b"wtpt").map(|t| t.len()), Some(20));
+
+    // Carried by the decoded image
+    let image = TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap();
+    assert_eq!(image.icc_profile.as_deref(), t.ifds[0].icc_profile());
+}
+
+#[test]
+fn test_v4_profile() {
+    let profile = ICCProfile::parse(&v4_profile()).unwrap();
+    assert_eq!(profile.version, (4, 3, 0));
+    assert_eq!(profile.class, ICCProfileClass::Output);
+    assert_eq!(profile.color_space, ICCColorSpace::Gray);
+    assert_eq!(profile.pcs, ICCColorSpace::Lab);
+    assert_eq!(profile.rendering_intent, RenderingIntent::Saturation);
+    assert_eq!(&profile.cmm, b"lcms");
+    assert_eq!(profile.description(), Some("Grey".to_string()));
+    assert!(profile.tag(b"cprt").is_none());
+}
+
+#[test]
+fn test_malformed_profiles() {
+    let data = v4_profile();
+    assert!(ICCProfile::parse(&data[..100]).is_err());
+    assert!(ICCProfile::parse(&data[..data.len() - 1]).is_err());
+    let mut bad = data.clone();
+    bad[36] = b'x';
+    assert!(ICCProfile::parse(&bad).is_err());
+
+    // An mluc record count far beyond the tag, with empty or full records
+    let mut bad = data.clone();
+    bad[152..156].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert_eq!(ICCProfile::parse(&bad).unwrap().description(), Some("Grey".to_string()));
+    bad[156..160].copy_from_slice(&0u32.to_be_bytes());
+    assert_eq!(ICCProfile::parse(&bad).unwrap().description(), None);
+}
+
+#[test]
+fn test_embed_profile_on_write() {
+    let profile = v4_profile();
+    let mut image = Image::new(2, 2, 1, PhotometricInterpretation::BlackIsZero, ImageData::U8(vec![0, 64, 128, 255]));
+    image.icc_profile = Some(profile.clone());
+
+    let mut file = Cursor::new(Vec::new());
+    TIFFWriter::new(TIFFByteOrder::BigEndian).write(&mut file, &image).unwrap();
+    let t = TIFFReader.read(&mut file).unwrap();
+    assert_eq!(t.ifds[0].icc_profile(), Some(&profile[..]));
+    let decoded = TIFFReader.read_image(&mut file, &t.ifds[0]).unwrap();
+    assert_eq!(decoded.icc_profile, image.icc_profile);
+    assert_eq!(decoded.data, image.data);
+}
+
+#[test]
+fn test_replace_profile_by_editing() {
+    let profile = v4_profile();
+    let mut editor = TIFFEditor::open(Cursor::new(fixture())).unwrap();
+    editor.tiff.ifds[0].set_icc_profile(&profile);
+    editor.save().unwrap();
+
+    let t = TIFFReader.read(&mut Cursor::new(editor.into_inner().into_inner())).unwrap();
+    assert_eq!(t.ifds[0].icc().unwrap().unwrap().description(), Some("Grey".to_string()));
+}
@@ -0,0 +1,114 @@
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use common::{one_pixel, single_ifd_file};
+use tiff::{IPTC, IPTCCharset, IPTCTag, TIFFReader, IFD};
+
+// One IIM dataset with a standard length.
+fn dataset(record: u8, number: u8, value: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x1c, record, number];
+    data.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    data.extend_from_slice(value);
+    data
+}
+
+// The first IFD of a 1x1 image with the given IPTC field type and bytes.
+fn with_iptc(typ: u16, data: &[u8]) -> IFD {
+    let size = if typ == 4 { 4 } else { 1 };
+    let file = single_ifd_file(&one_pixel(&[(0x83bb, typ, (data.len() / size) as u32, data.to_vec())]));
+
+    let t = TIFFReader.read(&mut Cursor::new(file)).unwrap();
+    t.ifds[0].clone()
+}
+
+fn stream() -> Vec<u8> {
+    [
+        dataset(1, 90, b"\x1b%G"),
+        dataset(2, 0, &[0, 4]),
+        dataset(2, 5, b"Harbour"),
+        dataset(2, 10, b"3"),
+        dataset(2, 25, b"bridge"),
+        dataset(2, 25, b"opera house"),
+        dataset(2, 55, b"20141124"),
+        dataset(2, 80, "Zoë Smith".as_bytes()),
+        dataset(2, 90, b"Sydney"),
+        dataset(2, 101, b"Australia"),
+        dataset(2, 105, b"Morning on the harbour"),
+        dataset(2, 116, "© 2014".as_bytes()),
+    ].concat()
+}
+
+//----------------------------------------------------------------------------
+
+#[test]
+fn test_fixture_iptc() {
+    let data = std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut Cursor::new(data)).unwrap();
+    let iptc = t.ifds[0].iptc().unwrap().unwrap();
+    assert_eq!(iptc.charset, IPTCCharset::UTF8);
+    assert_eq!(iptc.record_version(), Some(0));
+    assert_eq!(iptc.datasets.len(), 2);
+    assert!(iptc.keywords().is_empty());
+    assert_eq!(iptc.caption(), None);
+}
+
+#[test]
+fn test_iptc_fields() {
+    let iptc = with_iptc(7, &stream()).iptc().unwrap().unwrap();
+    assert_eq!(iptc.charset, IPTCCharset::UTF8);
+    assert_eq!(iptc.record_version(), Some(4));
+    assert_eq!(iptc.object_name(), Some("Harbour".to_string()));
+    assert_eq!(iptc.urgency(), Some(3));
+    assert_eq!(iptc.keywords(), ["bridge", "opera house"]);
+    assert_eq!(iptc.date_created(), Some((2014, 11, 24)));
+    assert_eq!(iptc.by_lines(), ["Zoë Smith"]);
+    assert_eq!(iptc.city(), Some("Sydney".to_string()));
+    assert_eq!(iptc.country_name(), Some("Australia".to_string()));
+    assert_eq!(iptc.headline(), Some("Morning on the harbour".to_string()));
+    assert_eq!(iptc.copyright_notice(), Some("© 2014".to_string()));
+    assert_eq!(iptc.get(IPTCTag::KeywordsTag), Some(&b"bridge"[..]));
+    assert_eq!(iptc.get_all(IPTCTag::KeywordsTag).len(), 2);
+    assert_eq!(iptc.caption(), None);
+}
+
+#[test]
+fn test_iptc_typed_long() {
+    // Padded to a whole number of LONGs
+    let mut data = stream();
+    data.resize(data.len().next_multiple_of(4), 0);
+    let expected = IPTC::parse(&stream()).unwrap();
+    assert_eq!(with_iptc(4, &data).iptc().unwrap().unwrap(), expected);
+
+    // Written as LONGs of the other byte order
+    let swapped: Vec<u8> = data.chunks(4).flat_map(|c| c.iter().rev().cloned().collect::<Vec<_>>()).collect();
+    assert_eq!(with_iptc(4, &swapped).iptc().unwrap().unwrap(), expected);
+}
+
+#[test]
+fn test_iptc_charsets() {
+    // Latin-1, declared or not
+    let latin1 = [dataset(1, 90, b"\x1b.A"), dataset(2, 120, b"Caf\xe9")].concat();
+    assert_eq!(IPTC::parse(&latin1).unwrap().caption(), Some("Café".to_string()));
+    let unspecified = dataset(2, 120, b"Caf\xe9");
+    let iptc = IPTC::parse(&unspecified).unwrap();
+    assert_eq!(iptc.charset, IPTCCharset::Unspecified);
+    assert_eq!(iptc.caption(), Some("Café".to_string()));
+    assert_eq!(IPTC::parse(&dataset(2, 120, "Café".as_bytes())).unwrap().caption(), Some("Café".to_string()));
+}
+
+#[test]
+fn test_iptc_extended_and_malformed() {
+    // An extended dataset with a two byte length field
+    let caption = vec![b'x'; 300];
+    let mut data = vec![0x1c, 2, 120, 0x80, 0x02, 0x01, 0x2c];
+    data.extend_from_slice(&caption);
+    assert_eq!(IPTC::parse(&data).unwrap().caption(), Some("x".repeat(300)));
+
+    assert!(IPTC::parse(&[0x1c, 2, 120, 0, 10, b'a']).is_err());
+    assert!(IPTC::parse(&[0x1c, 2]).is_err());
+    assert!(IPTC::parse(&[0x1d, 2, 120, 0, 0]).is_err());
+    assert!(IPTC::parse(&[0, 0, 0]).unwrap().datasets.is_empty());
+}
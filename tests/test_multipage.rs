@@ -0,0 +1,99 @@
+
+extern crate rust_tiff as tiff;
+
+use std::io::{Cursor, ErrorKind};
+
+use tiff::{BigTIFFMode, Compression, Image, ImageData, PhotometricInterpretation, Predictor, TIFFByteOrder,
+           TIFFReader, TIFFTag, TIFFWriter};
+
+fn pages() -> Vec<(TIFFWriter, Image)> {
+    let grey = Image::new(100, 50, 1, PhotometricInterpretation::BlackIsZero,
+                          ImageData::U8((0..5000).map(|i| (i % 251) as u8).collect()));
+    let rgb = Image::new(30, 40, 3, PhotometricInterpretation::RGB,
+                         ImageData::U16((0..3600).map(|i| (i * 17) as u16).collect()));
+    let mut bilevel = Image::new(13, 7, 1, PhotometricInterpretation::WhiteIsZero,
+                                 ImageData::U8((0..91).map(|i| (i % 3 == 0) as u8).collect()));
+    bilevel.bits_per_sample = 1;
+
+    vec![
+        (TIFFWriter::new(TIFFByteOrder::BigEndian), grey),
+        (TIFFWriter { compression: Compression::LZW, predictor: Predictor::Horizontal,
+                      ..TIFFWriter::new(TIFFByteOrder::BigEndian) }, rgb),
+        (TIFFWriter { compression: Compression::PackBits, ..TIFFWriter::new(TIFFByteOrder::BigEndian) }, bilevel),
+    ]
+}
+
+#[test]
+fn test_write_pages() {
+    let pages = pages();
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = pages[0].0.pages(&mut cursor).unwrap();
+        for (settings, image) in &pages {
+            writer.append(settings, image).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds.len(), 3);
+    for (i, (ifd, (settings, image))) in t.ifds.iter().zip(&pages).enumerate() {
+        assert_eq!(ifd.get_u32(TIFFTag::NewSubfileTypeTag), Some(2));
+        assert_eq!(ifd.get_u32s(TIFFTag::PageNumberTag), Some(vec![i as u32, 3]));
+        assert_eq!(ifd.get_u32(TIFFTag::CompressionTag), Some(settings.compression as u32));
+
+        let decoded = TIFFReader.read_image(&mut cursor, ifd).unwrap();
+        assert_eq!((decoded.width, decoded.height), (image.width, image.height));
+        assert_eq!(decoded.bits_per_sample, image.bits_per_sample);
+        assert_eq!(decoded.data, image.data);
+    }
+    assert_eq!(t.ifds[2].next_offset, 0);
+}
+
+#[test]
+fn test_page_total_unknown_until_finished() {
+    let pages = pages();
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = pages[0].0.pages(&mut cursor).unwrap();
+        writer.append(&pages[0].0, &pages[0].1).unwrap();
+        writer.append(&pages[1].0, &pages[1].1).unwrap();
+    }
+
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds.len(), 2);
+    assert_eq!(t.ifds[1].get_u32s(TIFFTag::PageNumberTag), Some(vec![1, 0]));
+}
+
+#[test]
+fn test_page_byte_order_must_match() {
+    let pages = pages();
+    let mut cursor = Cursor::new(Vec::new());
+    let mut writer = TIFFWriter::default().pages(&mut cursor).unwrap();
+    assert!(writer.append(&pages[0].0, &pages[0].1).is_err());
+}
+
+#[test]
+fn test_page_count_limits() {
+    let settings = TIFFWriter::default();
+    let never = TIFFWriter { bigtiff: BigTIFFMode::Never, ..TIFFWriter::default() };
+    for writer in [&settings, &never].iter() {
+        let mut cursor = Cursor::new(Vec::new());
+        assert_eq!(writer.pages(&mut cursor).unwrap().finish().unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    // PageNumber counts up to 65535 pages
+    let page = Image::new(1, 1, 1, PhotometricInterpretation::BlackIsZero, ImageData::U8(vec![7]));
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = settings.pages(&mut cursor).unwrap();
+        for _ in 0..65535 {
+            writer.append(&settings, &page).unwrap();
+        }
+        assert!(writer.append(&settings, &page).is_err());
+        writer.finish().unwrap();
+    }
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds.len(), 65535);
+    assert_eq!(t.ifds[65534].get_u32s(TIFFTag::PageNumberTag), Some(vec![65534, 65535]));
+}
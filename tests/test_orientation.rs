@@ -0,0 +1,58 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use tiff::{DecodeOptions, ImageData, ImageOrientation, TIFFReader};
+
+// 3 x 2 grayscale image with the given Orientation:
+//     0 1 2
+//     3 4 5
+fn oriented_tiff(orientation: u32) -> Vec<u8> {
+    common::build_tiff(&[
+        (0x0100, 3, 1, 3),              // ImageWidth
+        (0x0101, 3, 1, 2),              // ImageLength
+        (0x0102, 3, 1, 8),              // BitsPerSample
+        (0x0106, 3, 1, 1),              // PhotometricInterpretation
+        (0x0111, 4, 1, 8),              // StripOffsets
+        (0x0112, 3, 1, orientation),    // Orientation
+        (0x0117, 4, 1, 6),              // StripByteCounts
+    ], &[0, 1, 2, 3, 4, 5])
+}
+
+#[test]
+fn test_orientation_normalized() {
+    let expected: [(u32, (u32, u32), [u8; 6]); 8] = [
+        (1, (3, 2), [0, 1, 2, 3, 4, 5]),
+        (2, (3, 2), [2, 1, 0, 5, 4, 3]),
+        (3, (3, 2), [5, 4, 3, 2, 1, 0]),
+        (4, (3, 2), [3, 4, 5, 0, 1, 2]),
+        (5, (2, 3), [0, 3, 1, 4, 2, 5]),
+        (6, (2, 3), [3, 0, 4, 1, 5, 2]),
+        (7, (2, 3), [5, 2, 4, 1, 3, 0]),
+        (8, (2, 3), [2, 5, 1, 4, 0, 3]),
+    ];
+    let options = DecodeOptions { normalize_orientation: true };
+
+    for &(orientation, size, ref pixels) in expected.iter() {
+        let mut cursor = Cursor::new(oriented_tiff(orientation));
+        let t = TIFFReader.read(&mut cursor).unwrap();
+        let image = TIFFReader.read_image_with(&mut cursor, &t.ifds[0], &options).unwrap();
+        assert_eq!((image.width, image.height), size, "orientation {}", orientation);
+        assert_eq!(image.data, ImageData::U8(pixels.to_vec()), "orientation {}", orientation);
+        assert_eq!(image.orientation, ImageOrientation::TopLeft);
+        assert_eq!(image.source_orientation as u32, orientation);
+    }
+}
+
+#[test]
+fn test_orientation_kept_by_default() {
+    let mut cursor = Cursor::new(oriented_tiff(6));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let image = TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap();
+    assert_eq!((image.width, image.height), (3, 2));
+    assert_eq!(image.orientation, ImageOrientation::RightTop);
+    assert_eq!(image.data, ImageData::U8(vec![0, 1, 2, 3, 4, 5]));
+}
@@ -0,0 +1,100 @@
+extern crate rust_tiff as tiff;
+
+use std::io::Cursor;
+
+use tiff::{ImageResourceID, PhotoshopResources, ResolutionUnit, TIFFReader};
+
+// One 8BIM resource block, with the name and data padded to even lengths.
+fn resource(id: u16, name: &str, data: &[u8]) -> Vec<u8> {
+    let mut block = b"8BIM".to_vec();
+    block.extend_from_slice(&id.to_be_bytes());
+    block.push(name.len() as u8);
+    block.extend_from_slice(name.as_bytes());
+    if name.len().is_multiple_of(2) {
+        block.push(0);
+    }
+    block.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    block.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        block.push(0);
+    }
+    block
+}
+
+//----------------------------------------------------------------------------
+
+#[test]
+fn test_fixture_resources() {
+    let data = std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut Cursor::new(data)).unwrap();
+    let resources = t.ifds[0].photoshop_resources().unwrap().unwrap();
+    assert_eq!(resources.resources.len(), 19);
+    assert!(resources.resources.iter().all(|r| &r.signature == b"8BIM" && r.name.is_empty()));
+
+    let info = resources.resolution_info().unwrap();
+    assert_eq!((info.h_res, info.v_res, info.h_res_unit, info.width_unit), (300.0, 300.0, 1, 1));
+    let resolution = info.resolution();
+    assert_eq!((resolution.x, resolution.unit), (300.0, ResolutionUnit::Inch));
+
+    let thumbnail = resources.thumbnail().unwrap();
+    assert_eq!((thumbnail.format, thumbnail.width, thumbnail.height), (1, 160, 100));
+    assert_eq!((thumbnail.bits_per_pixel, thumbnail.planes, thumbnail.bgr), (24, 1, false));
+    assert_eq!(&thumbnail.data[..2], [0xff, 0xd8]);
+
+    let version = resources.version_info().unwrap();
+    assert!(version.has_real_merged_data);
+    assert_eq!(version.writer, "Adobe Photoshop");
+    assert!(version.reader.starts_with("Adobe Photoshop"));
+
+    assert!(!resources.has_layers());
+    assert!(resources.iptc().unwrap().is_none());
+}
+
+#[test]
+fn test_resource_blocks() {
+    let iptc = [0x1c, 2, 120, 0, 5, b'h', b'e', b'l', b'l', b'o'];
+    let mut thumbnail = vec![0; 28];
+    thumbnail[3] = 1;
+    thumbnail[7] = 2;
+    thumbnail[11] = 3;
+    thumbnail.extend_from_slice(&[0xff, 0xd8, 0xff]);
+    let resolution = [0, 0x48, 0, 0, 0, 2, 0, 2, 0, 0x48, 0, 0, 0, 2, 0, 2];
+
+    let data = [
+        resource(0x03ed, "", &resolution),
+        resource(0x0404, "IPTC", &iptc),
+        resource(0x0409, "Old", &thumbnail),
+        resource(0x0400, "", &[0, 1]),
+        resource(0x2710, "Odd", &[1, 2, 3]),
+        vec![0, 0],
+    ].concat();
+    let resources = PhotoshopResources::parse(&data).unwrap();
+
+    assert_eq!(resources.resources.len(), 5);
+    assert_eq!(resources.resources[1].name, "IPTC");
+    assert_eq!(resources.get_raw(0x2710).unwrap().data, [1, 2, 3]);
+    assert_eq!(resources.get(ImageResourceID::LayerState).unwrap().data, [0, 1]);
+    assert!(resources.has_layers());
+
+    let resolution = resources.resolution_info().unwrap().resolution();
+    assert_eq!(resolution.unit, ResolutionUnit::Centimetre);
+    assert!((resolution.x - 72.0 / 2.54).abs() < 1e-9);
+
+    assert_eq!(resources.iptc().unwrap().unwrap().caption(), Some("hello".to_string()));
+
+    let thumbnail = resources.thumbnail().unwrap();
+    assert_eq!((thumbnail.format, thumbnail.width, thumbnail.height, thumbnail.bgr), (1, 2, 3, true));
+    assert_eq!(thumbnail.data, [0xff, 0xd8, 0xff]);
+}
+
+#[test]
+fn test_malformed_resources() {
+    let block = resource(0x03ed, "", &[0; 16]);
+    assert!(PhotoshopResources::parse(&block[..block.len() - 1]).is_err());
+    assert!(PhotoshopResources::parse(&block[..9]).is_err());
+    assert!(PhotoshopResources::parse(&[]).unwrap().resources.is_empty());
+
+    // Too short to decode, but present
+    let resources = PhotoshopResources::parse(&resource(0x03ed, "", &[0; 8])).unwrap();
+    assert!(resources.resolution_info().is_none());
+}
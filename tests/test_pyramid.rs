@@ -0,0 +1,89 @@
+
+extern crate rust_tiff as tiff;
+
+use std::io::Cursor;
+
+use tiff::{Compression, Image, ImageData, PhotometricInterpretation, PyramidOptions, Resampling,
+           TIFFByteOrder, TIFFReader, TIFFTag, TIFFWriter};
+
+fn fixture() -> Image {
+    let mut file = std::fs::File::open("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut file).unwrap();
+    TIFFReader.read_image(&mut file, &t.ifds[0]).unwrap()
+}
+
+#[test]
+fn test_write_pyramid() {
+    let image = fixture();
+    let writer = TIFFWriter { compression: Compression::Deflate, ..TIFFWriter::new(TIFFByteOrder::BigEndian) };
+    let options = PyramidOptions { tile_width: 64, tile_length: 64, factors: vec![2, 4],
+                                   resampling: Resampling::Nearest };
+
+    let mut cursor = Cursor::new(Vec::new());
+    writer.write_pyramid(&mut cursor, &image, &options).unwrap();
+
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds.len(), 3);
+
+    let sizes = [(320, 200), (160, 100), (80, 50)];
+    for (level, ifd) in t.ifds.iter().enumerate() {
+        assert_eq!(ifd.get_u32(TIFFTag::NewSubfileTypeTag).unwrap_or(0), if level > 0 { 1 } else { 0 });
+        assert_eq!(ifd.get_u32(TIFFTag::TileWidthTag), Some(64));
+
+        let decoded = TIFFReader.read_image(&mut cursor, ifd).unwrap();
+        assert_eq!((decoded.width, decoded.height), sizes[level]);
+        if level == 0 {
+            assert_eq!(decoded.data, image.data);
+        } else {
+            let factor = 1 << level;
+            assert_eq!(decoded.data, image.downsample(factor, Resampling::Nearest).data);
+        }
+    }
+
+    // Every IFD precedes the tile data, which starts with the smallest overview
+    let first_tile = |level: usize| *t.ifds[level].get_u32s(TIFFTag::TileOffsetsTag).unwrap().iter().min().unwrap();
+    assert!(t.ifds.iter().all(|ifd| ifd.offset < first_tile(2) as u64));
+    assert!(first_tile(2) < first_tile(1) && first_tile(1) < first_tile(0));
+}
+
+#[test]
+fn test_pyramid_default_factors() {
+    let image = fixture();
+    let options = PyramidOptions { tile_width: 32, tile_length: 32, ..PyramidOptions::default() };
+    assert_eq!(options.factors_for(image.width, image.height), vec![2, 4, 8, 16]);
+
+    let mut cursor = Cursor::new(Vec::new());
+    TIFFWriter::default().write_pyramid(&mut cursor, &image, &options).unwrap();
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds.len(), 5);
+    assert_eq!(t.ifds[4].get_u32(TIFFTag::ImageWidthTag), Some(20));
+    assert_eq!(t.ifds[4].get_u32(TIFFTag::ImageLengthTag), Some(13));
+}
+
+#[test]
+fn test_downsample() {
+    // 3 x 3, reduced by 2: the edge pixels cover a single row or column
+    let image = Image::new(3, 3, 1, PhotometricInterpretation::BlackIsZero,
+                           ImageData::U8(vec![0, 10, 20,  30, 40, 50,  60, 70, 80]));
+    let average = image.downsample(2, Resampling::Average);
+    assert_eq!((average.width, average.height), (2, 2));
+    assert_eq!(average.data, ImageData::U8(vec![20, 35, 65, 80]));
+
+    let nearest = image.downsample(2, Resampling::Nearest);
+    assert_eq!(nearest.data, ImageData::U8(vec![40, 50, 70, 80]));
+
+    let signed = Image::new(2, 1, 1, PhotometricInterpretation::BlackIsZero, ImageData::I16(vec![-3, -4]));
+    assert_eq!(signed.downsample(2, Resampling::Average).data, ImageData::I16(vec![-4]));
+
+    let float = Image::new(2, 1, 1, PhotometricInterpretation::BlackIsZero, ImageData::F32(vec![1.0, 2.0]));
+    assert_eq!(float.downsample(2, Resampling::Average).data, ImageData::F32(vec![1.5]));
+}
+
+#[test]
+fn test_pyramid_rejects_bad_factors() {
+    let image = fixture();
+    for factors in &[vec![1], vec![4, 2], vec![2, 2]] {
+        let options = PyramidOptions { factors: factors.clone(), ..PyramidOptions::default() };
+        assert!(TIFFWriter::default().write_pyramid(&mut Cursor::new(Vec::new()), &image, &options).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use tiff::{ResolutionUnit, TIFFReader};
+
+fn approx(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+#[test]
+fn test_fixture_resolution() {
+    let t = TIFFReader.load("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let ifd = &t.ifds[0];
+    let res = ifd.resolution().unwrap();
+    assert_eq!(res.unit, ResolutionUnit::Inch);
+    assert_eq!(res.dpi(), Some((300.0, 300.0)));
+
+    let (x, y) = res.dots_per_cm().unwrap();
+    assert!(approx(x, 300.0 / 2.54) && approx(y, 300.0 / 2.54));
+
+    let (w, h) = ifd.physical_size(ResolutionUnit::Inch).unwrap();
+    assert!(approx(w, 320.0 / 300.0) && approx(h, 200.0 / 300.0));
+
+    let (w, h) = ifd.physical_size(ResolutionUnit::Centimetre).unwrap();
+    assert!(approx(w, 320.0 / 300.0 * 2.54) && approx(h, 200.0 / 300.0 * 2.54));
+}
+
+// Image with rationals (x_num, x_den) and (y_num, y_den) in centimetres.
+fn resolution_tiff(x: (u32, u32), y: (u32, u32), unit: u32) -> Vec<u8> {
+    let mut rationals = Vec::new();
+    for &v in &[x.0, x.1, y.0, y.1] {
+        rationals.extend_from_slice(&v.to_le_bytes());
+    }
+    common::build_tiff(&[
+        (0x0100, 3, 1, 2480),   // ImageWidth
+        (0x0101, 3, 1, 3508),   // ImageLength
+        (0x011a, 5, 1, 8),      // XResolution
+        (0x011b, 5, 1, 16),     // YResolution
+        (0x0128, 3, 1, unit),   // ResolutionUnit
+    ], &rationals)
+}
+
+#[test]
+fn test_centimetre_resolution() {
+    let mut cursor = Cursor::new(resolution_tiff((1181, 10), (1181, 10), 3));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let ifd = &t.ifds[0];
+
+    let (w, h) = ifd.physical_size(ResolutionUnit::Centimetre).unwrap();
+    assert!(approx(w, 2480.0 / 118.1) && approx(h, 3508.0 / 118.1));
+
+    let (x, _) = ifd.resolution().unwrap().dpi().unwrap();
+    assert!(approx(x, 118.1 * 2.54));
+}
+
+#[test]
+fn test_unitless_and_invalid_resolution() {
+    let mut cursor = Cursor::new(resolution_tiff((1, 1), (2, 1), 1));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let res = t.ifds[0].resolution().unwrap();
+    assert_eq!(res.dpi(), None);
+    assert_eq!(res.pixel_aspect_ratio(), 2.0);
+    assert_eq!(t.ifds[0].physical_size(ResolutionUnit::Inch), None);
+
+    // Zero denominators are treated as missing
+    let mut cursor = Cursor::new(resolution_tiff((300, 0), (300, 0), 2));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds[0].resolution(), None);
+
+    // A missing YResolution follows XResolution
+    let mut cursor = Cursor::new(resolution_tiff((72, 1), (72, 0), 2));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds[0].resolution().unwrap().dpi(), Some((72.0, 72.0)));
+}
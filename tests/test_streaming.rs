@@ -0,0 +1,102 @@
+
+extern crate rust_tiff as tiff;
+
+use std::io::Cursor;
+
+use tiff::{BigTIFFMode, Compression, IFDEntry, Image, ImageData, PhotometricInterpretation, Predictor,
+           TIFFByteOrder, TIFFReader, TIFFTag, TIFFVersion, TIFFWriter};
+
+#[test]
+fn test_stream_rows() {
+    let mut file = std::fs::File::open("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut file).unwrap();
+    let image = TIFFReader.read_image(&mut file, &t.ifds[0]).unwrap();
+    let samples = match image.data { ImageData::U8(ref v) => v.clone(), _ => panic!("expected U8") };
+
+    let settings = TIFFWriter { compression: Compression::LZW, predictor: Predictor::Horizontal,
+                                ..TIFFWriter::new(TIFFByteOrder::BigEndian) };
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = settings.strips(&mut cursor, &image).unwrap();
+        writer.set_tag(IFDEntry::ascii(TIFFTag::SoftwareTag, "scanner")).unwrap();
+        for row in samples.chunks(320 * 3) {
+            writer.push_rows(&ImageData::U8(row.to_vec())).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let ifd = &t.ifds[0];
+    assert_eq!(ifd.get_ascii(TIFFTag::SoftwareTag), Some("scanner".to_string()));
+
+    // The IFD precedes the strips it describes
+    let offsets = ifd.get_u32s(TIFFTag::StripOffsetsTag).unwrap();
+    assert_eq!(offsets.len(), 200usize.div_ceil(8));
+    assert!(offsets.iter().all(|&o| o as u64 > ifd.offset));
+
+    assert_eq!(TIFFReader.read_image(&mut cursor, ifd).unwrap().data, image.data);
+}
+
+#[test]
+fn test_stream_uneven_chunks() {
+    let samples: Vec<u16> = (0..500 * 37).map(|i| (i * 13) as u16).collect();
+    let layout = Image::new(500, 37, 1, PhotometricInterpretation::BlackIsZero, ImageData::U16(Vec::new()));
+
+    for &bigtiff in &[BigTIFFMode::Never, BigTIFFMode::Always] {
+        let settings = TIFFWriter { bigtiff, compression: Compression::PackBits, ..TIFFWriter::default() };
+        let mut cursor = Cursor::new(Vec::new());
+        {
+            let mut writer = settings.strips(&mut cursor, &layout).unwrap();
+            assert_eq!(writer.rows_per_strip(), 8);
+            let mut rest = &samples[..];
+            for &rows in &[3, 10, 1, 8, 15] {
+                let (chunk, tail) = rest.split_at(rows * 500);
+                writer.push_rows(&ImageData::U16(chunk.to_vec())).unwrap();
+                rest = tail;
+            }
+            writer.finish().unwrap();
+        }
+
+        let t = TIFFReader.read(&mut cursor).unwrap();
+        assert_eq!(t.header.version == TIFFVersion::BigTIFF, bigtiff == BigTIFFMode::Always);
+        assert_eq!(TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap().data, ImageData::U16(samples.clone()));
+    }
+}
+
+#[test]
+fn test_stream_single_strip_bilevel() {
+    let samples: Vec<u8> = (0..21 * 5).map(|i| (i % 4 == 1) as u8).collect();
+    let mut layout = Image::new(21, 5, 1, PhotometricInterpretation::WhiteIsZero, ImageData::U8(Vec::new()));
+    layout.bits_per_sample = 1;
+
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = TIFFWriter::default().strips(&mut cursor, &layout).unwrap();
+        writer.push_rows(&ImageData::U8(samples.clone())).unwrap();
+        writer.finish().unwrap();
+    }
+
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds[0].get_u32s(TIFFTag::StripByteCountsTag), Some(vec![15]));
+    assert_eq!(TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap().data, ImageData::U8(samples));
+}
+
+#[test]
+fn test_stream_errors() {
+    let layout = Image::new(4, 3, 1, PhotometricInterpretation::BlackIsZero, ImageData::U8(Vec::new()));
+    let mut cursor = Cursor::new(Vec::new());
+    let mut writer = TIFFWriter::default().strips(&mut cursor, &layout).unwrap();
+
+    assert!(writer.set_tag(IFDEntry::longs(TIFFTag::ImageWidthTag, &[8])).is_err());
+    assert!(writer.push_rows(&ImageData::U8(vec![0; 6])).is_err());
+    assert!(writer.push_rows(&ImageData::U16(vec![0; 4])).is_err());
+    assert!(writer.push_rows(&ImageData::U8(vec![0; 16])).is_err());
+
+    // No rows leaves the fields open
+    writer.push_rows(&ImageData::U8(Vec::new())).unwrap();
+    writer.set_tag(IFDEntry::ascii(TIFFTag::SoftwareTag, "early")).unwrap();
+
+    writer.push_rows(&ImageData::U8(vec![0; 8])).unwrap();
+    assert!(writer.set_tag(IFDEntry::ascii(TIFFTag::SoftwareTag, "late")).is_err());
+    assert!(writer.finish().is_err());
+}
@@ -0,0 +1,126 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::Cursor;
+
+use common::{file_with, push_ifd, short, long, Field};
+use tiff::{ImageData, TIFFReader, TIFFTag};
+
+// One row of 8-bit greyscale, `width` pixels wide, with the given SubIFDs.
+fn fields(width: u16, strip_offset: u32, sub_ifds: &[u32]) -> Vec<Field> {
+    let mut fields = vec![
+        (0x0100, 3, 1, short(width)),
+        (0x0101, 3, 1, short(1)),
+        (0x0102, 3, 1, short(8)),
+        (0x0106, 3, 1, short(1)),
+        (0x0111, 4, 1, long(strip_offset)),
+        (0x0117, 4, 1, long(width as u32)),
+    ];
+    if !sub_ifds.is_empty() {
+        let offsets = sub_ifds.iter().flat_map(|o| o.to_le_bytes().to_vec()).collect();
+        fields.push((0x014a, 13, sub_ifds.len() as u32, offsets));
+    }
+    fields
+}
+
+// main ─┬─ a ── a2 (chained)
+//       └─ b ─── c
+// The pixels of each image are its width repeated.
+fn tree(loops: bool) -> Vec<u8> {
+    let mut file = vec![0x49, 0x49, 0x2a, 0x00, 0, 0, 0, 0];
+    let pixels = |file: &mut Vec<u8>, width: u8| {
+        let offset = file.len() as u32;
+        file.extend(vec![width; width as usize]);
+        file.resize(file.len().next_multiple_of(2), 0);
+        offset
+    };
+    let (p1, p2, p3, p4, p5) = (pixels(&mut file, 1), pixels(&mut file, 2), pixels(&mut file, 3),
+                                pixels(&mut file, 4), pixels(&mut file, 5));
+
+    // With `loops`, c lists itself and a2 chains back to a
+    let c_subs = if loops { vec![file.len() as u32] } else { vec![] };
+    let c = push_ifd(&mut file, &fields(1, p1, &c_subs), 0);
+    let a2 = push_ifd(&mut file, &fields(2, p2, &[]), 0);
+    let b = push_ifd(&mut file, &fields(3, p3, &[c]), 0);
+    let a = push_ifd(&mut file, &fields(4, p4, &[]), a2);
+    if loops {
+        let next = a2 as usize + 2 + 12 * 6;
+        file[next..next + 4].copy_from_slice(&a.to_le_bytes());
+    }
+    let main = push_ifd(&mut file, &fields(5, p5, &[a, b]), 0);
+    file[4..8].copy_from_slice(&main.to_le_bytes());
+    file
+}
+
+fn width(ifd: &tiff::IFD) -> u32 {
+    ifd.get_u32(TIFFTag::ImageWidthTag).unwrap()
+}
+
+#[test]
+fn test_sub_ifd_tree() {
+    let mut cursor = Cursor::new(tree(false));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.ifds.len(), 1);
+
+    let main = &t.ifds[0];
+    assert_eq!(main.sub_ifds.iter().map(width).collect::<Vec<_>>(), vec![4, 2, 3]);
+    assert_eq!(main.sub_ifds[2].sub_ifds.iter().map(width).collect::<Vec<_>>(), vec![1]);
+    assert!(main.sub_ifds[0].sub_ifds.is_empty());
+
+    let leaf = &main.sub_ifds[2].sub_ifds[0];
+    assert_eq!(TIFFReader.read_image(&mut cursor, leaf).unwrap().data, ImageData::U8(vec![1]));
+    assert_eq!(TIFFReader.read_image(&mut cursor, &main.sub_ifds[1]).unwrap().data, ImageData::U8(vec![2, 2]));
+}
+
+#[test]
+fn test_all_ifds_depth_first() {
+    let mut cursor = Cursor::new(tree(false));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(t.all_ifds().into_iter().map(width).collect::<Vec<_>>(), vec![5, 4, 2, 3, 1]);
+}
+
+#[test]
+fn test_sub_ifd_loops() {
+    let mut cursor = Cursor::new(tree(true));
+    let t = TIFFReader.read(&mut cursor).unwrap();
+
+    // Each IFD is read once, however often it is referenced
+    assert_eq!(t.all_ifds().into_iter().map(width).collect::<Vec<_>>(), vec![5, 4, 2, 3, 1]);
+    assert!(t.ifds[0].sub_ifds[2].sub_ifds[0].sub_ifds.is_empty());
+}
+
+#[test]
+fn test_no_sub_ifds() {
+    let t = TIFFReader.load("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    assert_eq!(t.all_ifds().len(), 1);
+    assert!(t.ifds[0].sub_ifds.is_empty());
+}
+
+#[test]
+fn test_unreadable_sub_ifd() {
+    // A SubIFD past the end of the file is skipped, keeping the others
+    let file = file_with(|file| {
+        let a = push_ifd(file, &fields(1, 8, &[]), 0);
+        fields(2, 8, &[0xffff_fff0, a])
+    });
+
+    let t = TIFFReader.read(&mut Cursor::new(file)).unwrap();
+    assert_eq!(t.ifds[0].sub_ifds.iter().map(width).collect::<Vec<_>>(), vec![1]);
+}
+
+#[test]
+fn test_sub_ifd_depth() {
+    // Each IFD nests the next, far deeper than is followed
+    let file = file_with(|file| {
+        let mut child = push_ifd(file, &fields(1, 8, &[]), 0);
+        for _ in 0..99 {
+            child = push_ifd(file, &fields(1, 8, &[child]), 0);
+        }
+        fields(1, 8, &[child])
+    });
+
+    let t = TIFFReader.read(&mut Cursor::new(file)).unwrap();
+    assert_eq!(t.all_ifds().len(), 17);
+}
@@ -0,0 +1,147 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::{Cursor, ErrorKind};
+
+use tiff::{Compression, Image, ImageData, PhotometricInterpretation, Predictor, TIFFByteOrder,
+           TIFFReader, TIFFTag, TIFFWriter};
+
+// The samples of the w x h region of an image at (x, y), cropped to the
+// image's width, height and samples per pixel.
+fn region<T: Copy>(samples: &[T], (width, height, spp): (usize, usize, usize),
+                   (x, y, w, h): (usize, usize, usize, usize)) -> Vec<T> {
+    let mut result = Vec::new();
+    for row in y..(y + h).min(height) {
+        let start = (row * width + x) * spp;
+        let end = (row * width + (x + w).min(width)) * spp;
+        result.extend_from_slice(&samples[start..end]);
+    }
+    result
+}
+
+fn read_back(cursor: &mut Cursor<Vec<u8>>) -> (Box<tiff::TIFF>, Image) {
+    let t = TIFFReader.read(cursor).unwrap();
+    let image = TIFFReader.read_image(cursor, &t.ifds[0]).unwrap();
+    (t, image)
+}
+
+#[test]
+fn test_write_tiles_any_order() {
+    let mut file = std::fs::File::open("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut file).unwrap();
+    let image = TIFFReader.read_image(&mut file, &t.ifds[0]).unwrap();
+    let samples = match image.data { ImageData::U8(ref v) => v.clone(), _ => panic!("expected U8") };
+
+    let settings = TIFFWriter { compression: Compression::LZW, predictor: Predictor::Horizontal,
+                                ..TIFFWriter::new(TIFFByteOrder::BigEndian) };
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = settings.tiles(&mut cursor, &image, 64, 48).unwrap();
+        assert_eq!((writer.tiles_across(), writer.tiles_down()), (5, 5));
+
+        // Bottom to top, right to left; edge tiles cropped to the image
+        for row in (0..5).rev() {
+            for column in (0..5).rev() {
+                let tile = region(&samples, (320, 200, 3), (column * 64, row * 48, 64, 48));
+                writer.write_tile(column as u32, row as u32, &ImageData::U8(tile)).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    let (t, decoded) = read_back(&mut cursor);
+    let ifd = &t.ifds[0];
+    assert_eq!(ifd.get_u32(TIFFTag::TileWidthTag), Some(64));
+    assert_eq!(ifd.get_u32(TIFFTag::TileLengthTag), Some(48));
+    assert_eq!(ifd.get_u32s(TIFFTag::TileOffsetsTag).unwrap().len(), 25);
+    assert!(ifd.get(TIFFTag::StripOffsetsTag).is_none());
+
+    // Offsets follow the row-major tile order, not the order written
+    let offsets = ifd.get_u32s(TIFFTag::TileOffsetsTag).unwrap();
+    assert!(offsets[0] > offsets[24]);
+
+    assert_eq!((decoded.width, decoded.height), (320, 200));
+    assert_eq!(decoded.data, image.data);
+}
+
+#[test]
+fn test_write_padded_tiles() {
+    let samples: Vec<u16> = (0..40 * 20).map(|i| (i * 7) as u16).collect();
+    let layout = Image::new(40, 20, 1, PhotometricInterpretation::BlackIsZero, ImageData::U16(Vec::new()));
+
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = TIFFWriter::default().tiles(&mut cursor, &layout, 32, 16).unwrap();
+        for row in 0..2 {
+            for column in 0..2 {
+                // Whole tiles, padded by the caller
+                let mut tile = vec![0xffff; 32 * 16];
+                let part = region(&samples, (40, 20, 1), (column * 32, row * 16, 32, 16));
+                let width = part.len() / 16.min(20 - row * 16);
+                for (r, source) in part.chunks(width).enumerate() {
+                    tile[r * 32..r * 32 + width].copy_from_slice(source);
+                }
+                writer.write_tile(column as u32, row as u32, &ImageData::U16(tile)).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    let (_, decoded) = read_back(&mut cursor);
+    assert_eq!(decoded.data, ImageData::U16(samples));
+}
+
+#[test]
+fn test_write_bilevel_tiles() {
+    let samples: Vec<u8> = (0..20 * 18).map(|i| (i % 5 == 0) as u8).collect();
+    let mut layout = Image::new(20, 18, 1, PhotometricInterpretation::WhiteIsZero, ImageData::U8(Vec::new()));
+    layout.bits_per_sample = 1;
+
+    let settings = TIFFWriter { compression: Compression::PackBits, ..TIFFWriter::default() };
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = settings.tiles(&mut cursor, &layout, 16, 16).unwrap();
+        for &(column, row) in &[(1, 1), (0, 0), (1, 0), (0, 1)] {
+            let tile = region(&samples, (20, 18, 1), (column * 16, row * 16, 16, 16));
+            writer.write_tile(column as u32, row as u32, &ImageData::U8(tile)).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    let (_, decoded) = read_back(&mut cursor);
+    assert_eq!(decoded.bits_per_sample, 1);
+    assert_eq!(decoded.data, ImageData::U8(samples));
+}
+
+#[test]
+fn test_tile_errors() {
+    let layout = Image::new(40, 20, 1, PhotometricInterpretation::BlackIsZero, ImageData::U8(Vec::new()));
+    let mut cursor = Cursor::new(Vec::new());
+    assert!(TIFFWriter::default().tiles(&mut cursor, &layout, 24, 16).is_err());
+
+    let mut writer = TIFFWriter::default().tiles(&mut cursor, &layout, 16, 16).unwrap();
+    assert!(writer.write_tile(3, 0, &ImageData::U8(vec![0; 256])).is_err());
+    assert!(writer.write_tile(0, 0, &ImageData::U8(vec![0; 100])).is_err());
+    assert!(writer.write_tile(0, 0, &ImageData::U16(vec![0; 256])).is_err());
+    writer.write_tile(0, 0, &ImageData::U8(vec![0; 256])).unwrap();
+    assert!(writer.finish().is_err());
+}
+
+#[test]
+fn test_read_rejects_huge_tiles() {
+    // One tile of 16 bytes cannot hold a 0x0fffffff square image
+    let data = common::build_tiff(&[
+        (0x0100, 4, 1, 0x0fffffff),         // ImageWidth
+        (0x0101, 4, 1, 0x0fffffff),         // ImageLength
+        (0x0102, 3, 1, 8),                  // BitsPerSample
+        (0x0142, 4, 1, 0x10000000),         // TileWidth
+        (0x0143, 4, 1, 0x10000000),         // TileLength
+        (0x0144, 4, 1, 8),                  // TileOffsets
+        (0x0145, 4, 1, 16),                 // TileByteCounts
+    ], &[0xff; 16]);
+    let mut cursor = Cursor::new(data);
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    assert_eq!(TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap_err().kind(), ErrorKind::InvalidData);
+}
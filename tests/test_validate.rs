@@ -0,0 +1,116 @@
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::collections::HashSet;
+use std::io::Cursor;
+
+use common::{short, long, rationals, single_ifd_file, Field};
+use tiff::{validate_required_tags_for, ImageType, TIFFReader, TIFFTag, IFD};
+
+// The first IFD of a file with the given fields.
+fn ifd(fields: &[Field]) -> IFD {
+    TIFFReader.read(&mut Cursor::new(single_ifd_file(fields))).unwrap().ifds[0].clone()
+}
+
+// The fields every stripped image needs, with the given photometric
+fn baseline(photometric: u16) -> Vec<Field> {
+    vec![
+        (0x0100, 3, 1, short(1)),
+        (0x0101, 3, 1, short(1)),
+        (0x0103, 3, 1, short(1)),
+        (0x0106, 3, 1, short(photometric)),
+        (0x0111, 4, 1, long(8)),
+        (0x0116, 3, 1, short(1)),
+        (0x0117, 4, 1, long(1)),
+        (0x011a, 5, 1, rationals(&[(72, 1)])),
+        (0x011b, 5, 1, rationals(&[(72, 1)])),
+        (0x0128, 3, 1, short(2)),
+    ]
+}
+
+fn with(mut fields: Vec<Field>, extra: &[Field]) -> Vec<Field> {
+    fields.extend_from_slice(extra);
+    fields.sort_by_key(|f| f.0);
+    fields
+}
+
+fn tags(tags: &[TIFFTag]) -> Option<HashSet<TIFFTag>> {
+    Some(tags.iter().cloned().collect())
+}
+
+//----------------------------------------------------------------------------
+
+#[test]
+fn test_bilevel_and_grayscale() {
+    let bilevel = ifd(&baseline(0));
+    assert_eq!(bilevel.image_type(), Some(ImageType::Bilevel));
+    assert_eq!(validate_required_tags_for(&ImageType::Bilevel, &bilevel), None);
+    // BitsPerSample has a default for bilevel images only
+    assert_eq!(validate_required_tags_for(&ImageType::Grayscale, &bilevel), tags(&[TIFFTag::BitsPerSampleTag]));
+
+    let grayscale = ifd(&with(baseline(1), &[(0x0102, 3, 1, short(8))]));
+    assert_eq!(grayscale.image_type(), Some(ImageType::Grayscale));
+    assert_eq!(validate_required_tags_for(&ImageType::Grayscale, &grayscale), None);
+
+    // Everything but the image size
+    let sparse = ifd(&[(0x0100, 3, 1, short(1)), (0x0101, 3, 1, short(1))]);
+    assert_eq!(sparse.image_type(), None);
+    assert_eq!(validate_required_tags_for(&ImageType::Bilevel, &sparse), tags(&[
+        TIFFTag::CompressionTag, TIFFTag::PhotometricInterpretationTag, TIFFTag::StripOffsetsTag,
+        TIFFTag::RowsPerStripTag, TIFFTag::StripByteCountsTag, TIFFTag::XResolutionTag,
+        TIFFTag::YResolutionTag, TIFFTag::ResolutionUnitTag,
+    ]));
+}
+
+#[test]
+fn test_palette_and_rgb() {
+    let palette = ifd(&with(baseline(3), &[(0x0102, 3, 1, short(1))]));
+    assert_eq!(palette.image_type(), Some(ImageType::PaletteColour));
+    assert_eq!(validate_required_tags_for(&ImageType::PaletteColour, &palette), tags(&[TIFFTag::ColorMapTag]));
+    let colormap = [0u16, 65535, 0, 65535, 0, 65535].iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+    let palette = ifd(&with(baseline(3), &[(0x0102, 3, 1, short(1)), (0x0140, 3, 6, colormap)]));
+    assert_eq!(validate_required_tags_for(&ImageType::PaletteColour, &palette), None);
+
+    let rgb = ifd(&with(baseline(2), &[(0x0102, 3, 1, short(8))]));
+    assert_eq!(rgb.image_type(), Some(ImageType::RGB));
+    assert_eq!(validate_required_tags_for(&ImageType::RGB, &rgb), tags(&[TIFFTag::SamplesPerPixelTag]));
+
+    // The fixture has every field an RGB image needs
+    let fixture = std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut Cursor::new(fixture)).unwrap();
+    assert_eq!(t.ifds[0].image_type(), Some(ImageType::RGB));
+    assert_eq!(validate_required_tags_for(&ImageType::RGB, &t.ifds[0]), None);
+}
+
+#[test]
+fn test_ycbcr() {
+    let mut fields = with(baseline(6), &[(0x0102, 3, 1, short(8)), (0x0115, 3, 1, short(3))]);
+    let ycbcr = ifd(&fields);
+    assert_eq!(ycbcr.image_type(), Some(ImageType::YCbCr));
+    assert_eq!(validate_required_tags_for(&ImageType::YCbCr, &ycbcr), tags(&[
+        TIFFTag::YCbCrSubsampling, TIFFTag::YCbCrPositioning, TIFFTag::ReferenceBlackWhite,
+    ]));
+
+    fields = with(fields, &[
+        (0x0212, 3, 2, [short(2), short(2)].concat()),
+        (0x0213, 3, 1, short(1)),
+        (0x0214, 5, 6, rationals(&[(0, 1), (255, 1), (128, 1), (255, 1), (128, 1), (255, 1)])),
+    ]);
+    assert_eq!(validate_required_tags_for(&ImageType::YCbCr, &ifd(&fields)), None);
+}
+
+#[test]
+fn test_tiled() {
+    // Tile fields stand in for the strip fields
+    let mut fields: Vec<Field> = baseline(1).into_iter()
+        .filter(|f| f.0 != 0x0111 && f.0 != 0x0116 && f.0 != 0x0117)
+        .collect();
+    fields = with(fields, &[(0x0102, 3, 1, short(8)), (0x0142, 3, 1, short(16)), (0x0143, 3, 1, short(16))]);
+    assert_eq!(validate_required_tags_for(&ImageType::Grayscale, &ifd(&fields)), tags(&[
+        TIFFTag::TileOffsetsTag, TIFFTag::TileByteCountsTag,
+    ]));
+
+    fields = with(fields, &[(0x0144, 4, 1, long(8)), (0x0145, 4, 1, long(256))]);
+    assert_eq!(validate_required_tags_for(&ImageType::Grayscale, &ifd(&fields)), None);
+}
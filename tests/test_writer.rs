@@ -0,0 +1,146 @@
+
+extern crate rust_tiff as tiff;
+
+mod common;
+
+use std::io::{Cursor, ErrorKind};
+
+use tiff::{validate_required_tags_for, Image, ImageData, ImageType, PhotometricInterpretation, TIFFByteOrder,
+           TIFFReader, TIFFTag, TIFFWriter};
+
+#[test]
+fn test_write_grayscale() {
+    let pixels: Vec<u16> = (0..300 * 70).map(|i| (i * 3) as u16).collect();
+    let image = Image::new(300, 70, 1, PhotometricInterpretation::BlackIsZero, ImageData::U16(pixels));
+
+    for &byte_order in &[TIFFByteOrder::LittleEndian, TIFFByteOrder::BigEndian] {
+        let (t, decoded) = common::round_trip(&TIFFWriter::new(byte_order), &image);
+        assert_eq!(t.header.byte_order, byte_order);
+
+        let ifd = &t.ifds[0];
+        assert_eq!(validate_required_tags_for(&ImageType::Grayscale, ifd), None);
+        assert!(ifd.get_u32s(TIFFTag::StripOffsetsTag).unwrap().len() > 1);
+        assert_eq!(decoded.data, image.data);
+        assert_eq!(decoded.photometric, PhotometricInterpretation::BlackIsZero);
+    }
+}
+
+#[test]
+fn test_write_rgb() {
+    let mut file = std::fs::File::open("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap();
+    let t = TIFFReader.read(&mut file).unwrap();
+    let image = TIFFReader.read_image(&mut file, &t.ifds[0]).unwrap();
+
+    let (t, decoded) = common::round_trip(&TIFFWriter::new(TIFFByteOrder::BigEndian), &image);
+    let ifd = &t.ifds[0];
+    assert_eq!(validate_required_tags_for(&ImageType::RGB, ifd), None);
+    assert_eq!(ifd.get_u32(TIFFTag::SamplesPerPixelTag), Some(3));
+    assert_eq!(ifd.get_u32s(TIFFTag::BitsPerSampleTag), Some(vec![8, 8, 8]));
+    assert_eq!(ifd.resolution().unwrap().dpi(), Some((72.0, 72.0)));
+    assert_eq!((decoded.width, decoded.height), (320, 200));
+    assert_eq!(decoded.data, image.data);
+}
+
+#[test]
+fn test_write_bilevel() {
+    let pixels = vec![1, 0, 1, 1, 0, 0, 1, 0, 1,  0, 1, 0, 0, 1, 1, 0, 1, 0];
+    let mut image = Image::new(9, 2, 1, PhotometricInterpretation::WhiteIsZero, ImageData::U8(pixels));
+    image.bits_per_sample = 1;
+
+    let (t, decoded) = common::round_trip(&TIFFWriter::default(), &image);
+    assert_eq!(t.ifds[0].get_u32s(TIFFTag::StripByteCountsTag), Some(vec![4]));
+    assert_eq!(decoded.bits_per_sample, 1);
+    assert_eq!(decoded.data, image.data);
+}
+
+#[test]
+fn test_write_rejects_short_buffer() {
+    let image = Image::new(4, 4, 3, PhotometricInterpretation::RGB, ImageData::U8(vec![0; 47]));
+    assert!(TIFFWriter::default().write(&mut Cursor::new(Vec::new()), &image).is_err());
+}
+
+#[test]
+fn test_write_rejects_empty_image() {
+    let settings = TIFFWriter::default();
+    for &(width, height) in [(0, 4), (4, 0), (0, 0)].iter() {
+        let image = Image::new(width, height, 1, PhotometricInterpretation::BlackIsZero, ImageData::U8(Vec::new()));
+        let mut cursor = Cursor::new(Vec::new());
+        assert_eq!(settings.write(&mut cursor, &image).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(settings.strips(&mut cursor, &image).err().unwrap().kind(), ErrorKind::InvalidInput);
+        assert_eq!(settings.tiles(&mut cursor, &image, 16, 16).err().unwrap().kind(), ErrorKind::InvalidInput);
+    }
+}
+
+#[test]
+fn test_sub_byte_samples() {
+    // Samples of 3, 5, 6 and 7 bits straddle byte boundaries
+    for bits in 1..8u16 {
+        let max = (1u16 << bits) - 1;
+        let pixels: Vec<u8> = (0..7 * 3).map(|i| ((i * 5) % (max + 1)) as u8).collect();
+        let mut image = Image::new(7, 3, 1, PhotometricInterpretation::BlackIsZero, ImageData::U8(pixels));
+        image.bits_per_sample = bits;
+
+        let (t, decoded) = common::round_trip(&TIFFWriter::default(), &image);
+        let row_bytes = (7 * bits as u32).div_ceil(8);
+        assert_eq!(t.ifds[0].get_u32s(TIFFTag::StripByteCountsTag), Some(vec![row_bytes * 3]), "{} bits", bits);
+        assert_eq!(decoded.bits_per_sample, bits);
+        assert_eq!(decoded.data, image.data, "{} bits", bits);
+    }
+}
+
+#[test]
+fn test_read_rejects_bad_bits_per_sample() {
+    for &(count, bits) in [(1, 0), (0, 0)].iter() {
+        let data = common::build_tiff(&[
+            (0x0100, 3, 1, 1),              // ImageWidth
+            (0x0101, 3, 1, 1),              // ImageLength
+            (0x0102, 3, count, bits),       // BitsPerSample
+            (0x0111, 4, 1, 8),              // StripOffsets
+            (0x0117, 4, 1, 1),              // StripByteCounts
+        ], &[0xff]);
+        let mut cursor = Cursor::new(data);
+        let t = TIFFReader.read(&mut cursor).unwrap();
+        assert!(TIFFReader.read_image(&mut cursor, &t.ifds[0]).is_err());
+    }
+}
+
+#[test]
+fn test_read_rejects_huge_dimensions() {
+    let read = |entries: &[(u16, u16, u32, u32)]| {
+        let mut cursor = Cursor::new(common::build_tiff(entries, &[0xff; 16]));
+        let t = TIFFReader.read(&mut cursor).unwrap();
+        TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap_err().kind()
+    };
+
+    // 16 bytes of strip cannot hold a 0x0fffffff square image, even
+    // compressed
+    for &compression in [1, 5].iter() {
+        assert_eq!(read(&[
+            (0x0100, 4, 1, 0x0fffffff),     // ImageWidth
+            (0x0101, 4, 1, 0x0fffffff),     // ImageLength
+            (0x0102, 3, 1, 8),              // BitsPerSample
+            (0x0103, 3, 1, compression),    // Compression
+            (0x0111, 4, 1, 8),              // StripOffsets
+            (0x0117, 4, 1, 16),             // StripByteCounts
+        ]), ErrorKind::InvalidData);
+    }
+
+    // Nor can a byte count beyond the end of the file
+    assert_eq!(read(&[
+        (0x0100, 4, 1, 0x0fffffff),         // ImageWidth
+        (0x0101, 4, 1, 0x0fffffff),         // ImageLength
+        (0x0102, 3, 1, 8),                  // BitsPerSample
+        (0x0111, 4, 1, 8),                  // StripOffsets
+        (0x0117, 4, 1, u32::MAX),           // StripByteCounts
+    ]), ErrorKind::InvalidData);
+
+    // The row size overflows
+    assert_eq!(read(&[
+        (0x0100, 4, 1, u32::MAX),           // ImageWidth
+        (0x0101, 3, 1, 1),                  // ImageLength
+        (0x0102, 3, 1, 8),                  // BitsPerSample
+        (0x0111, 4, 1, 8),                  // StripOffsets
+        (0x0115, 4, 1, u32::MAX),           // SamplesPerPixel
+        (0x0117, 4, 1, 16),                 // StripByteCounts
+    ]), ErrorKind::InvalidData);
+}
@@ -0,0 +1,158 @@
+extern crate rust_tiff as tiff;
+
+use std::io::{Cursor, ErrorKind};
+
+use tiff::{TIFFEditor, TIFFReader, XMP, XMPValue};
+use tiff::xmp::{NS_DC, NS_PHOTOSHOP, NS_XMP, NS_XMP_MM};
+
+fn fixture() -> Vec<u8> {
+    std::fs::read("resources/text_w320_h200_b8_cn_prgb.tiff").unwrap()
+}
+
+const NS_CUSTOM: &str = "http://example.com/ns/custom/1.0/";
+
+const PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <!-- properties as attributes -->
+  <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+      xmp:Rating="4" xmp:Label='Tom &amp; Jerry &#x263A;'/>
+  <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/"
+      xmlns:c="http://example.com/ns/custom/1.0/">
+   <dc:creator><rdf:Seq><rdf:li>First</rdf:li><rdf:li>Second</rdf:li></rdf:Seq></dc:creator>
+   <dc:subject><rdf:Bag><rdf:li>cats</rdf:li><rdf:li>dogs</rdf:li></rdf:Bag></dc:subject>
+   <dc:title>
+    <rdf:Alt>
+     <rdf:li xml:lang="fr">Titre</rdf:li>
+     <rdf:li xml:lang="x-default">Title</rdf:li>
+    </rdf:Alt>
+   </dc:title>
+   <c:Note><![CDATA[a <raw> note]]></c:Note>
+   <c:Source rdf:resource="http://example.com/source"/>
+   <c:Camera c:Make="Acme" c:Model="One"/>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+//----------------------------------------------------------------------------
+
+#[test]
+fn test_reads_fixture_packet() {
+    let t = TIFFReader.read(&mut Cursor::new(fixture())).unwrap();
+    let packet = t.ifds[0].xmp_packet().unwrap();
+    assert!(packet.starts_with("<?xpacket begin="));
+    assert!(packet.ends_with("<?xpacket end=\"w\"?>"));
+
+    let xmp = t.ifds[0].xmp().unwrap().unwrap();
+    assert_eq!(xmp.get_text(NS_XMP, "CreatorTool"), Some("Adobe Photoshop CC 2014 (Macintosh)"));
+    assert_eq!(xmp.get_text(NS_XMP, "CreateDate"), Some("2014-11-24T15:32:05+11:00"));
+    assert_eq!(xmp.get_text(NS_XMP_MM, "InstanceID"), Some("xmp.iid:26999410-c903-4472-b913-84147a2125ef"));
+    assert_eq!(xmp.get_text(NS_DC, "format"), Some("image/tiff"));
+    assert_eq!(xmp.get_text(NS_PHOTOSHOP, "ColorMode"), Some("3"));
+    assert_eq!(xmp.get_text(NS_PHOTOSHOP, "ICCProfile"), Some("sRGB IEC61966-2.1"));
+    assert_eq!(xmp.prefixes.get(NS_PHOTOSHOP).map(|p| p.as_str()), Some("photoshop"));
+
+    // A sequence of structs in the stEvt namespace
+    let history = match xmp.get(NS_XMP_MM, "History") {
+        Some(XMPValue::Seq(items)) => items,
+        other => panic!("History is {:?}", other),
+    };
+    let actions: Vec<_> = history.iter().map(|event| match *event {
+        XMPValue::Struct(ref fields) => fields.iter()
+            .find(|&(name, _)| name.name == "action" && name.namespace.ends_with("sType/ResourceEvent#"))
+            .and_then(|(_, v)| v.as_text()).unwrap().to_string(),
+        ref other => panic!("event is {:?}", other),
+    }).collect();
+    assert_eq!(actions, ["created", "saved", "saved"]);
+}
+
+#[test]
+fn test_parses_rdf_forms() {
+    let xmp = XMP::parse(PACKET).unwrap();
+
+    assert_eq!(xmp.get_text(NS_XMP, "Rating"), Some("4"));
+    assert_eq!(xmp.get_text(NS_XMP, "Label"), Some("Tom & Jerry \u{263a}"));
+    assert_eq!(xmp.get(NS_DC, "creator").unwrap().as_texts(), ["First", "Second"]);
+    assert!(matches!(xmp.get(NS_DC, "subject"), Some(XMPValue::Bag(_))));
+    assert_eq!(xmp.get_text(NS_DC, "title"), Some("Title"));
+    match xmp.get(NS_DC, "title") {
+        Some(XMPValue::Alt(items)) => assert_eq!(items[0].0.as_deref(), Some("fr")),
+        other => panic!("title is {:?}", other),
+    }
+
+    assert_eq!(xmp.get_text(NS_CUSTOM, "Note"), Some("a <raw> note"));
+    assert_eq!(xmp.get_text(NS_CUSTOM, "Source"), Some("http://example.com/source"));
+    match xmp.get(NS_CUSTOM, "Camera") {
+        Some(XMPValue::Struct(fields)) => assert_eq!(fields.len(), 2),
+        other => panic!("Camera is {:?}", other),
+    }
+    assert_eq!(xmp.prefixes.get(NS_CUSTOM).map(|p| p.as_str()), Some("c"));
+}
+
+#[test]
+fn test_rejects_malformed_packets() {
+    for packet in ["", "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">", "<a></b>", "<a>&bogus;</a>",
+                   "<q:a/>", "<a b=c/>", "<a/>"].iter() {
+        assert!(XMP::parse(packet).is_err(), "{:?}", packet);
+    }
+
+    // Nesting deep enough to overflow the stack is refused
+    let deep = format!("{}{}", "<a>".repeat(100000), "</a>".repeat(100000));
+    assert!(XMP::parse(&deep).is_err());
+}
+
+#[test]
+fn test_round_trips_through_packet() {
+    let mut xmp = XMP::parse(PACKET).unwrap();
+    xmp.set(NS_PHOTOSHOP, "Headline", XMPValue::Text("<\"quoted\" & escaped>".to_string()));
+    xmp.set("http://example.com/undeclared/", "Value", XMPValue::Text("x".to_string()));
+    assert!(xmp.remove(NS_XMP, "Rating").is_some());
+
+    let packet = xmp.to_packet().unwrap();
+    assert!(packet.ends_with("<?xpacket end=\"w\"?>"));
+    let parsed = XMP::parse(&packet).unwrap();
+    assert_eq!(parsed.properties, xmp.properties);
+
+    // Properties in no namespace cannot be written
+    xmp.set("", "Bare", XMPValue::Text("x".to_string()));
+    assert_eq!(xmp.to_packet().unwrap_err().kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_default_namespace_gets_a_prefix() {
+    let xmp = XMP::parse(r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="">
+   <Rating xmlns="http://ns.adobe.com/xap/1.0/">5</Rating>
+   <Source xmlns="http://example.com/custom/">scan</Source>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#).unwrap();
+
+    let packet = xmp.to_packet().unwrap();
+    assert!(packet.contains("xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\""), "{}", packet);
+    assert!(packet.contains("<xmp:Rating>5</xmp:Rating>"), "{}", packet);
+    assert!(packet.contains("<ns:Source>scan</ns:Source>"), "{}", packet);
+    assert!(!packet.contains("xmlns:=") && !packet.contains("<:"), "{}", packet);
+    assert_eq!(XMP::parse(&packet).unwrap().properties, xmp.properties);
+}
+
+#[test]
+fn test_replaces_packet_on_write() {
+    let original = fixture();
+    let mut editor = TIFFEditor::open(Cursor::new(original)).unwrap();
+    let mut xmp = editor.tiff.ifds[0].xmp().unwrap().unwrap();
+    xmp.set(NS_DC, "creator", XMPValue::Seq(vec![XMPValue::Text("A. Photographer".to_string())]));
+    editor.tiff.ifds[0].set_xmp(&xmp).unwrap();
+    editor.save().unwrap();
+    let edited = editor.into_inner().into_inner();
+
+    let mut cursor = Cursor::new(edited);
+    let t = TIFFReader.read(&mut cursor).unwrap();
+    let after = t.ifds[0].xmp().unwrap().unwrap();
+    assert_eq!(after.get_text(NS_DC, "creator"), Some("A. Photographer"));
+    assert_eq!(after.get_text(NS_XMP, "CreatorTool"), Some("Adobe Photoshop CC 2014 (Macintosh)"));
+    assert_eq!(after.properties, xmp.properties);
+    TIFFReader.read_image(&mut cursor, &t.ifds[0]).unwrap();
+}